@@ -0,0 +1,210 @@
+//! User-defined aliases, with the `alias` and `unalias` built-ins.
+//!
+//! Like variables, the aliases live in one store for the whole process. The
+//! parser replaces a command name that is an alias with its value, so an
+//! alias can stand for a whole pipeline.
+
+use crate::commands::{self, Command};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+fn store() -> &'static RwLock<BTreeMap<String, String>> {
+    static STORE: OnceLock<RwLock<BTreeMap<String, String>>> = OnceLock::new();
+    STORE.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+fn read() -> RwLockReadGuard<'static, BTreeMap<String, String>> {
+    store().read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write() -> RwLockWriteGuard<'static, BTreeMap<String, String>> {
+    store().write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The value of an alias, if there is one by that name.
+pub fn get(name: &str) -> Option<String> {
+    read().get(name).cloned()
+}
+
+pub fn set(name: &str, value: &str) {
+    write().insert(name.to_string(), value.to_string());
+}
+
+/// Removes an alias, returning whether there was one.
+pub fn remove(name: &str) -> bool {
+    write().remove(name).is_some()
+}
+
+/// The aliases as they are now, to put back with [`restore`].
+#[derive(Debug)]
+pub struct Saved(BTreeMap<String, String>);
+
+pub fn save() -> Saved {
+    Saved(read().clone())
+}
+
+/// Puts back the aliases saved by [`save`], so that those defined or
+/// removed since are forgotten.
+pub fn restore(saved: Saved) {
+    *write() = saved.0;
+}
+
+/// Every alias with its value, sorted by name.
+pub fn all() -> Vec<(String, String)> {
+    read()
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Whether `name` can be used as an alias, which like a command name must
+/// be a single plain word.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "|&;<>()'\"\\$`=/".contains(c))
+}
+
+/// The `alias` command that defines an alias.
+pub fn definition(name: &str, value: &str) -> String {
+    format!("alias {}='{}'", name, value.replace('\'', r"'\''"))
+}
+
+fn write_alias(stdout: &mut dyn Write, name: &str, value: &str) -> io::Result<()> {
+    writeln!(stdout, "{}", definition(name, value))
+}
+
+/// `alias [NAME[=VALUE]]...`
+pub struct Alias;
+
+impl Command for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn usage(&self) -> &'static str {
+        "alias [NAME[=VALUE]]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Define or list aliases for commands"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        if args.is_empty() {
+            let listed: io::Result<()> = all()
+                .iter()
+                .try_for_each(|(name, value)| write_alias(stdout, name, value));
+            return commands::exit_code(stderr, self.name(), listed);
+        }
+
+        let mut status = 0;
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if is_valid_name(name) => set(name, value),
+                Some((name, _)) => {
+                    status = commands::report_error(
+                        stderr,
+                        self.name(),
+                        format!("'{}': invalid alias name", name),
+                    );
+                }
+                None => match get(arg) {
+                    Some(value) => {
+                        if let Err(e) = write_alias(stdout, arg, &value) {
+                            return commands::report_error(stderr, self.name(), e);
+                        }
+                    }
+                    None => {
+                        status = commands::report_error(
+                            stderr,
+                            self.name(),
+                            format!("{}: not found", arg),
+                        );
+                    }
+                },
+            }
+        }
+        status
+    }
+}
+
+/// `unalias [-a] NAME...`
+pub struct Unalias;
+
+impl Command for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn usage(&self) -> &'static str {
+        "unalias [-a] NAME..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove aliases, or all of them with -a"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        if args.first().is_some_and(|arg| arg == "-a") {
+            write().clear();
+            return 0;
+        }
+        if args.is_empty() {
+            return commands::usage_error(stderr, self.usage());
+        }
+
+        let mut status = 0;
+        for name in args {
+            if !remove(name) {
+                status =
+                    commands::report_error(stderr, self.name(), format!("{}: not found", name));
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &dyn Command, args: &[&str]) -> (i32, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut output = Vec::new();
+        let code = command.run(&args, &mut io::empty(), &mut output, &mut io::sink());
+        (code, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_alias_and_unalias() {
+        assert_eq!(run(&Alias, &["winix_ll=ls -l", "winix_q=echo 'hi'"]).0, 0);
+        assert_eq!(get("winix_ll").as_deref(), Some("ls -l"));
+        assert_eq!(
+            run(&Alias, &["winix_q"]),
+            (0, "alias winix_q='echo '\\''hi'\\'''\n".to_string())
+        );
+        assert!(run(&Alias, &[]).1.contains("alias winix_ll='ls -l'\n"));
+
+        assert_eq!(run(&Unalias, &["winix_ll", "winix_q"]).0, 0);
+        assert_eq!(get("winix_ll"), None);
+        assert_eq!(run(&Unalias, &["winix_ll"]).0, 1);
+        assert_eq!(run(&Alias, &["winix_ll"]).0, 1);
+        assert_eq!(run(&Alias, &["a b=c"]).0, 1);
+        assert_eq!(run(&Unalias, &[]).0, 2);
+    }
+}
@@ -0,0 +1,175 @@
+//! Key bindings that run a command when a key is pressed at the CLI's
+//! prompt, with the `bind` built-in that defines them, usually from
+//! `~/.winixrc`.
+//!
+//! Like aliases, the bindings live in one store for the whole process. The
+//! line editor picks up any changes before it reads the next line.
+
+use crate::commands::{self, Command};
+use rustyline::{KeyCode, KeyEvent, Modifiers};
+use std::io::{self, Read, Write};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+fn store() -> &'static Mutex<Vec<(String, String)>> {
+    static STORE: OnceLock<Mutex<Vec<(String, String)>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn lock() -> MutexGuard<'static, Vec<(String, String)>> {
+    store().lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Every binding, as the key it was given with and the command it runs, in
+/// the order they were made.
+pub fn all() -> Vec<(String, String)> {
+    lock().clone()
+}
+
+/// The key a key name such as `C-g`, `"\M-x"`, `C-M-k` or `F5` stands for,
+/// in the form it is shown in, or `None` if it is not a key name.
+pub fn parse_key(name: &str) -> Option<(String, KeyEvent)> {
+    let mut rest = name.trim_matches('"');
+    let mut modifiers = Modifiers::NONE;
+    let mut shown = String::new();
+    loop {
+        rest = rest.strip_prefix('\\').unwrap_or(rest);
+        if let Some(after) = rest.strip_prefix("C-") {
+            modifiers |= Modifiers::CTRL;
+            shown.push_str("C-");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("M-") {
+            modifiers |= Modifiers::ALT;
+            shown.push_str("M-");
+            rest = after;
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = rest.chars();
+    let key = match (chars.next(), chars.next()) {
+        (Some(c), None) if !modifiers.is_empty() => {
+            // Control keys do not tell upper and lower case apart
+            let c = if modifiers.contains(Modifiers::CTRL) {
+                c.to_ascii_lowercase()
+            } else {
+                c
+            };
+            shown.push(c);
+            KeyEvent::new(c, modifiers)
+        }
+        (Some('F'), Some(_)) => {
+            let number: u8 = rest[1..].parse().ok().filter(|n| (1..=12).contains(n))?;
+            shown.push_str(rest);
+            KeyEvent(KeyCode::F(number), modifiers)
+        }
+        _ => return None,
+    };
+    Some((shown, key))
+}
+
+/// The form a key name is shown in, or the status for reporting that it is
+/// not one.
+fn key_name(stderr: &mut dyn Write, name: &str) -> Result<String, i32> {
+    parse_key(name)
+        .map(|(shown, _)| shown)
+        .ok_or_else(|| commands::report_error(stderr, "bind", format!("{}: unknown key", name)))
+}
+
+/// `bind [-l] [-r KEY] [KEY COMMAND...]`
+pub struct Bind;
+
+impl Command for Bind {
+    fn name(&self) -> &'static str {
+        "bind"
+    }
+
+    fn usage(&self) -> &'static str {
+        "bind [-l] [-r KEY] [KEY COMMAND...]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a command when a key such as C-g or F5 is pressed, or list (-l) or remove (-r) bindings"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        match args.first().map(String::as_str) {
+            None | Some("-l") if args.len() <= 1 => {
+                let listed: io::Result<()> = all().iter().try_for_each(|(key, command)| {
+                    writeln!(stdout, "bind {} '{}'", key, command.replace('\'', r"'\''"))
+                });
+                commands::exit_code(stderr, self.name(), listed)
+            }
+            Some("-r") if args.len() == 2 => {
+                let key = match key_name(stderr, &args[1]) {
+                    Ok(key) => key,
+                    Err(status) => return status,
+                };
+                lock().retain(|(bound, _)| *bound != key);
+                0
+            }
+            Some(name) if args.len() >= 2 && !name.starts_with('-') => {
+                let key = match key_name(stderr, name) {
+                    Ok(key) => key,
+                    Err(status) => return status,
+                };
+                let mut bindings = lock();
+                bindings.retain(|(bound, _)| *bound != key);
+                bindings.push((key, args[1..].join(" ")));
+                0
+            }
+            _ => commands::usage_error(stderr, self.usage()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> (i32, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut output = Vec::new();
+        let code = Bind.run(&args, &mut io::empty(), &mut output, &mut io::sink());
+        (code, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            parse_key(r#""\C-G""#),
+            Some(("C-g".to_string(), KeyEvent::ctrl('g')))
+        );
+        assert_eq!(
+            parse_key("M-x"),
+            Some(("M-x".to_string(), KeyEvent::alt('x')))
+        );
+        assert_eq!(
+            parse_key("C-M-k").map(|(shown, _)| shown).as_deref(),
+            Some("C-M-k")
+        );
+        assert_eq!(
+            parse_key("F5"),
+            Some(("F5".to_string(), KeyEvent(KeyCode::F(5), Modifiers::NONE)))
+        );
+        assert_eq!(parse_key("g"), None);
+        assert_eq!(parse_key("F13"), None);
+    }
+
+    #[test]
+    fn test_bind() {
+        assert_eq!(run(&["F11", "git", "status"]).0, 0);
+        assert_eq!(run(&["\\C-F11"]).0, 2);
+        assert_eq!(run(&["F11", "echo", "it's"]).0, 0);
+        assert!(run(&["-l"]).1.contains("bind F11 'echo it'\\''s'\n"));
+        assert_eq!(run(&["-r", "F11"]).0, 0);
+        assert!(!all().iter().any(|(key, _)| key == "F11"));
+        assert_eq!(run(&["nokey", "ls"]).0, 1);
+    }
+}
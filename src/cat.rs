@@ -1,10 +1,12 @@
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
 use tokio::fs::File as TokioFile;
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use futures::stream::{self, Stream, StreamExt};
 use bytes::Bytes;
+use crate::commands::{self, Command};
+use crate::pipeline::{self, CatStage, Stage};
 
 // === Sync implementation ===
 #[allow(dead_code)]
@@ -37,17 +39,15 @@ pub async fn cat_async<S: AsRef<Path> + Send + 'static>(
         return stream::empty().boxed();
     }
 
-    let mut all_streams = Vec::new();
-
-    for file_path in files {
+    let file_streams = files.into_iter().map(|file_path| {
         let path = file_path.as_ref().to_path_buf();
-        let file_stream = async move {
+        stream::once(async move {
             match TokioFile::open(&path).await {
                 Ok(file) => {
                     let reader = TokioBufReader::new(file);
                     let lines = reader.lines();
 
-                    Ok::<_, io::Error>(stream::unfold(lines, |mut lines| async move {
+                    stream::unfold(lines, |mut lines| async move {
                         match lines.next_line().await {
                             Ok(Some(line)) => {
                                 let mut normalized = line;
@@ -60,22 +60,23 @@ pub async fn cat_async<S: AsRef<Path> + Send + 'static>(
                             Ok(None) => None,
                             Err(e) => Some((Err(e), lines)),
                         }
-                    }))
+                    })
+                    .boxed()
+                }
+                Err(e) => {
+                    let e = io::Error::new(
+                        e.kind(),
+                        format!("{}: {}", path.display(), commands::io_message(&e)),
+                    );
+                    stream::once(async move { Err(e) }).boxed()
                 }
-                Err(e) => Err(e),
             }
-        };
-        all_streams.push(file_stream);
-    }
+        })
+        .flatten()
+    });
 
-    if let Some(first_stream) = all_streams.into_iter().next() {
-        match first_stream.await {
-            Ok(stream) => stream.boxed(),
-            Err(e) => stream::once(async move { Err(e) }).boxed(),
-        }
-    } else {
-        stream::empty().boxed()
-    }
+    // Files are opened one after another as the previous one is exhausted
+    stream::iter(file_streams).flatten().boxed()
 }
 
 #[allow(dead_code)]
@@ -127,6 +128,42 @@ pub async fn benchmark_cat_sync_vs_async<S: AsRef<Path> + Send + Clone + 'static
 }
 
 // === Tests ===
+pub struct Cat;
+
+impl Cat {
+    fn build(args: &[String]) -> Result<Box<dyn Stage>, String> {
+        Ok(Box::new(CatStage::new(args.to_vec())))
+    }
+}
+
+impl Command for Cat {
+    fn name(&self) -> &'static str {
+        "cat"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cat [FILE]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Print files, or pass the input through"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        pipeline::run_stage(self.name(), Self::build(args), stdin, stdout, stderr)
+    }
+
+    fn stage(&self, args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        Some(Self::build(args))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
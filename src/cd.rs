@@ -1 +1,35 @@
+use crate::commands::{self, Command};
+use std::env;
+use std::io::{Read, Write};
 
+pub struct Cd;
+
+impl Command for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cd <directory>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Change the working directory"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        match args.first() {
+            Some(path) => match env::set_current_dir(path) {
+                Ok(()) => 0,
+                Err(e) => commands::report_error(stderr, self.name(), e),
+            },
+            None => commands::usage_error(stderr, self.usage()),
+        }
+    }
+}
@@ -16,7 +16,37 @@ use winapi::um::winnt::{
     FILE_GENERIC_EXECUTE, FILE_GENERIC_READ, FILE_GENERIC_WRITE, PSID, TOKEN_USER,
 };
 
-pub fn execute(args: &[&str]) {
+pub struct Chmod;
+
+impl crate::commands::Command for Chmod {
+    fn name(&self) -> &'static str {
+        "chmod"
+    }
+
+    fn usage(&self) -> &'static str {
+        "chmod <mode> <file>..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Change file permissions"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn std::io::Read,
+        _stdout: &mut dyn std::io::Write,
+        stderr: &mut dyn std::io::Write,
+    ) -> i32 {
+        if args.len() < 2 {
+            return crate::commands::usage_error(stderr, self.usage());
+        }
+        execute(&args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+}
+
+/// Returns the exit code: 0 if every file was changed
+pub fn execute(args: &[&str]) -> i32 {
     if args.len() < 2 {
         println!(
             "{}",
@@ -30,11 +60,12 @@ pub fn execute(args: &[&str]) {
         println!("  {}", "chmod g-w,o-w file.txt".dimmed());
         println!("  {}", "chmod a=r file.txt".dimmed());
         println!("  {}", "chmod u=rwx,g=rx,o=r file.txt".dimmed());
-        return;
+        return 2;
     }
 
     let mode = args[0];
     let files = &args[1..];
+    let mut status = 0;
 
     for filename in files {
         if !std::path::Path::new(filename).exists() {
@@ -46,6 +77,7 @@ pub fn execute(args: &[&str]) {
                 )
                 .red()
             );
+            status = 1;
             continue;
         }
 
@@ -58,9 +90,11 @@ pub fn execute(args: &[&str]) {
             }
             Err(e) => {
                 println!("{}", format!("chmod: {}", e).red());
+                status = 1;
             }
         }
     }
+    status
 }
 
 fn parse_and_mode(filename: &str, mode: &str) -> Result<bool, String> {
@@ -23,9 +23,41 @@ use winapi::um::winbase::{FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_ALLOCATE_BU
 use winapi::um::winbase::LocalFree;
 // no OsStringExt needed when using from_utf16_lossy
 
-/// Main entry point for chown command (only works on Windows)
 #[cfg(windows)]
-pub fn execute(args: &[&str]) {
+pub struct Chown;
+
+#[cfg(windows)]
+impl crate::commands::Command for Chown {
+    fn name(&self) -> &'static str {
+        "chown"
+    }
+
+    fn usage(&self) -> &'static str {
+        "chown <owner>[:group] <file>..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Change file ownership"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn std::io::Read,
+        _stdout: &mut dyn std::io::Write,
+        stderr: &mut dyn std::io::Write,
+    ) -> i32 {
+        if args.len() < 2 {
+            return crate::commands::usage_error(stderr, self.usage());
+        }
+        execute(&args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+}
+
+/// Main entry point for chown command (only works on Windows). Returns the
+/// exit code: 0 if every file was changed
+#[cfg(windows)]
+pub fn execute(args: &[&str]) -> i32 {
     if args.len() < 2 {
         println!("{}", "Usage: chown [OPTION]... [OWNER][:[GROUP]] FILE...".red());
         println!("{}", "   or: chown [OPTION]... --reference=RFILE FILE...".red());
@@ -36,15 +68,17 @@ pub fn execute(args: &[&str]) {
         println!("  {}", "chown :developers file.txt".dimmed());
         println!("  {}", "chown --recursive alice:developers /mydir".dimmed());
         println!("  {}", "chown --reference=ref.txt file.txt".dimmed());
-        return;
+        return 2;
     }
 
     let mode = args[0];
     let files = &args[1..];
+    let mut status = 0;
 
     for filename in files {
         if !std::path::Path::new(filename).exists() {
             println!("{}", format!("chown: cannot access '{}': No such file or directory", filename).red());
+            status = 1;
             continue;
         }
 
@@ -54,9 +88,11 @@ pub fn execute(args: &[&str]) {
             }
             Err(e) => {
                 println!("{}", format!("chown: {}", e).red());
+                status = 1;
             }
         }
     }
+    status
 }
 
 /// Parses user:group mode string and calls `change_owner_with_only_sid`
@@ -206,6 +242,7 @@ fn name_to_sid(name: &str, _domain: Option<&str>) -> Result<Vec<u8>, u32> {
 
 /// Dummy stub for non-Windows platforms
 #[cfg(not(windows))]
-pub fn execute(_args: &[&str]) {
+pub fn execute(_args: &[&str]) -> i32 {
     eprintln!("Error: `chown` command is only supported on Windows platforms.");
+    1
 }
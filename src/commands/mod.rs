@@ -1,66 +1,277 @@
-#[cfg(any(target_os = "macos", target_os = "windows"))]
-pub mod echo;
-
-#[cfg(any(target_os = "macos", target_os = "windows"))]
-pub mod touch;
-
-#[cfg(any(target_os = "macos", target_os = "windows"))]
-pub mod sudo;
-
-#[cfg(any(target_os = "macos", target_os = "windows"))]
-pub mod disown;
-
-// Windows-only modules
-#[cfg(target_os = "windows")]
-pub mod chmod;
-#[cfg(target_os = "windows")]
-pub mod chown;
-#[cfg(target_os = "windows")]
-pub mod pipeline;
-#[cfg(target_os = "windows")]
-pub mod powershell;
-#[cfg(target_os = "windows")]
-pub mod cd;
-#[cfg(target_os = "windows")]
-pub mod df;
-#[cfg(target_os = "windows")]
-pub mod free;
-#[cfg(target_os = "windows")]
-pub mod git;
-#[cfg(target_os = "windows")]
-pub mod process;
-#[cfg(target_os = "windows")]
-pub mod ps;
-#[cfg(target_os = "windows")]
-pub mod sensors;
-#[cfg(target_os = "windows")]
-pub mod tui;
-#[cfg(target_os = "windows")]
-pub mod uname;
-#[cfg(target_os = "windows")]
-pub mod uptime;
-#[cfg(windows)]
-pub mod kill;
-
-// src/command/mod.rs
-pub fn dummy() {
-  println!("command module loaded");
+//! The `Command` trait implemented by every built-in, and the registry the
+//! CLI, the TUI, `help` and completion all look commands up in.
+
+use crate::pipeline::Stage;
+use colored::Colorize;
+use std::io::{self, Read, Write};
+use std::sync::OnceLock;
+
+/// A built-in command.
+pub trait Command: Send + Sync {
+    /// The name the command is invoked by.
+    fn name(&self) -> &'static str;
+
+    /// Other names for the command, such as `powershell` for `psh`.
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The command line synopsis, like `head [-n N] [FILE]...`.
+    fn usage(&self) -> &'static str;
+
+    /// A one-line summary for `help`.
+    fn description(&self) -> &'static str;
+
+    /// Runs the command and returns its exit code.
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32;
+
+    /// Builds a native streaming stage for commands such as `head` that
+    /// process their input as it arrives. Other commands run in pipelines
+    /// through [`Command::run`].
+    fn stage(&self, _args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        None
+    }
+
+    /// Runs the command attached directly to the terminal, for commands
+    /// wrapping interactive programs such as `git`. Returns `None` if the
+    /// command has no such mode.
+    fn run_in_terminal(&self, _args: &[String]) -> Option<i32> {
+        None
+    }
+}
+
+/// Every built-in command, looked up by name.
+pub struct Registry {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        let mut commands: Vec<Box<dyn Command>> = vec![
+            Box::new(crate::alias::Alias),
+            Box::new(crate::test::Bracket),
+            Box::new(crate::bind::Bind),
+            Box::new(crate::cat::Cat),
+            Box::new(crate::cd::Cd),
+            Box::new(Clear),
+            Box::new(crate::cp::Cp),
+            Box::new(crate::df::Df),
+            Box::new(crate::echo::Echo),
+            Box::new(crate::vars::Env),
+            Box::new(crate::vars::Export),
+            Box::new(crate::test::False),
+            Box::new(crate::free::Free),
+            Box::new(crate::git::Git),
+            Box::new(crate::grep::Grep),
+            Box::new(crate::head::Head),
+            Box::new(Help),
+            Box::new(crate::history::History),
+            Box::new(crate::ls::Ls),
+            Box::new(crate::mkdir::Mkdir),
+            Box::new(crate::mv::Mv),
+            Box::new(crate::powershell::PowerShell),
+            Box::new(crate::printf::Printf),
+            Box::new(crate::ps::Ps),
+            Box::new(crate::pwd::Pwd),
+            Box::new(crate::rm::Rm),
+            Box::new(crate::sensors::Sensors),
+            Box::new(crate::vars::Set),
+            Box::new(crate::options::Shopt),
+            Box::new(crate::tail::Tail),
+            Box::new(crate::test::Test),
+            Box::new(crate::touch::Touch),
+            Box::new(crate::trash::Trash),
+            Box::new(crate::test::True),
+            Box::new(crate::uname::Uname),
+            Box::new(crate::alias::Unalias),
+            Box::new(crate::uptime::Uptime),
+            Box::new(crate::vars::Unset),
+        ];
+        #[cfg(windows)]
+        commands.extend([
+            Box::new(crate::chmod::Chmod) as Box<dyn Command>,
+            Box::new(crate::chown::Chown),
+            Box::new(crate::kill::Kill),
+        ]);
+        commands.sort_by_key(|command| command.name());
+
+        Self { commands }
+    }
+
+    /// Finds a command by name or alias, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&dyn Command> {
+        let name = name.to_lowercase();
+        self.commands
+            .iter()
+            .find(|command| command.name() == name || command.aliases().contains(&name.as_str()))
+            .map(|command| command.as_ref())
+    }
+
+    /// All commands, sorted by name.
+    pub fn commands(&self) -> impl Iterator<Item = &dyn Command> {
+        self.commands.iter().map(|command| command.as_ref())
+    }
+
+    /// Every name a command can be invoked by, including aliases.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands.iter().flat_map(|command| {
+            std::iter::once(command.name()).chain(command.aliases().iter().copied())
+        })
+    }
+}
+
+/// The registry of built-in commands.
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::new)
+}
+
+/// Writes a command's error to `stderr` and returns the exit code for it.
+pub fn report_error(stderr: &mut dyn Write, name: &str, error: impl std::fmt::Display) -> i32 {
+    let _ = writeln!(stderr, "{}", format!("{}: {}", name, error).red());
+    1
+}
+
+/// The text of an I/O error as other shells show it, without the
+/// ` (os error 2)` Rust adds to errors from the operating system.
+pub fn io_message(error: &io::Error) -> String {
+    let message = error.to_string();
+    match message.rfind(" (os error ") {
+        Some(end) if message.ends_with(')') => message[..end].to_string(),
+        _ => message,
+    }
+}
+
+/// Writes a command's usage line to `stderr` and returns the exit code for misuse.
+pub fn usage_error(stderr: &mut dyn Write, usage: &str) -> i32 {
+    let _ = writeln!(stderr, "{}", format!("Usage: {}", usage).red());
+    2
+}
+
+/// Converts the result of a command that writes to an output sink into an exit code.
+pub fn exit_code(stderr: &mut dyn Write, name: &str, result: io::Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => report_error(stderr, name, e),
+    }
+}
+
+/// Writes the list of commands with their usage and description.
+pub fn write_help(out: &mut dyn Write) -> io::Result<()> {
+    let commands: Vec<_> = registry().commands().collect();
+    let width = commands.iter().map(|c| c.usage().len()).max().unwrap_or(0);
+
+    writeln!(out, "{}", "Available commands:".bold().white())?;
+    for command in commands {
+        writeln!(
+            out,
+            "  {}  {}",
+            format!("{:<width$}", command.usage()).bold().yellow(),
+            command.description()
+        )?;
+    }
+    Ok(())
 }
 
-pub async fn grep_async_from_string(pattern: &str, content: &str) -> io::Result<String> {
-  let result = content
-      .lines()
-      .filter(|line| line.contains(pattern))
-      .map(|s| format!("{}\n", s))
-      .collect();
-  Ok(result)
+/// `help`
+pub struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn usage(&self) -> &'static str {
+        "help"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show this list of commands"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        exit_code(stderr, self.name(), write_help(stdout))
+    }
 }
 
-pub async fn head_async_from_string(content: &str, lines: usize) -> io::Result<String> {
-  let result = content
-      .lines()
-      .take(lines)
-      .map(|s| format!("{}\n", s))
-      .collect();
-  Ok(result)
+/// `clear`
+pub struct Clear;
+
+impl Command for Clear {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn usage(&self) -> &'static str {
+        "clear"
+    }
+
+    fn description(&self) -> &'static str {
+        "Clear the screen"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        exit_code(stderr, self.name(), write!(stdout, "{}", CLEAR_SCREEN))
+    }
+}
+
+/// The escape sequence `clear` writes: erase the screen and home the cursor.
+pub const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_name_and_alias() {
+        assert_eq!(registry().get("uname").unwrap().name(), "uname");
+        assert_eq!(registry().get("PS").unwrap().name(), "ps");
+        assert_eq!(registry().get("powershell").unwrap().name(), "psh");
+        assert!(registry().get("winix-no-such-command").is_none());
+    }
+
+    #[test]
+    fn test_help_lists_every_command() {
+        let mut output = Vec::new();
+        write_help(&mut output).unwrap();
+        let help = String::from_utf8(output).unwrap();
+
+        for command in registry().commands() {
+            assert!(help.contains(command.usage()), "{} missing", command.name());
+        }
+    }
+
+    #[test]
+    fn test_echo_through_registry() {
+        let args = vec!["hello".to_string(), "world".to_string()];
+        let mut output = Vec::new();
+
+        let code = registry().get("echo").unwrap().run(
+            &args,
+            &mut io::empty(),
+            &mut output,
+            &mut io::sink(),
+        );
+
+        assert_eq!(code, 0);
+        assert_eq!(String::from_utf8(output).unwrap(), "hello world\n");
+    }
 }
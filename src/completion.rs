@@ -0,0 +1,537 @@
+//! Tab completion shared by the CLI's line editor and the TUI's command
+//! input. What is offered depends on where the cursor is: command names at
+//! the start of a command, `$VARIABLES`, git subcommands and branches,
+//! processes and signals for `kill`, and otherwise file paths.
+
+use crate::alias;
+use crate::commands;
+use crate::git;
+use crate::parser;
+use crate::shell;
+use crate::vars;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysinfo::{ProcessesToUpdate, System};
+
+/// One way to complete the word under the cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// What to show in a list of candidates
+    pub display: String,
+    /// What to put in place of the word
+    pub replacement: String,
+}
+
+impl Candidate {
+    fn new(display: impl Into<String>, replacement: impl Into<String>) -> Self {
+        Self {
+            display: display.into(),
+            replacement: replacement.into(),
+        }
+    }
+
+    /// A candidate that is a whole word, so a space follows it.
+    fn word(text: &str) -> Self {
+        Self::new(text, format!("{} ", text))
+    }
+}
+
+const GIT_SUBCOMMANDS: [&str; 26] = [
+    "add",
+    "bisect",
+    "blame",
+    "branch",
+    "checkout",
+    "cherry-pick",
+    "clone",
+    "commit",
+    "diff",
+    "fetch",
+    "grep",
+    "init",
+    "log",
+    "merge",
+    "mv",
+    "pull",
+    "push",
+    "rebase",
+    "remote",
+    "reset",
+    "restore",
+    "rm",
+    "show",
+    "stash",
+    "status",
+    "switch",
+];
+
+/// Git subcommands that take a branch, then those of them that take file
+/// paths as well.
+const GIT_BRANCH_COMMANDS: [&str; 11] = [
+    "branch",
+    "merge",
+    "pull",
+    "push",
+    "rebase",
+    "switch",
+    "checkout",
+    "cherry-pick",
+    "diff",
+    "log",
+    "reset",
+];
+const GIT_PATH_COMMANDS: [&str; 4] = ["checkout", "diff", "log", "reset"];
+
+const SIGNALS: &[&str] = if cfg!(windows) {
+    &["INT", "KILL", "QUIT", "TERM"]
+} else {
+    &[
+        "CONT", "HUP", "INT", "KILL", "QUIT", "STOP", "TERM", "USR1", "USR2",
+    ]
+};
+
+/// The candidates for completing `line` with the cursor at byte `pos`, and
+/// the byte offset where the word they replace starts.
+pub fn complete(line: &str, pos: usize) -> (usize, Vec<Candidate>) {
+    let (words, word) = split_command(&line[..pos]);
+
+    if let Some((start, prefix)) = variable_prefix(&line[word.start..pos]) {
+        return (word.start + start, variables(prefix));
+    }
+
+    let candidates = match words.first().map(String::as_str) {
+        None if !word.text.contains(is_separator) && !word.text.starts_with('.') => {
+            command_names(&word.text)
+        }
+        Some("git") if words.len() == 1 && !word.text.starts_with('-') => {
+            matching(GIT_SUBCOMMANDS, &word.text)
+        }
+        Some("git")
+            if words
+                .get(1)
+                .is_some_and(|sub| GIT_BRANCH_COMMANDS.contains(&sub.as_str())) =>
+        {
+            let mut candidates = Vec::new();
+            if !word.text.starts_with('-') {
+                candidates = matching(git::get_branches().iter().map(String::as_str), &word.text);
+            }
+            if GIT_PATH_COMMANDS.contains(&words[1].as_str()) {
+                candidates.extend(paths(&word));
+            }
+            candidates
+        }
+        Some("kill") if words.last().is_some_and(|word| word == "-s") => {
+            matching(SIGNALS.iter().copied(), &word.text)
+        }
+        Some("kill") if word.text.starts_with('-') => SIGNALS
+            .iter()
+            .map(|signal| format!("-{}", signal))
+            .filter(|signal| signal.starts_with(&word.text))
+            .map(|signal| Candidate::word(&signal))
+            .collect(),
+        Some("kill") => processes(&word.text),
+        _ => paths(&word),
+    };
+    (word.start, candidates)
+}
+
+/// The longest text every candidate's replacement starts with, for filling
+/// in as much as is certain when there are several candidates.
+pub fn common_prefix(candidates: &[Candidate]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+    let mut prefix = first.replacement.as_str();
+    for candidate in &candidates[1..] {
+        let shared = prefix
+            .char_indices()
+            .zip(candidate.replacement.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(
+                prefix.len().min(candidate.replacement.len()),
+                |((i, _), _)| i,
+            );
+        prefix = &prefix[..shared];
+    }
+    prefix.to_string()
+}
+
+/// The word under the cursor.
+#[derive(Debug, Default)]
+struct CurrentWord {
+    /// Byte offset of the word in the line
+    start: usize,
+    /// The word with quotes and escapes removed
+    text: String,
+    /// The quote the word was opened with, if any
+    quote: Option<char>,
+}
+
+/// Splits the text before the cursor into the words of the command being
+/// typed, with quoting removed, and the word under the cursor.
+fn split_command(prefix: &str) -> (Vec<String>, CurrentWord) {
+    let mut words = Vec::new();
+    let mut word = CurrentWord::default();
+    let mut quote = None;
+    let mut chars = prefix.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => {
+                if let Some((_, next)) = chars.next() {
+                    word.text.push(next);
+                }
+            }
+            Some(_) => word.text.push(c),
+            None => match c {
+                '\\' if chars
+                    .peek()
+                    .is_some_and(|&(_, next)| parser::is_escapable(next)) =>
+                {
+                    if let Some((_, next)) = chars.next() {
+                        word.text.push(next);
+                    }
+                }
+                '\'' | '"' => {
+                    if word.text.is_empty() && word.quote.is_none() {
+                        word.quote = Some(c);
+                    }
+                    quote = Some(c);
+                }
+                c if c.is_whitespace() || "|&;<>()".contains(c) => {
+                    if !word.text.is_empty() || word.quote.is_some() {
+                        words.push(std::mem::take(&mut word.text));
+                    }
+                    // A new command starts after these
+                    if "|&;(".contains(c) {
+                        words.clear();
+                    }
+                    word = CurrentWord {
+                        start: i + c.len_utf8(),
+                        ..CurrentWord::default()
+                    };
+                }
+                c => word.text.push(c),
+            },
+        }
+    }
+    (words, word)
+}
+
+/// The start and name prefix of a `$NAME` or `${NAME` at the end of the word.
+fn variable_prefix(raw: &str) -> Option<(usize, &str)> {
+    let dollar = raw.rfind('$')?;
+    let name = raw[dollar + 1..]
+        .strip_prefix('{')
+        .unwrap_or(&raw[dollar + 1..]);
+    name.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        .then_some((dollar, name))
+}
+
+fn variables(prefix: &str) -> Vec<Candidate> {
+    vars::all()
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, _)| Candidate::new(name.clone(), format!("${}", name)))
+        .collect()
+}
+
+/// The candidates among `names` that start with `prefix`, sorted.
+fn matching<'a>(names: impl IntoIterator<Item = &'a str>, prefix: &str) -> Vec<Candidate> {
+    let names: BTreeSet<&str> = names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.into_iter().map(Candidate::word).collect()
+}
+
+/// Built-ins, shell built-ins, aliases and programs on the PATH.
+fn command_names(prefix: &str) -> Vec<Candidate> {
+    let mut names: Vec<String> = commands::registry()
+        .names()
+        .chain(shell::SPECIAL_BUILTINS)
+        .map(str::to_string)
+        .collect();
+    names.extend(alias::all().into_iter().map(|(name, _)| name));
+    // Listing every program on the PATH is too much to be useful
+    if !prefix.is_empty() {
+        names.extend(programs(prefix));
+    }
+    matching(names.iter().map(String::as_str), prefix)
+}
+
+/// The programs on the PATH whose names start with `prefix`.
+fn programs(prefix: &str) -> Vec<String> {
+    let Some(path) = vars::get("PATH") else {
+        return Vec::new();
+    };
+    let mut programs = Vec::new();
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(prefix) && is_executable(&entry.path()) {
+                programs.push(program_name(name));
+            }
+        }
+    }
+    programs
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_uppercase());
+    path.is_file()
+        && extension.is_some_and(|extension| executable_extensions().contains(&extension))
+}
+
+/// A program's name as it is typed, which on Windows leaves off `.exe` and
+/// the other extensions in PATHEXT.
+fn program_name(file_name: String) -> String {
+    #[cfg(windows)]
+    if let Some((stem, extension)) = file_name.rsplit_once('.')
+        && executable_extensions().contains(&extension.to_uppercase())
+    {
+        return stem.to_string();
+    }
+    file_name
+}
+
+#[cfg(windows)]
+fn executable_extensions() -> Vec<String> {
+    vars::get("PATHEXT")
+        .unwrap_or_else(|| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .map(|extension| extension.trim_start_matches('.').to_uppercase())
+        .collect()
+}
+
+/// Process IDs when the prefix is a number, and process names otherwise.
+fn processes(prefix: &str) -> Vec<Candidate> {
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::All, true);
+
+    if prefix.chars().all(|c| c.is_ascii_digit()) {
+        let mut processes: Vec<(u32, String)> = system
+            .processes()
+            .iter()
+            .map(|(pid, process)| (pid.as_u32(), process.name().to_string_lossy().into_owned()))
+            .filter(|(pid, _)| pid.to_string().starts_with(prefix))
+            .collect();
+        processes.sort();
+        return processes
+            .into_iter()
+            .map(|(pid, name)| Candidate::new(format!("{} ({})", pid, name), format!("{} ", pid)))
+            .collect();
+    }
+    let names: Vec<String> = system
+        .processes()
+        .values()
+        .map(|process| process.name().to_string_lossy().into_owned())
+        .collect();
+    matching(names.iter().map(String::as_str), prefix)
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || (cfg!(windows) && c == '\\')
+}
+
+/// Files and directories starting with the word, quoted the way the word was.
+fn paths(word: &CurrentWord) -> Vec<Candidate> {
+    let (dir_part, name_prefix) = match word.text.rfind(is_separator) {
+        Some(i) => word.text.split_at(i + 1),
+        None => ("", word.text.as_str()),
+    };
+    let dir: PathBuf = match dir_part.strip_prefix("~") {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_separator) => match dirs::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches(is_separator)),
+            None => return Vec::new(),
+        },
+        _ if dir_part.is_empty() => PathBuf::from("."),
+        _ => PathBuf::from(dir_part),
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+
+    let ignore_case = cfg!(windows);
+    let starts_with = |name: &str| {
+        if ignore_case {
+            name.to_lowercase().starts_with(&name_prefix.to_lowercase())
+        } else {
+            name.starts_with(name_prefix)
+        }
+    };
+    // Keep to the separator the user has been typing
+    let separator = dir_part
+        .chars()
+        .rev()
+        .find(|&c| is_separator(c))
+        .unwrap_or('/');
+
+    let mut candidates: Vec<(String, bool)> = entries
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            (name, entry.path().is_dir())
+        })
+        .filter(|(name, _)| {
+            starts_with(name) && (name_prefix.starts_with('.') || !name.starts_with('.'))
+        })
+        .collect();
+    candidates.sort();
+
+    candidates
+        .into_iter()
+        .map(|(name, is_dir)| {
+            let path = format!("{}{}", dir_part, name);
+            let display = if is_dir {
+                format!("{}{}", name, separator)
+            } else {
+                name
+            };
+            let replacement = match word.quote {
+                Some(quote) if is_dir => {
+                    format!("{}{}{}", quote, quote_text(&path, quote), separator)
+                }
+                Some(quote) => format!("{}{}{} ", quote, quote_text(&path, quote), quote),
+                None if is_dir => format!("{}{}", escape(&path), separator),
+                None => format!("{} ", escape(&path)),
+            };
+            Candidate::new(display, replacement)
+        })
+        .collect()
+}
+
+/// Escapes the characters the shell would otherwise treat specially,
+/// leaving a leading `~` to stand for the home directory.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for (i, c) in text.chars().enumerate() {
+        let special = c.is_whitespace() || "|&;<>()'\"$`*?[]#".contains(c) || (c == '~' && i > 0);
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Text as it must be written inside the given quotes.
+fn quote_text(text: &str, quote: char) -> String {
+    if quote == '\'' {
+        text.replace('\'', r"'\''")
+    } else {
+        let mut quoted = String::new();
+        for c in text.chars() {
+            if matches!(c, '"' | '\\' | '$' | '`') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacements(line: &str) -> (usize, Vec<String>) {
+        let (start, candidates) = complete(line, line.len());
+        (
+            start,
+            candidates.into_iter().map(|c| c.replacement).collect(),
+        )
+    }
+
+    #[test]
+    fn test_split_command() {
+        let (words, word) = split_command("ls | git 'check out' a\\ b");
+        assert_eq!(words, vec!["git", "check out"]);
+        assert_eq!(word.text, "a b");
+        assert_eq!(word.start, 21);
+
+        let (words, word) = split_command("echo \"my fi");
+        assert_eq!(words, vec!["echo"]);
+        assert_eq!((word.text.as_str(), word.quote), ("my fi", Some('"')));
+    }
+
+    #[test]
+    fn test_commands_and_variables() {
+        assert_eq!(replacements("unal"), (0, vec!["unalias ".to_string()]));
+        assert_eq!(replacements("ls; diso").1, vec!["disown "]);
+
+        vars::set("WINIX_COMPLETE_TEST", "1");
+        assert_eq!(
+            replacements("echo x$WINIX_COMPLETE_"),
+            (6, vec!["$WINIX_COMPLETE_TEST".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_git_and_kill() {
+        assert_eq!(replacements("git sta").1, vec!["stash ", "status "]);
+        assert!(replacements("git -").1.is_empty());
+        assert_eq!(replacements("kill -KI").1, vec!["-KILL "]);
+        assert_eq!(replacements("kill -s TE").1, vec!["TERM "]);
+
+        let pid = std::process::id().to_string();
+        let (_, candidates) = complete(&format!("kill {}", pid), 5 + pid.len());
+        assert!(
+            candidates
+                .iter()
+                .any(|candidate| candidate.replacement == format!("{} ", pid))
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("my notes.txt"), "").unwrap();
+        fs::write(dir.path().join("my.log"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+        fs::create_dir(dir.path().join("mydir")).unwrap();
+        let base = format!("{}/", dir.path().display());
+
+        let (start, candidates) = complete(&format!("cat {}my", base), base.len() + 6);
+        assert_eq!(start, 4);
+        let replacements: Vec<&str> = candidates
+            .iter()
+            .map(|c| &c.replacement[base.len()..])
+            .collect();
+        assert_eq!(replacements, vec!["my\\ notes.txt ", "my.log ", "mydir/"]);
+        assert_eq!(candidates[2].display, "mydir/");
+
+        let line = format!("cat '{}my n", base);
+        let (_, candidates) = complete(&line, line.len());
+        assert_eq!(
+            candidates[0].replacement,
+            format!("'{}my notes.txt' ", base)
+        );
+
+        let line = format!("cat {}.h", base);
+        assert_eq!(complete(&line, line.len()).1.len(), 1);
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let candidates = vec![Candidate::word("status"), Candidate::word("stash")];
+        assert_eq!(common_prefix(&candidates), "sta");
+        assert_eq!(common_prefix(&candidates[..1]), "status ");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
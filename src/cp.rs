@@ -0,0 +1,501 @@
+//! `cp`, and the copying `mv` falls back on to move files to another
+//! filesystem.
+
+use crate::commands::{self, Command};
+use filetime::FileTime;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// How `cp` goes about copying.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// `-r`, `-R`: copy directories, and links as links
+    pub recursive: bool,
+    /// `-p`: keep the modes and times of what is copied
+    pub preserve: bool,
+    /// `-n`: leave files that are already there alone
+    pub no_clobber: bool,
+    /// `-u`: only replace files that are older than what is copied
+    pub update: bool,
+    /// `-v`
+    pub verbose: bool,
+}
+
+/// Pairs each source with where it goes: into the last operand if it is a
+/// directory, and otherwise, for a single source, to the last operand
+/// itself. `operands` holds at least two paths.
+pub fn destinations(operands: &[String]) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let (target, sources) = operands.split_last().expect("a source and a target");
+    let target = Path::new(target);
+    if target.is_dir() {
+        Ok(sources
+            .iter()
+            .map(|source| {
+                let source = PathBuf::from(source);
+                // `.` and `..` have no name of their own
+                let name = source
+                    .file_name()
+                    .map(|name| name.to_os_string())
+                    .or_else(|| {
+                        fs::canonicalize(&source)
+                            .ok()?
+                            .file_name()
+                            .map(|n| n.to_os_string())
+                    })
+                    .unwrap_or_default();
+                let destination = target.join(name);
+                (source, destination)
+            })
+            .collect())
+    } else if sources.len() > 1 {
+        Err(format!("target '{}' is not a directory", target.display()))
+    } else {
+        Ok(vec![(PathBuf::from(&sources[0]), target.to_path_buf())])
+    }
+}
+
+/// Whether `path` is `dir` or somewhere inside it, even if `path` does not
+/// exist yet.
+pub fn is_within(path: &Path, dir: &Path) -> bool {
+    let Ok(dir) = fs::canonicalize(dir) else {
+        return false;
+    };
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            fs::canonicalize(parent).map(|parent| parent.join(name))
+        }
+        _ => fs::canonicalize(path),
+    };
+    resolved.is_ok_and(|path| path.starts_with(dir))
+}
+
+/// Whether two paths name the same existing file.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Whether a file already at a destination should stay rather than be
+/// replaced by `source`, as `-n` and `-u` say.
+pub fn keep_existing(
+    existing: &fs::Metadata,
+    source: &fs::Metadata,
+    no_clobber: bool,
+    update: bool,
+) -> bool {
+    let newer = match (existing.modified(), source.modified()) {
+        (Ok(existing), Ok(source)) => existing >= source,
+        _ => false,
+    };
+    no_clobber || (update && newer)
+}
+
+/// Gives `to` the access and modification times in `metadata`.
+fn copy_times(metadata: &fs::Metadata, to: &Path) -> io::Result<()> {
+    filetime::set_file_times(
+        to,
+        FileTime::from_last_access_time(metadata),
+        FileTime::from_last_modification_time(metadata),
+    )
+}
+
+/// Makes `to` a symbolic link pointing where `from` does.
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, to);
+    #[cfg(windows)]
+    return if fs::metadata(from).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, to)
+    } else {
+        std::os::windows::fs::symlink_file(target, to)
+    };
+}
+
+/// Copies a file or a whole directory, keeping links as links and the
+/// modes and times of everything, as moving it would.
+pub fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())?;
+        copy_times(&metadata, to)
+    } else if metadata.file_type().is_symlink() {
+        copy_link(from, to)
+    } else {
+        fs::copy(from, to)?;
+        copy_times(&metadata, to)
+    }
+}
+
+/// One run of `cp`, with the streams it reports on.
+struct Copier<'a> {
+    options: Options,
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+    status: i32,
+}
+
+impl Copier<'_> {
+    fn fail(&mut self, message: String) {
+        self.status = commands::report_error(self.stderr, "cp", message);
+    }
+
+    fn copy_operand(&mut self, source: &Path, destination: &Path) {
+        // Without -r, links are copied as what they point to
+        let metadata = if self.options.recursive {
+            fs::symlink_metadata(source)
+        } else {
+            fs::metadata(source)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let message = format!(
+                    "cannot access '{}': {}",
+                    source.display(),
+                    commands::io_message(&e)
+                );
+                return self.fail(message);
+            }
+        };
+        if metadata.is_dir() {
+            if !self.options.recursive {
+                let message = format!(
+                    "-r not specified; omitting directory '{}'",
+                    source.display()
+                );
+                return self.fail(message);
+            }
+            if is_within(destination, source) {
+                let message = format!(
+                    "cannot copy a directory, '{}', into itself, '{}'",
+                    source.display(),
+                    destination.display()
+                );
+                return self.fail(message);
+            }
+        }
+        if same_file(source, destination) {
+            let message = format!(
+                "'{}' and '{}' are the same file",
+                source.display(),
+                destination.display()
+            );
+            return self.fail(message);
+        }
+        self.copy(source, destination, &metadata);
+    }
+
+    fn copy(&mut self, source: &Path, destination: &Path, metadata: &fs::Metadata) {
+        let existing = fs::symlink_metadata(destination).ok();
+        if metadata.is_dir() {
+            match existing {
+                Some(existing) if existing.is_dir() => {}
+                Some(_) => {
+                    let message = format!(
+                        "cannot overwrite non-directory '{}' with directory '{}'",
+                        destination.display(),
+                        source.display()
+                    );
+                    return self.fail(message);
+                }
+                None => {
+                    if let Err(e) = fs::create_dir(destination) {
+                        let message = format!(
+                            "cannot create directory '{}': {}",
+                            destination.display(),
+                            commands::io_message(&e)
+                        );
+                        return self.fail(message);
+                    }
+                    self.report(source, destination);
+                }
+            }
+            let entries = match fs::read_dir(source) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    let message = format!(
+                        "cannot access '{}': {}",
+                        source.display(),
+                        commands::io_message(&e)
+                    );
+                    return self.fail(message);
+                }
+            };
+            for entry in entries.flatten() {
+                let child = entry.path();
+                match fs::symlink_metadata(&child) {
+                    Ok(child_metadata) => self.copy(
+                        &child,
+                        &destination.join(entry.file_name()),
+                        &child_metadata,
+                    ),
+                    Err(e) => {
+                        let message = format!(
+                            "cannot access '{}': {}",
+                            child.display(),
+                            commands::io_message(&e)
+                        );
+                        self.fail(message);
+                    }
+                }
+            }
+            // Last, so that copying into it did not change its times
+            if self.options.preserve {
+                let preserved = fs::set_permissions(destination, metadata.permissions())
+                    .and_then(|()| copy_times(metadata, destination));
+                self.check_preserved(destination, preserved);
+            }
+            return;
+        }
+
+        if let Some(existing) = existing {
+            if existing.is_dir() {
+                let message = format!(
+                    "cannot overwrite directory '{}' with non-directory",
+                    destination.display()
+                );
+                return self.fail(message);
+            }
+            if keep_existing(
+                &existing,
+                metadata,
+                self.options.no_clobber,
+                self.options.update,
+            ) {
+                return;
+            }
+        }
+        let copied = if metadata.file_type().is_symlink() {
+            fs::remove_file(destination)
+                .or_else(|e| {
+                    if e.kind() == io::ErrorKind::NotFound {
+                        Ok(())
+                    } else {
+                        Err(e)
+                    }
+                })
+                .and_then(|()| copy_link(source, destination))
+        } else {
+            fs::copy(source, destination).map(|_| ())
+        };
+        if let Err(e) = copied {
+            let message = format!(
+                "cannot copy '{}' to '{}': {}",
+                source.display(),
+                destination.display(),
+                commands::io_message(&e)
+            );
+            return self.fail(message);
+        }
+        if self.options.preserve && !metadata.file_type().is_symlink() {
+            self.check_preserved(destination, copy_times(metadata, destination));
+        }
+        self.report(source, destination);
+    }
+
+    fn check_preserved(&mut self, destination: &Path, result: io::Result<()>) {
+        if let Err(e) = result {
+            let message = format!(
+                "cannot preserve times for '{}': {}",
+                destination.display(),
+                commands::io_message(&e)
+            );
+            self.fail(message);
+        }
+    }
+
+    fn report(&mut self, source: &Path, destination: &Path) {
+        if self.options.verbose {
+            let _ = writeln!(
+                self.stdout,
+                "'{}' -> '{}'",
+                source.display(),
+                destination.display()
+            );
+        }
+    }
+}
+
+/// `cp [-nprRuv] SOURCE... DEST`
+pub struct Cp;
+
+impl Cp {
+    /// Collects the flags, with `-a` standing for `-rp`, and puts the
+    /// sources and the destination after them in `operands`, leaving the
+    /// caller to split off the destination. An unknown flag gives `None`.
+    fn parse_args(args: &[String], operands: &mut Vec<String>) -> Option<Options> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(args.cloned());
+                    break;
+                }
+                "--recursive" => options.recursive = true,
+                "--preserve" => options.preserve = true,
+                "--no-clobber" => options.no_clobber = true,
+                "--update" => options.update = true,
+                "--verbose" => options.verbose = true,
+                flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                    for flag in flags[1..].chars() {
+                        match flag {
+                            'r' | 'R' => options.recursive = true,
+                            'p' => options.preserve = true,
+                            'a' => {
+                                options.recursive = true;
+                                options.preserve = true;
+                            }
+                            'n' => options.no_clobber = true,
+                            'u' => options.update = true,
+                            'v' => options.verbose = true,
+                            _ => return None,
+                        }
+                    }
+                }
+                _ if arg.starts_with("--") => return None,
+                _ => operands.push(arg.clone()),
+            }
+        }
+        Some(options)
+    }
+}
+
+impl Command for Cp {
+    fn name(&self) -> &'static str {
+        "cp"
+    }
+
+    fn usage(&self) -> &'static str {
+        "cp [-anprRuv] SOURCE... DEST"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy files, or directories with -r, keeping their times with -p"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let mut operands = Vec::new();
+        let options = match Self::parse_args(args, &mut operands) {
+            Some(options) if operands.len() >= 2 => options,
+            _ => return commands::usage_error(stderr, self.usage()),
+        };
+        let pairs = match destinations(&operands) {
+            Ok(pairs) => pairs,
+            Err(message) => return commands::report_error(stderr, self.name(), message),
+        };
+        let mut copier = Copier {
+            options,
+            stdout,
+            stderr,
+            status: 0,
+        };
+        for (source, destination) in pairs {
+            copier.copy_operand(&source, &destination);
+        }
+        copier.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cp(args: &[&Path]) -> (i32, String, String) {
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        let code = Cp.run(&args, &mut io::empty(), &mut output, &mut errors);
+        (
+            code,
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_copy_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("a"), "new").unwrap();
+        fs::write(path("b"), "old").unwrap();
+        fs::create_dir(path("into")).unwrap();
+
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(path("a"), old).unwrap();
+        assert_eq!(cp(&[Path::new("-p"), &path("a"), &path("into")]).0, 0);
+        let copied = fs::metadata(path("into/a")).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&copied), old);
+
+        // b is newer than a, so -u leaves it, as -n does
+        assert_eq!(cp(&[Path::new("-u"), &path("a"), &path("b")]).0, 0);
+        assert_eq!(cp(&[Path::new("-n"), &path("a"), &path("b")]).0, 0);
+        assert_eq!(fs::read_to_string(path("b")).unwrap(), "old");
+        let (code, output, _) = cp(&[Path::new("-v"), &path("a"), &path("b")]);
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(path("b")).unwrap(), "new");
+        assert_eq!(
+            output,
+            format!("'{}' -> '{}'\n", path("a").display(), path("b").display())
+        );
+
+        let (code, _, errors) = cp(&[&path("a"), &path("b"), &path("a")]);
+        assert_eq!(code, 1);
+        assert!(errors.contains("is not a directory"));
+        let (_, _, errors) = cp(&[&path("a"), &path("a")]);
+        assert!(errors.contains("are the same file"));
+        let (_, _, errors) = cp(&[&path("missing"), &path("c")]);
+        assert!(errors.contains(&format!(
+            "cannot access '{}': No such file",
+            path("missing").display()
+        )));
+        assert_eq!(cp(&[&path("a")]).0, 2);
+    }
+
+    #[test]
+    fn test_copy_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::create_dir_all(path("src/deep")).unwrap();
+        fs::write(path("src/deep/file"), "x").unwrap();
+
+        let (code, _, errors) = cp(&[&path("src"), &path("dest")]);
+        assert_eq!(code, 1);
+        assert!(errors.contains("-r not specified; omitting directory"));
+
+        assert_eq!(cp(&[Path::new("-r"), &path("src"), &path("dest")]).0, 0);
+        assert_eq!(fs::read_to_string(path("dest/deep/file")).unwrap(), "x");
+        // Into an existing directory, it goes inside
+        assert_eq!(cp(&[Path::new("-R"), &path("src"), &path("dest")]).0, 0);
+        assert!(path("dest/src/deep/file").exists());
+
+        let (code, _, errors) = cp(&[Path::new("-r"), &path("src"), &path("src/deep")]);
+        assert_eq!(code, 1);
+        assert!(errors.contains("into itself"));
+
+        copy_all(&path("src"), &path("moved")).unwrap();
+        assert!(path("moved/deep/file").exists());
+    }
+}
@@ -1,14 +1,43 @@
+use crate::commands::{self, Command};
+use std::io::{self, Read, Write};
 use sysinfo::Disks;
 
-pub fn execute() {
+pub struct Df;
+
+impl Command for Df {
+    fn name(&self) -> &'static str {
+        "df"
+    }
+
+    fn usage(&self) -> &'static str {
+        "df"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show disk usage"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
     let disks = Disks::new_with_refreshed_list();
 
     // Print header
-    println!(
+    writeln!(
+        out,
         "{:<20} {:<15} {:<15} {:<15}",
         "Disk", "Total", "Available", "Used"
-    );
-    println!("{:-<65}", "");
+    )?;
+    writeln!(out, "{:-<65}", "")?;
 
     // Print disk information in rows
     for disk in disks.list() {
@@ -16,14 +45,16 @@ pub fn execute() {
         let available = disk.available_space();
         let used = total - available;
 
-        println!(
+        writeln!(
+            out,
             "{:<20} {:<15} {:<15} {:<15}",
             format!("{:?}", disk.name()),
             format_memory(total),
             format_memory(available),
             format_memory(used)
-        );
+        )?;
     }
+    Ok(())
 }
 
 fn format_memory(bytes: u64) -> String {
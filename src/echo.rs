@@ -1,8 +1,186 @@
-use std::io::{self, Write};
+use crate::commands::{self, Command};
+use std::io::{self, Read, Write};
 
-pub fn run(args: &[String]) {
-    print!("{}", args.join(" "));
-    io::stdout().flush().unwrap();
+pub struct Echo;
+
+impl Command for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn usage(&self) -> &'static str {
+        "echo [-neE] [STRING]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the arguments and a newline (-n leaves it off, -e reads \\t, \\n, \\x41 and \\033)"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), run(args, stdout))
+    }
+}
+
+/// Where backslash escapes are read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Escapes {
+    /// `echo -e` and `printf %b`: octal is `\0NNN`, and `\c` ends the output
+    Echo,
+    /// A `printf` format: octal is `\NNN`
+    Format,
+}
+
+/// The bytes `text` stands for once its backslash escapes are replaced,
+/// and whether a `\c` asked for the output to stop there.
+pub fn unescape(text: &str, escapes: Escapes) -> (Vec<u8>, bool) {
+    let bytes = text.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            output.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        // The digits of a number of at most `max` digits in `radix` from `start`
+        let digits = |start: usize, max: usize, radix: u32| {
+            let end = bytes[start..]
+                .iter()
+                .take(max)
+                .take_while(|b| (**b as char).is_digit(radix))
+                .count();
+            let value = u32::from_str_radix(&text[start..start + end], radix).ok();
+            (value, start + end)
+        };
+        let escape = bytes[i + 1];
+        i += 2;
+        let simple = match escape {
+            b'\\' => Some(b'\\'),
+            b'a' => Some(0x07),
+            b'b' => Some(0x08),
+            b'e' | b'E' => Some(0x1b),
+            b'f' => Some(0x0c),
+            b'n' => Some(b'\n'),
+            b'r' => Some(b'\r'),
+            b't' => Some(b'\t'),
+            b'v' => Some(0x0b),
+            b'"' | b'\'' if escapes == Escapes::Format => Some(escape),
+            _ => None,
+        };
+        if let Some(byte) = simple {
+            output.push(byte);
+            continue;
+        }
+        match escape {
+            b'c' if escapes == Escapes::Echo => return (output, true),
+            b'0'..=b'7' => {
+                let (value, end) = match escapes {
+                    Escapes::Echo if escape == b'0' => digits(i, 3, 8),
+                    _ => digits(i - 1, 3, 8),
+                };
+                // A 0 on its own is the NUL it starts
+                output.push(value.unwrap_or(0) as u8);
+                i = end;
+            }
+            b'x' => match digits(i, 2, 16) {
+                (Some(value), end) => {
+                    output.push(value as u8);
+                    i = end;
+                }
+                _ => output.extend_from_slice(b"\\x"),
+            },
+            b'u' | b'U' => {
+                let max = if escape == b'u' { 4 } else { 8 };
+                match digits(i, max, 16) {
+                    (Some(value), end) if char::from_u32(value).is_some() => {
+                        let c = char::from_u32(value).unwrap_or_default();
+                        output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        i = end;
+                    }
+                    _ => output.extend_from_slice(&[b'\\', escape]),
+                }
+            }
+            _ => {
+                // Not an escape, so it is printed as it was written
+                output.push(b'\\');
+                i -= 1;
+            }
+        }
+    }
+    (output, false)
+}
+
+/// Prints the arguments separated by spaces, as `echo` does. Leading
+/// arguments made only of `-n`, `-e` and `-E` are options, as in bash.
+pub fn run(args: &[String], out: &mut dyn Write) -> io::Result<()> {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = args;
+    while let Some(option) = words.first()
+        && option.len() > 1
+        && option.starts_with('-')
+        && option[1..].chars().all(|c| matches!(c, 'n' | 'e' | 'E'))
+    {
+        for c in option[1..].chars() {
+            match c {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        words = &words[1..];
+    }
+
+    let text = words.join(" ");
+    if escapes {
+        let (bytes, stopped) = unescape(&text, Escapes::Echo);
+        out.write_all(&bytes)?;
+        if stopped {
+            return out.flush();
+        }
+    } else {
+        out.write_all(text.as_bytes())?;
+    }
+    if newline {
+        out.write_all(b"\n")?;
+    }
+    out.flush()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn echo(args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut output = Vec::new();
+        run(&args, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_options() {
+        assert_eq!(echo(&[]), "\n");
+        assert_eq!(echo(&["-n", "a", "b"]), "a b");
+        assert_eq!(echo(&[r"a\tb"]), "a\\tb\n");
+        assert_eq!(echo(&["-e", r"a\tb\n"]), "a\tb\n\n");
+        assert_eq!(echo(&["-ne", r"\x41\0101\033[0m"]), "AA\x1b[0m");
+        assert_eq!(echo(&["-eE", r"\t"]), "\\t\n");
+        assert_eq!(echo(&["-e", r"stop\chere"]), "stop");
+        assert_eq!(echo(&["-n-", "-x"]), "-n- -x\n");
+        assert_eq!(echo(&["a", "-n"]), "a -n\n");
+    }
+
+    #[test]
+    fn test_unescape() {
+        let format = |text| String::from_utf8(unescape(text, Escapes::Format).0).unwrap();
+        assert_eq!(format(r"\101\0101\q\x4a\x\u00e9\\"), "A\u{8}1\\qJ\\xé\\");
+        assert_eq!(format(r#"\c\'end\"\"#), "\\c'end\"\\");
+    }
+}
@@ -0,0 +1,213 @@
+use crate::pipeline::{self, ByteStream, Stage, StageOutput};
+use crate::redirect::{Input, Redirections, Target};
+use crate::vars;
+use futures::StreamExt;
+use futures::stream;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use tokio::io::AsyncWriteExt;
+
+/// Exit status used when a program cannot be found, as in POSIX shells.
+pub const NOT_FOUND_STATUS: i32 = 127;
+
+/// Looks a program up on the shell's PATH.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    which::which_in(name, vars::get("PATH"), cwd).ok()
+}
+
+/// Runs a program in the foreground with the terminal's stdin, stdout and
+/// stderr, except where they are redirected.
+pub fn run_foreground(
+    program: &str,
+    args: &[String],
+    redirections: Redirections,
+) -> io::Result<i32> {
+    let mut command = Command::new(program);
+    vars::apply(&mut command);
+    command
+        .args(args)
+        .stdout(redirections.stdout.stdio()?)
+        .stderr(redirections.stderr.stdio()?);
+
+    let status = match redirections.stdin {
+        None => command.status()?,
+        Some(Input::File(file)) => command.stdin(file).status()?,
+        Some(Input::Text(text)) => run_with_input(&mut command, text.as_bytes())?,
+        Some(Input::Bytes(bytes)) => run_with_input(&mut command, &bytes)?,
+    };
+    Ok(exit_code(status))
+}
+
+/// The exit code a shell reports for a finished program. Programs killed by
+/// a signal report 128 plus the signal number, as in POSIX shells.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+fn run_with_input(command: &mut Command, input: &[u8]) -> io::Result<ExitStatus> {
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A program that exits without reading is not an error
+        let _ = stdin.write_all(input);
+    }
+    child.wait()
+}
+
+/// A pipeline stage backed by a child process whose stdin and stdout are
+/// connected to the neighbouring stages.
+pub struct ExternalStage {
+    program: String,
+    args: Vec<String>,
+    stderr: Target,
+    inherit_stdin: bool,
+}
+
+impl ExternalStage {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            stderr: Target::Stderr,
+            inherit_stdin: false,
+        }
+    }
+}
+
+impl Stage for ExternalStage {
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput {
+        let mut command = tokio::process::Command::new(&self.program);
+        vars::apply(command.as_std_mut());
+        let stdin = if self.inherit_stdin {
+            Stdio::inherit()
+        } else {
+            Stdio::piped()
+        };
+        command.args(&self.args).stdin(stdin).stdout(Stdio::piped());
+        // `2>&1` pipes stderr too so it can be merged into the output stream
+        let stderr = match &self.stderr {
+            Target::Stdout => Ok(Stdio::piped()),
+            Target::Stderr => Ok(Stdio::inherit()),
+            target => target.stdio(),
+        };
+        let spawned = stderr.and_then(|stderr| command.stderr(stderr).spawn());
+
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                let (message, code) = if e.kind() == io::ErrorKind::NotFound {
+                    ("command not found".to_string(), NOT_FOUND_STATUS)
+                } else {
+                    (e.to_string(), 126)
+                };
+                let error = io::Error::new(e.kind(), message);
+                return StageOutput {
+                    stream: stream::once(async move { Err(error) }).boxed(),
+                    status: Some(Box::pin(async move { Ok(code) })),
+                };
+            }
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            tokio::spawn(async move {
+                let mut input = input;
+                while let Some(Ok(chunk)) = input.next().await {
+                    if stdin.write_all(&chunk).await.is_err() {
+                        // The child exited or closed its stdin
+                        break;
+                    }
+                }
+                // Dropping stdin closes the pipe so the child sees end of input
+            });
+        }
+
+        let mut stream = match child.stdout.take() {
+            Some(stdout) => pipeline::reader_stream(stdout),
+            None => stream::empty().boxed(),
+        };
+        if let Some(stderr) = child.stderr.take() {
+            stream = stream::select(stream, pipeline::reader_stream(stderr)).boxed();
+        }
+
+        StageOutput {
+            stream,
+            status: Some(Box::pin(async move {
+                let status = child.wait().await?;
+                Ok(exit_code(status))
+            })),
+        }
+    }
+
+    fn redirect_stderr(&mut self, target: Target) {
+        self.stderr = target;
+    }
+
+    fn inherit_stdin(&mut self) -> bool {
+        self.inherit_stdin = true;
+        true
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::pipeline::{parse_stage, run_stages};
+
+    fn argv(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[tokio::test]
+    async fn test_builtin_into_external() {
+        let file_path = "test_external_sort.txt";
+        tokio::fs::write(file_path, "pear\napple\nfig\n")
+            .await
+            .unwrap();
+
+        let stages = vec![
+            parse_stage(&argv(&format!("cat {}", file_path))).unwrap(),
+            parse_stage(&argv("sort")).unwrap(),
+        ];
+        let mut output = Vec::new();
+        let statuses = run_stages(stages, &mut output).await;
+
+        assert_eq!(String::from_utf8(output).unwrap(), "apple\nfig\npear\n");
+        assert!(statuses.iter().all(|s| s.success()));
+
+        tokio::fs::remove_file(file_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_external_into_builtin() {
+        let stages = vec![
+            parse_stage(&argv("printf a\\nb\\nc\\n")).unwrap(),
+            parse_stage(&argv("head -n 2")).unwrap(),
+        ];
+        let mut output = Vec::new();
+        run_stages(stages, &mut output).await;
+
+        assert_eq!(String::from_utf8(output).unwrap(), "a\nb\n");
+    }
+
+    #[tokio::test]
+    async fn test_exit_statuses_collected() {
+        let stages = vec![
+            parse_stage(&argv("false")).unwrap(),
+            parse_stage(&argv("winix-no-such-program")).unwrap(),
+        ];
+        let mut output = Vec::new();
+        let statuses = run_stages(stages, &mut output).await;
+
+        assert_eq!(statuses[0].code, 1);
+        assert_eq!(statuses[1].code, NOT_FOUND_STATUS);
+        assert!(statuses[1].error.is_some());
+    }
+}
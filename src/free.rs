@@ -1,13 +1,42 @@
+use crate::commands::{self, Command};
+use std::io::{self, Read, Write};
 use sysinfo::System;
 
-pub fn execute() {
+pub struct Free;
+
+impl Command for Free {
+    fn name(&self) -> &'static str {
+        "free"
+    }
+
+    fn usage(&self) -> &'static str {
+        "free"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show memory usage"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
     let mut sys = System::new_all();
     sys.refresh_all();
 
-    println!("Used memory : {}", format_memory(sys.used_memory()));
-    println!("Total memory: {}", format_memory(sys.total_memory()));
-    println!("Total swap  : {}", format_memory(sys.total_swap()));
-    println!("Used swap   : {}", format_memory(sys.used_swap()));
+    writeln!(out, "Used memory : {}", format_memory(sys.used_memory()))?;
+    writeln!(out, "Total memory: {}", format_memory(sys.total_memory()))?;
+    writeln!(out, "Total swap  : {}", format_memory(sys.total_swap()))?;
+    writeln!(out, "Used swap   : {}", format_memory(sys.used_swap()))?;
+    Ok(())
 }
 
 fn format_memory(bytes: u64) -> String {
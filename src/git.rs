@@ -1,9 +1,68 @@
+use crate::commands;
+use crate::external::{self, ExternalStage};
+use crate::pipeline::{self, Stage};
+use crate::vars;
 use colored::Colorize;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::process::Command;
 
-/// Execute git commands by shelling out to the system git
-pub fn execute(args: &[&str]) {
+pub struct Git;
+
+impl commands::Command for Git {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn usage(&self) -> &'static str {
+        "git <command> [options]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run git version control commands"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        if !is_git_available() {
+            return commands::report_error(
+                stderr,
+                self.name(),
+                "Git is not installed or not in PATH",
+            );
+        }
+        if args.is_empty() {
+            return commands::exit_code(stderr, self.name(), show_git_help(stdout));
+        }
+        pipeline::run_stage(
+            self.name(),
+            Ok(Box::new(stage(args))),
+            stdin,
+            stdout,
+            stderr,
+        )
+    }
+
+    fn stage(&self, args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        if args.is_empty() || !is_git_available() {
+            return None;
+        }
+        Some(Ok(Box::new(stage(args))))
+    }
+
+    fn run_in_terminal(&self, args: &[String]) -> Option<i32> {
+        Some(execute(
+            &args.iter().map(String::as_str).collect::<Vec<_>>(),
+        ))
+    }
+}
+
+/// Execute git commands by shelling out to the system git, returning git's exit code
+pub fn execute(args: &[&str]) -> i32 {
     // Check if git is available
     if !is_git_available() {
         println!("{}", "Error: Git is not installed or not in PATH".red());
@@ -11,17 +70,17 @@ pub fn execute(args: &[&str]) {
             "{}",
             "Please install Git and ensure it's in your PATH".yellow()
         );
-        return;
+        return external::NOT_FOUND_STATUS;
     }
 
     // If no arguments provided, show git help
     if args.is_empty() {
-        show_git_help();
-        return;
+        let _ = show_git_help(&mut io::stdout());
+        return 0;
     }
 
     // Execute the git command
-    execute_git_command(args);
+    execute_git_command(args)
 }
 
 /// Check if git is available on the system
@@ -32,40 +91,26 @@ pub fn is_git_available() -> bool {
     }
 }
 
-/// Execute a git command with the provided arguments
-fn execute_git_command(args: &[&str]) {
+/// Execute a git command with the provided arguments, streaming its output to the terminal
+fn execute_git_command(args: &[&str]) -> i32 {
     let mut cmd = Command::new("git");
+    vars::apply(&mut cmd);
     cmd.args(args);
 
-    // Execute the command and handle output
-    match cmd.output() {
-        Ok(output) => {
-            // Print stdout
-            if !output.stdout.is_empty() {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
-            }
-
-            // Print stderr
-            if !output.stderr.is_empty() {
-                eprint!("{}", String::from_utf8_lossy(&output.stderr));
-            }
-
-            // If the command failed, show the exit code
-            if !output.status.success() {
-                if let Some(code) = output.status.code() {
-                    eprintln!(
-                        "{}",
-                        format!("Git command failed with exit code: {}", code).red()
-                    );
-                }
-            }
-        }
+    match cmd.status() {
+        Ok(status) => external::exit_code(status),
         Err(e) => {
             eprintln!("{}", format!("Failed to execute git command: {}", e).red());
+            1
         }
     }
 }
 
+/// Pipeline stage running git, so its output can stream into other commands
+pub fn stage(args: &[String]) -> ExternalStage {
+    ExternalStage::new("git", args.to_vec())
+}
+
 /// Show interactive git mode for complex operations
 pub fn interactive_mode() {
     println!("{}", "Git Interactive Mode".bold().green());
@@ -100,7 +145,13 @@ pub fn interactive_mode() {
 
                 // Split the input into arguments
                 let args: Vec<&str> = input.split_whitespace().collect();
-                execute_git_command(&args);
+                let code = execute_git_command(&args);
+                if code != 0 {
+                    eprintln!(
+                        "{}",
+                        format!("Git command failed with exit code: {}", code).red()
+                    );
+                }
             }
             Err(e) => {
                 eprintln!("{}", format!("Error reading input: {}", e).red());
@@ -111,90 +162,80 @@ pub fn interactive_mode() {
 }
 
 /// Show git help and common commands
-fn show_git_help() {
-    println!("{}", "Git Commands Available".bold().green());
-    println!("{}", "Usage: git <command> [options]".dimmed());
-    println!();
+fn show_git_help(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{}", "Git Commands Available".bold().green())?;
+    writeln!(out, "{}", "Usage: git <command> [options]".dimmed())?;
+    writeln!(out)?;
 
-    println!("{}", "Most Common Git Commands:".bold().white());
-    println!("  {:<15} {}", "status".yellow(), "Show working tree status");
-    println!("  {:<15} {}", "log".yellow(), "Show commit logs");
-    println!(
-        "  {:<15} {}",
-        "add <file>".yellow(),
-        "Add file contents to index"
-    );
-    println!(
-        "  {:<15} {}",
-        "commit".yellow(),
-        "Record changes to repository"
-    );
-    println!("  {:<15} {}", "push".yellow(), "Update remote refs");
-    println!(
-        "  {:<15} {}",
-        "pull".yellow(),
-        "Fetch and merge from remote"
-    );
-    println!("  {:<15} {}", "clone <url>".yellow(), "Clone a repository");
-    println!(
-        "  {:<15} {}",
-        "branch".yellow(),
-        "List, create, or delete branches"
-    );
-    println!(
-        "  {:<15} {}",
-        "checkout".yellow(),
-        "Switch branches or restore files"
-    );
-    println!(
-        "  {:<15} {}",
-        "merge".yellow(),
-        "Join development histories"
-    );
-    println!(
-        "  {:<15} {}",
-        "diff".yellow(),
-        "Show changes between commits"
-    );
-    println!(
-        "  {:<15} {}",
-        "reset".yellow(),
-        "Reset current HEAD to state"
-    );
-    println!(
-        "  {:<15} {}",
-        "stash".yellow(),
-        "Stash changes in working directory"
-    );
-    println!(
-        "  {:<15} {}",
-        "remote".yellow(),
-        "Manage remote repositories"
-    );
-    println!(
-        "  {:<15} {}",
-        "init".yellow(),
-        "Create empty Git repository"
-    );
-    println!();
+    writeln!(out, "{}", "Most Common Git Commands:".bold().white())?;
+    writeln!(out, "  {:<15} Show working tree status", "status".yellow())?;
+    writeln!(out, "  {:<15} Show commit logs", "log".yellow())?;
+    writeln!(
+        out,
+        "  {:<15} Add file contents to index",
+        "add <file>".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<15} Record changes to repository",
+        "commit".yellow()
+    )?;
+    writeln!(out, "  {:<15} Update remote refs", "push".yellow())?;
+    writeln!(out, "  {:<15} Fetch and merge from remote", "pull".yellow())?;
+    writeln!(out, "  {:<15} Clone a repository", "clone <url>".yellow())?;
+    writeln!(
+        out,
+        "  {:<15} List, create, or delete branches",
+        "branch".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<15} Switch branches or restore files",
+        "checkout".yellow()
+    )?;
+    writeln!(out, "  {:<15} Join development histories", "merge".yellow())?;
+    writeln!(
+        out,
+        "  {:<15} Show changes between commits",
+        "diff".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<15} Reset current HEAD to state",
+        "reset".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<15} Stash changes in working directory",
+        "stash".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<15} Manage remote repositories",
+        "remote".yellow()
+    )?;
+    writeln!(out, "  {:<15} Create empty Git repository", "init".yellow())?;
+    writeln!(out)?;
 
-    println!("{}", "Examples:".bold().cyan());
-    println!("  {}", "git status".dimmed());
-    println!("  {}", "git log --oneline".dimmed());
-    println!("  {}", "git add .".dimmed());
-    println!("  {}", "git commit -m \"Initial commit\"".dimmed());
-    println!("  {}", "git push origin main".dimmed());
-    println!("  {}", "git pull origin main".dimmed());
-    println!("  {}", "git branch -a".dimmed());
-    println!("  {}", "git checkout -b new-feature".dimmed());
-    println!();
+    writeln!(out, "{}", "Examples:".bold().cyan())?;
+    writeln!(out, "  {}", "git status".dimmed())?;
+    writeln!(out, "  {}", "git log --oneline".dimmed())?;
+    writeln!(out, "  {}", "git add .".dimmed())?;
+    writeln!(out, "  {}", "git commit -m \"Initial commit\"".dimmed())?;
+    writeln!(out, "  {}", "git push origin main".dimmed())?;
+    writeln!(out, "  {}", "git pull origin main".dimmed())?;
+    writeln!(out, "  {}", "git branch -a".dimmed())?;
+    writeln!(out, "  {}", "git checkout -b new-feature".dimmed())?;
+    writeln!(out)?;
 
-    println!("{}", "Interactive Mode:".bold().magenta());
-    println!("  {}", "git --interactive".dimmed());
-    println!(
+    writeln!(out, "{}", "Interactive Mode:".bold().magenta())?;
+    writeln!(out, "  {}", "git --interactive".dimmed())?;
+    writeln!(
+        out,
         "  {}",
         "  Enter interactive git mode for easier command execution".dimmed()
-    );
+    )?;
+    Ok(())
 }
 
 /// Check if current directory is a git repository
@@ -211,7 +252,7 @@ pub fn is_git_repo() -> bool {
 
     // Fallback to git command only if needed (this is slower)
     match Command::new("git")
-        .args(&["rev-parse", "--git-dir"])
+        .args(["rev-parse", "--git-dir"])
         .output()
     {
         Ok(output) => output.status.success(),
@@ -222,7 +263,7 @@ pub fn is_git_repo() -> bool {
 /// Get current git branch name
 pub fn get_current_branch() -> Option<String> {
     match Command::new("git")
-        .args(&["branch", "--show-current"])
+        .args(["branch", "--show-current"])
         .output()
     {
         Ok(output) => {
@@ -241,12 +282,25 @@ pub fn get_current_branch() -> Option<String> {
     }
 }
 
-/// Get git repository status summary
-pub fn get_repo_status() -> Option<String> {
+/// Get the names of the local and remote-tracking branches
+pub fn get_branches() -> Vec<String> {
     match Command::new("git")
-        .args(&["status", "--porcelain"])
+        .args(["branch", "--all", "--format=%(refname:short)"])
         .output()
     {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|branch| !branch.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Get git repository status summary
+pub fn get_repo_status() -> Option<String> {
+    match Command::new("git").args(["status", "--porcelain"]).output() {
         Ok(output) => {
             if output.status.success() {
                 let status = String::from_utf8_lossy(&output.stdout);
@@ -0,0 +1,364 @@
+//! Brace expansion and file name globbing for command arguments.
+//!
+//! Words arrive here after parameter expansion as characters paired with
+//! whether they were quoted, because quoted `*`, `{` and the like stand for
+//! themselves.
+
+use crate::options::{self, ShellOption};
+use std::fs;
+use std::path::Path;
+
+/// A word's characters, each paired with whether it was quoted.
+pub type Chars = [(char, bool)];
+
+/// Expands a word's braces and patterns into the arguments it stands for.
+/// A pattern matching nothing stands for itself unless `nullglob` is set.
+pub fn expand(word: &Chars) -> Vec<String> {
+    let ignore_case = options::is_set(ShellOption::NoCaseGlob);
+    let mut words = Vec::new();
+    for alternative in expand_braces(word) {
+        let text: String = alternative.iter().map(|&(c, _)| c).collect();
+        if !is_pattern(&alternative) {
+            words.push(text);
+            continue;
+        }
+        let matched = glob(&alternative, ignore_case);
+        if matched.is_empty() && !options::is_set(ShellOption::NullGlob) {
+            words.push(text);
+        }
+        words.extend(matched);
+    }
+    words
+}
+
+/// Expands `{a,b}` alternatives, giving one word per combination in order.
+/// Braces without an unquoted comma between them are left as they are.
+pub fn expand_braces(word: &Chars) -> Vec<Vec<(char, bool)>> {
+    for (open, &(c, quoted)) in word.iter().enumerate() {
+        if c != '{' || quoted {
+            continue;
+        }
+        let Some((close, commas)) = matching_brace(word, open) else {
+            continue;
+        };
+        if commas.is_empty() {
+            continue;
+        }
+
+        let mut bounds = vec![open];
+        bounds.extend(commas);
+        bounds.push(close);
+        return bounds
+            .windows(2)
+            .flat_map(|pair| {
+                let mut alternative = word[..open].to_vec();
+                alternative.extend_from_slice(&word[pair[0] + 1..pair[1]]);
+                alternative.extend_from_slice(&word[close + 1..]);
+                expand_braces(&alternative)
+            })
+            .collect();
+    }
+    vec![word.to_vec()]
+}
+
+/// The `}` closing the `{` at `open`, and the commas directly inside the pair.
+fn matching_brace(word: &Chars, open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    for (i, &(c, quoted)) in word.iter().enumerate().skip(open) {
+        match c {
+            _ if quoted => {}
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((i, commas));
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether the word contains an unquoted `*`, `?` or `[`.
+pub fn is_pattern(word: &Chars) -> bool {
+    word.iter()
+        .any(|&(c, quoted)| !quoted && matches!(c, '*' | '?' | '['))
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || (cfg!(windows) && c == '\\')
+}
+
+/// The paths matching a pattern, sorted. `**` as a whole path component
+/// matches any number of directories. Names starting with `.` only match a
+/// pattern that starts with a literal `.`.
+pub fn glob(pattern: &Chars, ignore_case: bool) -> Vec<String> {
+    // Each component keeps the separator written after it
+    let mut components: Vec<(&Chars, Option<char>)> = Vec::new();
+    let mut start = 0;
+    for (i, &(c, _)) in pattern.iter().enumerate() {
+        if is_separator(c) {
+            components.push((&pattern[start..i], Some(c)));
+            start = i + 1;
+        }
+    }
+    components.push((&pattern[start..], None));
+
+    let mut paths = vec![String::new()];
+    let last = components.len() - 1;
+    for (index, (component, separator)) in components.into_iter().enumerate() {
+        let is_last = index == last;
+        let literal: String = component.iter().map(|&(c, _)| c).collect();
+        // Matches paired with whether the component's separator follows them
+        let mut next: Vec<(String, bool)> = Vec::new();
+
+        for path in &paths {
+            if !is_pattern(component) {
+                next.push((format!("{}{}", path, literal), true));
+            } else if literal == "**" {
+                // Zero directories, so the path already ends in a separator
+                next.push((path.clone(), false));
+                let mut found = Vec::new();
+                descendants(path, separator.unwrap_or('/'), is_last, &mut found);
+                next.extend(found.into_iter().map(|path| (path, true)));
+            } else {
+                let hidden_ok = literal.starts_with('.');
+                for name in read_names(path) {
+                    if (hidden_ok || !name.starts_with('.'))
+                        && matches(component, &name, ignore_case)
+                    {
+                        next.push((format!("{}{}", path, name), true));
+                    }
+                }
+            }
+        }
+
+        // Only directories can lead on to the next component
+        next.retain(|(path, _)| {
+            let path = if path.is_empty() { "." } else { path.as_str() };
+            if is_last && separator.is_none() {
+                fs::symlink_metadata(path).is_ok()
+            } else {
+                Path::new(path).is_dir()
+            }
+        });
+        paths = next
+            .into_iter()
+            .map(|(path, followed)| match separator {
+                Some(separator) if followed => format!("{}{}", path, separator),
+                _ => path,
+            })
+            .collect();
+    }
+
+    paths.retain(|path| !path.is_empty());
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// The names in a directory, where the empty path means the current directory.
+fn read_names(dir: &str) -> Vec<String> {
+    let dir = if dir.is_empty() { "." } else { dir };
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Adds the paths below `dir`, which is empty or ends in a separator,
+/// skipping hidden names and not following symlinks. Only directories are
+/// added unless `files` is set.
+fn descendants(dir: &str, separator: char, files: bool, paths: &mut Vec<String>) {
+    for name in read_names(dir) {
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = format!("{}{}", dir, name);
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|meta| meta.is_dir());
+        if is_dir || files {
+            paths.push(path.clone());
+        }
+        if is_dir {
+            descendants(&format!("{}{}", path, separator), separator, files, paths);
+        }
+    }
+}
+
+/// Matches a file name against one component of a pattern.
+pub fn matches(pattern: &Chars, name: &str, ignore_case: bool) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    match_from(pattern, &name, ignore_case)
+}
+
+fn match_from(pattern: &Chars, name: &[char], ignore_case: bool) -> bool {
+    let same = |a: char, b: char| {
+        if ignore_case {
+            a.to_lowercase().eq(b.to_lowercase())
+        } else {
+            a == b
+        }
+    };
+
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(&('*', false)) => {
+            (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..], ignore_case))
+        }
+        Some(&('?', false)) => {
+            !name.is_empty() && match_from(&pattern[1..], &name[1..], ignore_case)
+        }
+        Some(&('[', false)) => match parse_class(&pattern[1..]) {
+            Some((class, close)) => {
+                !name.is_empty()
+                    && class.matches(name[0], ignore_case)
+                    && match_from(&pattern[close + 2..], &name[1..], ignore_case)
+            }
+            // An unclosed `[` is an ordinary character
+            None => {
+                !name.is_empty()
+                    && name[0] == '['
+                    && match_from(&pattern[1..], &name[1..], ignore_case)
+            }
+        },
+        Some(&(c, _)) => {
+            !name.is_empty()
+                && same(c, name[0])
+                && match_from(&pattern[1..], &name[1..], ignore_case)
+        }
+    }
+}
+
+/// A bracket expression such as `[a-z]` or `[!0-9]`.
+struct Class {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl Class {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        let in_ranges = |c: char| self.ranges.iter().any(|&(low, high)| low <= c && c <= high);
+        let found = if ignore_case {
+            c.to_lowercase().chain(c.to_uppercase()).any(in_ranges)
+        } else {
+            in_ranges(c)
+        };
+        found != self.negated
+    }
+}
+
+/// Parses a bracket expression from just after its `[`, returning it and the
+/// offset of its closing `]` within `pattern`.
+fn parse_class(pattern: &Chars) -> Option<(Class, usize)> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some(&('!' | '^', false)));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let first = i;
+    while i < pattern.len() {
+        let (c, quoted) = pattern[i];
+        // A `]` first in the class is part of it
+        if c == ']' && !quoted && i > first {
+            return Some((Class { negated, ranges }, i));
+        }
+        if pattern.get(i + 1) == Some(&('-', false))
+            && let Some(&(high, _)) = pattern.get(i + 2)
+            && high != ']'
+        {
+            ranges.push((c, high));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unquoted(text: &str) -> Vec<(char, bool)> {
+        text.chars().map(|c| (c, false)).collect()
+    }
+
+    fn text(chars: &Chars) -> String {
+        chars.iter().map(|&(c, _)| c).collect()
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        let words: Vec<String> = expand_braces(&unquoted("a{b,c{d,e}}f{1,2}"))
+            .iter()
+            .map(|word| text(word))
+            .collect();
+        assert_eq!(
+            words,
+            vec!["abf1", "abf2", "acdf1", "acdf2", "acef1", "acef2"]
+        );
+
+        assert_eq!(expand_braces(&unquoted("{a}")).len(), 1);
+        let mut quoted = unquoted("{a,b}");
+        quoted[2].1 = true;
+        assert_eq!(expand_braces(&quoted).len(), 1);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(&unquoted("*.tmp"), "a.tmp", false));
+        assert!(!matches(&unquoted("*.tmp"), "a.tmp.bak", false));
+        assert!(matches(&unquoted("file?.[a-c]"), "file1.b", false));
+        assert!(!matches(&unquoted("file?.[!a-c]"), "file1.b", false));
+        assert!(matches(&unquoted("[]x]"), "]", false));
+        assert!(matches(&unquoted("*.TXT"), "notes.txt", true));
+        assert!(!matches(&unquoted("*.TXT"), "notes.txt", false));
+
+        let mut quoted = unquoted("a*");
+        quoted[1].1 = true;
+        assert!(matches(&quoted, "a*", false));
+        assert!(!matches(&quoted, "ab", false));
+    }
+
+    #[test]
+    fn test_glob_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "a.tmp",
+            "b.tmp",
+            ".hidden.tmp",
+            "sub/c.tmp",
+            "sub/deep/d.tmp",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let base = format!("{}/", dir.path().display());
+        let glob = |pattern: &str| -> Vec<String> {
+            glob(&unquoted(&format!("{}{}", base, pattern)), false)
+                .into_iter()
+                .map(|path| path[base.len()..].replace('\\', "/"))
+                .collect()
+        };
+
+        assert_eq!(glob("*.tmp"), vec!["a.tmp", "b.tmp"]);
+        assert_eq!(glob(".*.tmp"), vec![".hidden.tmp"]);
+        assert_eq!(glob("*/"), vec!["sub/"]);
+        assert_eq!(glob("s*/*.tmp"), vec!["sub/c.tmp"]);
+        assert_eq!(
+            glob("**/*.tmp"),
+            vec!["a.tmp", "b.tmp", "sub/c.tmp", "sub/deep/d.tmp"]
+        );
+        assert!(glob("*.none").is_empty());
+    }
+}
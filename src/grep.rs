@@ -1,90 +1,795 @@
-use std::io::{self, BufRead};
-use std::path::Path;
-use tokio::fs::File as TokioFile;
-use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
-use futures::stream::{self, Stream, StreamExt};
+//! `grep`, which prints the lines of files or its input that match patterns.
+//!
+//! Patterns are regular expressions in the syntax of the `regex` crate, which
+//! is extended syntax, so `-E` is accepted but changes nothing. `-r` skips
+//! what `.gitignore` files ignore, as `git grep` does, unless `--no-ignore`
+//! is given.
+
+use crate::commands::{self, Command};
+use crate::glob;
+use crate::pipeline::{BuiltinStage, ByteStream, Stage};
 use bytes::Bytes;
-use regex::Regex;
+use futures::stream::{self, StreamExt};
+use regex::{Regex, RegexBuilder};
+use std::borrow::BorrowMut;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use tokio::fs::File as TokioFile;
+use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader, Split};
 
-// Sync version for benchmarking
-pub fn grep_sync<S: AsRef<Path>>(pattern: &str, files: Vec<S>) -> io::Result<String> {
-    let regex = Regex::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-    let mut result = String::new();
+/// What `grep` looks for and how it shows it.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub patterns: Vec<String>,
+    pub ignore_case: bool,
+    /// `-v`: select the lines that do not match
+    pub invert: bool,
+    pub count: bool,
+    /// `-l`: print only the names of inputs with a selected line
+    pub files_with_matches: bool,
+    /// `-L`: print only the names of inputs without one
+    pub files_without_match: bool,
+    pub line_numbers: bool,
+    /// `-w`: matches must be whole words
+    pub word: bool,
+    /// `-x`: matches must be whole lines
+    pub whole_line: bool,
+    /// `-F`: patterns are plain strings
+    pub fixed: bool,
+    pub only_matching: bool,
+    pub quiet: bool,
+    /// Lines of context to show before and after selected lines
+    pub before: usize,
+    pub after: usize,
+    /// Stop reading an input after this many selected lines
+    pub max_count: Option<usize>,
+    pub recursive: bool,
+    /// `-R`: follow symbolic links while recursing
+    pub follow_links: bool,
+    /// Whether `-r` skips what `.gitignore` files ignore
+    pub no_ignore: bool,
+    /// Globs for the names of files to search, and of files and
+    /// directories to skip
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub exclude_dir: Vec<String>,
+    /// Whether to start lines with the input's name, by default only when
+    /// there are several inputs
+    pub with_filename: Option<bool>,
+    pub color: bool,
+}
 
-    for file_path in files {
-        let file = std::fs::File::open(&file_path)?;
-        let reader = std::io::BufReader::new(file);
+impl Options {
+    pub fn new(pattern: &str) -> Options {
+        Options {
+            patterns: vec![pattern.to_string()],
+            ..Options::default()
+        }
+    }
 
-        for (line_num, line) in reader.lines().enumerate() {
-            let line = line?;
-            if regex.is_match(&line) {
-                result.push_str(&format!("{}:{}", file_path.as_ref().display(), line_num + 1));
-                result.push_str(": ");
-                result.push_str(&line);
-                result.push('\n');
+    /// Sets an option that takes a value, such as `-A 2`.
+    fn set(&mut self, option: char, value: &str) -> Result<(), String> {
+        let number = |what: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("invalid {} argument '{}'", what, value))
+        };
+        match option {
+            'e' => self.patterns.push(value.to_string()),
+            'm' => self.max_count = Some(number("max count")?),
+            'A' => self.after = number("context length")?,
+            'B' => self.before = number("context length")?,
+            _ => {
+                self.before = number("context length")?;
+                self.after = self.before;
             }
         }
+        Ok(())
     }
+}
 
-    Ok(result)
+/// The patterns, compiled into one regular expression.
+pub struct Matcher {
+    regex: Regex,
+    word: bool,
+}
+
+impl Matcher {
+    pub fn new(options: &Options) -> Result<Matcher, regex::Error> {
+        // A pattern with newlines in it is one pattern per line
+        let pattern = options
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.split('\n'))
+            .map(|pattern| {
+                if options.fixed {
+                    format!("(?:{})", regex::escape(pattern))
+                } else {
+                    format!("(?:{})", pattern)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        let pattern = if options.whole_line {
+            format!("^(?:{})$", pattern)
+        } else {
+            pattern
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(options.ignore_case)
+            .build()?;
+        Ok(Matcher {
+            regex,
+            word: options.word,
+        })
+    }
+
+    /// Where the patterns match in `line`. With `-w` only matches with no
+    /// word character on either side count.
+    pub fn find(&self, line: &str) -> Vec<(usize, usize)> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        self.regex
+            .find_iter(line)
+            .filter(|found| {
+                !self.word
+                    || (!line[..found.start()]
+                        .chars()
+                        .next_back()
+                        .is_some_and(is_word)
+                        && !line[found.end()..].chars().next().is_some_and(is_word))
+            })
+            .map(|found| (found.start(), found.end()))
+            .collect()
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        if self.word {
+            !self.find(line).is_empty()
+        } else {
+            self.regex.is_match(line)
+        }
+    }
+}
+
+/// The escape sequences that colour matches, names, line numbers and
+/// separators, from `GREP_COLORS` in GNU grep's format.
+#[derive(Debug, Clone, Default)]
+struct Colors {
+    matched: String,
+    file: String,
+    line: String,
+    separator: String,
+}
+
+impl Colors {
+    fn from_env() -> Colors {
+        let mut colors = Colors {
+            matched: "01;31".to_string(),
+            file: "35".to_string(),
+            line: "32".to_string(),
+            separator: "36".to_string(),
+        };
+        let spec = std::env::var("GREP_COLORS").unwrap_or_default();
+        for (key, code) in spec.split(':').filter_map(|entry| entry.split_once('=')) {
+            let code = code.to_string();
+            match key {
+                "mt" | "ms" => colors.matched = code,
+                "fn" => colors.file = code,
+                "ln" => colors.line = code,
+                "se" => colors.separator = code,
+                _ => {}
+            }
+        }
+        colors
+    }
+}
+
+/// `text` in the colour `code` stands for, or as it is if there is none.
+fn paint(code: &str, text: &str) -> String {
+    if code.is_empty() {
+        text.to_string()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// Searches inputs one after the other with the same options.
+pub struct Searcher {
+    options: Options,
+    matcher: Matcher,
+    colors: Colors,
+    /// Whether lines have been printed, so the next group of context needs
+    /// a `--` before it
+    printed: bool,
+}
+
+impl Searcher {
+    pub fn new(mut options: Options) -> Result<Searcher, regex::Error> {
+        let matcher = Matcher::new(&options)?;
+        let colors = if options.color {
+            Colors::from_env()
+        } else {
+            Colors::default()
+        };
+        // -o prints only matches, so there are no lines around them to show
+        if options.only_matching {
+            options.before = 0;
+            options.after = 0;
+        }
+        Ok(Searcher {
+            options,
+            matcher,
+            colors,
+            printed: false,
+        })
+    }
+
+    /// Searches the lines of one input, which `label` names in the output.
+    /// Returns whether a line was selected, or with `-L` whether the input
+    /// was listed.
+    pub fn search(
+        &mut self,
+        label: &str,
+        reader: &mut dyn BufRead,
+        out: &mut dyn Write,
+    ) -> io::Result<bool> {
+        let binary = is_binary(reader.fill_buf()?);
+        let mut search = Search::new(self, label, binary);
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            if !search.line(&buffer, out)? {
+                break;
+            }
+        }
+        search.finish(out)
+    }
+}
+
+/// Whether an input is binary from the start of it: a NUL there makes it
+/// so, as GNU grep decides.
+fn is_binary(start: &[u8]) -> bool {
+    start.contains(&0)
+}
+
+/// The state of searching one input, with the [`Searcher`] either borrowed
+/// or, for `grep_async`, owned from one file to the next.
+struct Search<S> {
+    searcher: S,
+    label: String,
+    binary: bool,
+    line_number: usize,
+    /// How many lines have been selected
+    selected: usize,
+    /// The lines kept to show before the next selected line
+    before: VecDeque<(usize, String)>,
+    /// How many more lines to show after the last selected line
+    after: usize,
+    last_printed: Option<usize>,
+}
+
+impl<S: BorrowMut<Searcher>> Search<S> {
+    fn new(searcher: S, label: &str, binary: bool) -> Self {
+        Search {
+            searcher,
+            label: label.to_string(),
+            binary,
+            line_number: 0,
+            selected: 0,
+            before: VecDeque::new(),
+            after: 0,
+            last_printed: None,
+        }
+    }
+
+    /// Handles the next line, with or without its line ending. Returns
+    /// whether the rest of the input is still needed.
+    fn line(&mut self, mut line: &[u8], out: &mut dyn Write) -> io::Result<bool> {
+        while let [rest @ .., b'\n' | b'\r'] = line {
+            line = rest;
+        }
+        let text = &*String::from_utf8_lossy(line);
+        self.line_number += 1;
+        let options = &self.searcher.borrow().options;
+
+        // Past -m NUM selected lines, only the context after them is left
+        if options.max_count.is_some_and(|max| self.selected >= max) {
+            if self.after == 0 {
+                return Ok(false);
+            }
+            self.after -= 1;
+            self.print(self.line_number, text, false, out)?;
+            return Ok(self.after > 0);
+        }
+
+        let selected = self.searcher.borrow().matcher.is_match(text) != options.invert;
+        if !selected {
+            if self.after > 0 {
+                self.after -= 1;
+                self.print(self.line_number, text, false, out)?;
+            } else if options.before > 0 {
+                if self.before.len() == options.before {
+                    self.before.pop_front();
+                }
+                self.before.push_back((self.line_number, text.to_string()));
+            }
+            return Ok(true);
+        }
+
+        self.selected += 1;
+        if options.quiet || options.files_with_matches || options.files_without_match {
+            return Ok(false);
+        }
+        if options.count {
+            return Ok(true);
+        }
+        if self.binary {
+            writeln!(out, "Binary file {} matches", self.label)?;
+            return Ok(false);
+        }
+        self.after = options.after;
+        while let Some((number, line)) = self.before.pop_front() {
+            self.print(number, &line, false, out)?;
+        }
+        self.print(self.line_number, text, true, out)?;
+        Ok(true)
+    }
+
+    /// The name and line number a printed line starts with, followed by `:`
+    /// for selected lines and `-` for context.
+    fn prefix(&self, number: usize, selected: bool) -> String {
+        let Searcher {
+            options, colors, ..
+        } = self.searcher.borrow();
+        let separator = paint(&colors.separator, if selected { ":" } else { "-" });
+        let mut prefix = String::new();
+        if options.with_filename == Some(true) {
+            prefix.push_str(&paint(&colors.file, &self.label));
+            prefix.push_str(&separator);
+        }
+        if options.line_numbers {
+            prefix.push_str(&paint(&colors.line, &number.to_string()));
+            prefix.push_str(&separator);
+        }
+        prefix
+    }
+
+    fn print(
+        &mut self,
+        number: usize,
+        text: &str,
+        selected: bool,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let Searcher {
+            options,
+            matcher,
+            colors,
+            printed,
+        } = self.searcher.borrow();
+        if options.before > 0 || options.after > 0 {
+            let apart = match self.last_printed {
+                Some(last) => number > last + 1,
+                None => *printed,
+            };
+            if apart {
+                writeln!(out, "{}", paint(&colors.separator, "--"))?;
+            }
+        }
+        let prefix = self.prefix(number, selected);
+        let matches = matcher.find(text);
+        if options.only_matching {
+            for &(start, end) in matches.iter().filter(|(start, end)| start < end) {
+                writeln!(
+                    out,
+                    "{}{}",
+                    prefix,
+                    paint(&colors.matched, &text[start..end])
+                )?;
+            }
+        } else {
+            let mut line = prefix;
+            let mut shown = 0;
+            for (start, end) in matches {
+                line.push_str(&text[shown..start]);
+                line.push_str(&paint(&colors.matched, &text[start..end]));
+                shown = end;
+            }
+            line.push_str(&text[shown..]);
+            writeln!(out, "{}", line)?;
+        }
+        self.last_printed = Some(number);
+        self.searcher.borrow_mut().printed = true;
+        Ok(())
+    }
+
+    /// Prints what is only known at the end of the input: its count, or its
+    /// name for `-l` and `-L`.
+    fn finish(&self, out: &mut dyn Write) -> io::Result<bool> {
+        let Searcher {
+            options, colors, ..
+        } = self.searcher.borrow();
+        let name = paint(&colors.file, &self.label);
+        if options.quiet {
+            return Ok(self.selected > 0);
+        }
+        if options.files_with_matches {
+            if self.selected > 0 {
+                writeln!(out, "{}", name)?;
+            }
+        } else if options.files_without_match {
+            if self.selected == 0 {
+                writeln!(out, "{}", name)?;
+            }
+            return Ok(self.selected == 0);
+        } else if options.count {
+            match options.with_filename {
+                Some(true) => {
+                    let separator = paint(&colors.separator, ":");
+                    writeln!(out, "{}{}{}", name, separator, self.selected)?
+                }
+                _ => writeln!(out, "{}", self.selected)?,
+            }
+        }
+        Ok(self.selected > 0)
+    }
+}
+
+/// A rule from a `.gitignore`, such as `target/` or `!keep.log`.
+struct Rule {
+    pattern: Vec<(char, bool)>,
+    negated: bool,
+    /// Whether it only matches directories, from a trailing `/`
+    dir_only: bool,
+    /// Whether it matches the path from the `.gitignore`'s directory rather
+    /// than just the name, because it has a `/` in it
+    anchored: bool,
+}
+
+/// The rules of one `.gitignore`, which apply below its directory.
+struct Ignore {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    fn read(dir: &Path) -> Option<Ignore> {
+        let text = fs::read_to_string(dir.join(".gitignore")).ok()?;
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let line = line.strip_prefix("**/").unwrap_or(line);
+                let anchored = line.contains('/');
+                let line = line.strip_prefix('/').unwrap_or(line);
+                Rule {
+                    pattern: line.chars().map(|c| (c, false)).collect(),
+                    negated,
+                    dir_only,
+                    anchored,
+                }
+            })
+            .collect();
+        Some(Ignore {
+            dir: dir.to_path_buf(),
+            rules,
+        })
+    }
+
+    /// Whether the last rule matching `path` ignores it, or `None` if none
+    /// matches.
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let name = relative.rsplit('/').next().unwrap_or_default();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.dir_only)
+                    && glob::matches(
+                        &rule.pattern,
+                        if rule.anchored { &relative } else { name },
+                        false,
+                    )
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Whether the `.gitignore` files in `ignores`, outermost first, ignore `path`.
+fn is_ignored(ignores: &[Ignore], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.decide(path, is_dir))
+        .unwrap_or(false)
+}
+
+/// The `.gitignore` files that apply to `dir` from the directories above it,
+/// up to the top of its git repository. Outside a repository there are none.
+fn inherited_ignores(dir: &Path) -> Vec<Ignore> {
+    let mut ignores = Vec::new();
+    for ancestor in dir.ancestors().skip(1) {
+        ignores.extend(Ignore::read(ancestor));
+        if ancestor.join(".git").exists() {
+            ignores.reverse();
+            return ignores;
+        }
+    }
+    Vec::new()
+}
+
+/// Whether any of `globs` matches the name `path` ends in.
+fn any_matches(globs: &[String], path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    globs.iter().any(|pattern| {
+        let pattern: Vec<(char, bool)> = pattern.chars().map(|c| (c, false)).collect();
+        glob::matches(&pattern, &name, false)
+    })
+}
+
+/// Searches the inputs of one `grep`, remembering whether anything was
+/// selected and whether anything failed.
+struct Run<'a> {
+    searcher: Searcher,
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+    matched: bool,
+    failed: bool,
+}
+
+impl Run<'_> {
+    fn error(&mut self, shown: &str, e: &io::Error) {
+        let message = format!("{}: {}", shown, commands::io_message(e));
+        commands::report_error(self.stderr, "grep", message);
+        self.failed = true;
+    }
+
+    /// Whether a match in one input is all that was needed.
+    fn done(&self) -> bool {
+        self.matched && self.searcher.options.quiet
+    }
+
+    fn stdin(&mut self, stdin: &mut dyn Read) -> io::Result<()> {
+        let mut reader = BufReader::new(stdin);
+        self.matched |= self
+            .searcher
+            .search("(standard input)", &mut reader, self.stdout)?;
+        Ok(())
+    }
+
+    fn file(&mut self, path: &Path, shown: &str) -> io::Result<()> {
+        let options = &self.searcher.options;
+        if (!options.include.is_empty() && !any_matches(&options.include, path))
+            || any_matches(&options.exclude, path)
+        {
+            return Ok(());
+        }
+        let mut reader = match File::open(path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => {
+                self.error(shown, &e);
+                return Ok(());
+            }
+        };
+        match self.searcher.search(shown, &mut reader, self.stdout) {
+            Ok(matched) => self.matched |= matched,
+            // Failing to write is the end, but failing to read is this file's problem
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Err(e),
+            Err(e) => self.error(shown, &e),
+        }
+        Ok(())
+    }
+
+    /// Searches an operand, which for `-r` may be a directory.
+    fn operand(&mut self, path: &Path, shown: &str) -> io::Result<()> {
+        if !path.is_dir() {
+            return self.file(path, shown);
+        }
+        if !self.searcher.options.recursive {
+            let message = format!("{}: Is a directory", shown);
+            commands::report_error(self.stderr, "grep", message);
+            self.failed = true;
+            return Ok(());
+        }
+        let full = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut ignores = if self.searcher.options.no_ignore {
+            Vec::new()
+        } else {
+            inherited_ignores(&full)
+        };
+        self.dir(path, shown, &full, &mut ignores, &mut Vec::new())
+    }
+
+    /// Searches the files below a directory, in order of their names.
+    /// `full` is its canonical path, which `.gitignore` rules and `-R`'s
+    /// loop check go by.
+    fn dir(
+        &mut self,
+        path: &Path,
+        shown: &str,
+        full: &Path,
+        ignores: &mut Vec<Ignore>,
+        visited: &mut Vec<PathBuf>,
+    ) -> io::Result<()> {
+        let options = &self.searcher.options;
+        let (follow, no_ignore) = (options.follow_links, options.no_ignore);
+        if visited.iter().any(|dir| dir == full) {
+            let message = format!("{}: warning: recursive directory loop", shown);
+            commands::report_error(self.stderr, "grep", message);
+            return Ok(());
+        }
+        let mut names: Vec<_> = match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.file_name())
+                .collect(),
+            Err(e) => {
+                self.error(if shown.is_empty() { "." } else { shown }, &e);
+                return Ok(());
+            }
+        };
+        names.sort();
+
+        let own_ignore = if no_ignore { None } else { Ignore::read(full) };
+        let pushed = own_ignore.is_some();
+        ignores.extend(own_ignore);
+        visited.push(full.to_path_buf());
+        for name in names {
+            if self.done() {
+                break;
+            }
+            let child = path.join(&name);
+            let child_full = full.join(&name);
+            let child_shown = match shown {
+                "" => name.to_string_lossy().into_owned(),
+                _ => format!("{}/{}", shown.trim_end_matches('/'), name.to_string_lossy()),
+            };
+            let metadata = if follow {
+                fs::metadata(&child)
+            } else {
+                fs::symlink_metadata(&child)
+            };
+            let Ok(metadata) = metadata else {
+                continue;
+            };
+            let is_dir = metadata.is_dir();
+            if !no_ignore && (name == ".git" || is_ignored(ignores, &child_full, is_dir)) {
+                continue;
+            }
+            if is_dir {
+                if any_matches(&self.searcher.options.exclude_dir, &child) {
+                    continue;
+                }
+                let child_full = if follow {
+                    fs::canonicalize(&child).unwrap_or(child_full)
+                } else {
+                    child_full
+                };
+                self.dir(&child, &child_shown, &child_full, ignores, visited)?;
+            } else if metadata.is_file() {
+                self.file(&child, &child_shown)?;
+            }
+        }
+        visited.pop();
+        if pushed {
+            ignores.pop();
+        }
+        Ok(())
+    }
+}
+
+// Sync version for benchmarking
+pub fn grep_sync<S: AsRef<Path>>(pattern: &str, files: Vec<S>) -> io::Result<String> {
+    let mut searcher = legacy_searcher(pattern)?;
+    let mut result = Vec::new();
+
+    for file_path in files {
+        let file = File::open(&file_path)?;
+        let mut reader = BufReader::new(file);
+        let label = file_path.as_ref().display().to_string();
+        searcher.search(&label, &mut reader, &mut result)?;
+    }
+
+    Ok(String::from_utf8_lossy(&result).into_owned())
 }
 
-// Async version that returns a Stream<Bytes>
+/// A searcher for `grep_sync` and `grep_async`, which show every line with
+/// its file name and number.
+fn legacy_searcher(pattern: &str) -> io::Result<Searcher> {
+    let options = Options {
+        line_numbers: true,
+        with_filename: Some(true),
+        ..Options::new(pattern)
+    };
+    Searcher::new(options).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+// Async version that returns a Stream<Bytes>, reading each file a line at a
+// time and producing a chunk for each line of output
 pub async fn grep_async<S: AsRef<Path> + Send + 'static>(
     pattern: &str,
     files: Vec<S>,
-) -> impl Stream<Item = io::Result<Bytes>> {
-    let regex = match Regex::new(pattern) {
-        Ok(re) => re,
-        Err(e) => {
-            return stream::once(async move { 
-                Err(io::Error::new(io::ErrorKind::InvalidInput, e)) 
-            }).boxed();
-        }
+) -> ByteStream {
+    let searcher = match legacy_searcher(pattern) {
+        Ok(searcher) => searcher,
+        Err(e) => return stream::once(async move { Err(e) }).boxed(),
     };
 
-    if files.is_empty() {
-        return stream::empty().boxed();
-    }
-
-    let path = files[0].as_ref().to_path_buf();
-    
-    async move {
-        match TokioFile::open(&path).await {
-            Ok(file) => {
-                let reader = TokioBufReader::new(file);
-                let lines = reader.lines();
-                
-                stream::unfold((lines, 0, path, regex), |(mut lines, line_num, path, regex)| async move {
-                    match lines.next_line().await {
-                        Ok(Some(line)) => {
-                            if regex.is_match(&line) {
-                                let output = format!("{}:{}: {}\n", path.display(), line_num + 1, line);
-                                Some((Ok(Bytes::from(output)), (lines, line_num + 1, path, regex)))
-                            } else {
-                                // Skip non-matching lines by returning empty bytes
-                                Some((Ok(Bytes::new()), (lines, line_num + 1, path, regex)))
+    stream::unfold(
+        (Reading::Between(searcher), files.into_iter()),
+        |(mut reading, mut files)| async move {
+            loop {
+                let (mut search, mut lines) = match reading {
+                    Reading::Between(searcher) => {
+                        let path = files.next()?;
+                        let label = path.as_ref().display().to_string();
+                        match open_lines(path.as_ref()).await {
+                            Ok((binary, lines)) => {
+                                (Search::new(searcher, &label, binary), Box::new(lines))
                             }
+                            Err(e) => return Some((Err(e), (Reading::Between(searcher), files))),
                         }
-                        Ok(None) => None,
-                        Err(e) => Some((Err(e), (lines, line_num, path, regex))),
                     }
-                }).filter_map(|result| async move {
-                    match result {
-                        Ok(bytes) => {
-                            if bytes.is_empty() {
-                                None
-                            } else {
-                                Some(Ok(bytes))
-                            }
-                        }
-                        Err(e) => Some(Err(e)),
+                    Reading::File(search, lines) => (search, lines),
+                };
+                let mut output = Vec::new();
+                let more = match lines.next_segment().await {
+                    Ok(Some(line)) => search.line(&line, &mut output),
+                    Ok(None) => Ok(false),
+                    Err(e) => Err(e),
+                };
+                match more {
+                    Ok(true) => reading = Reading::File(search, lines),
+                    Ok(false) => {
+                        // Writing to a Vec cannot fail
+                        let _ = search.finish(&mut output);
+                        reading = Reading::Between(search.searcher);
                     }
-                }).boxed()
+                    Err(e) => {
+                        return Some((Err(e), (Reading::Between(search.searcher), files)));
+                    }
+                }
+                // Lines that are not printed add nothing
+                if !output.is_empty() {
+                    return Some((Ok(Bytes::from(output)), (reading, files)));
+                }
             }
-            Err(e) => stream::once(async move { Err(e) }).boxed(),
-        }
-    }.await
+        },
+    )
+    .boxed()
+}
+
+/// Where `grep_async` is up to: between files, or partway through one.
+enum Reading {
+    Between(Searcher),
+    File(Search<Searcher>, Box<Split<TokioBufReader<TokioFile>>>),
+}
+
+/// Opens a file for `grep_async`, telling whether it is binary and
+/// splitting it into lines.
+async fn open_lines(path: &Path) -> io::Result<(bool, Split<TokioBufReader<TokioFile>>)> {
+    let mut reader = TokioBufReader::new(TokioFile::open(path).await?);
+    let binary = is_binary(reader.fill_buf().await?);
+    Ok((binary, reader.split(b'\n')))
 }
 
 // Convenience function that collects the stream into a String
@@ -94,7 +799,7 @@ pub async fn grep_async_to_string<S: AsRef<Path> + Send + 'static>(
 ) -> io::Result<String> {
     let mut result = String::new();
     let mut stream = grep_async(pattern, files).await;
-    
+
     while let Some(chunk_result) = stream.next().await {
         match chunk_result {
             Ok(bytes) => {
@@ -105,10 +810,237 @@ pub async fn grep_async_to_string<S: AsRef<Path> + Send + 'static>(
             Err(e) => return Err(e),
         }
     }
-    
+
     Ok(result)
 }
 
+/// `grep [OPTION]... PATTERN [FILE]...`
+pub struct Grep;
+
+impl Grep {
+    /// Parses grep's short and long flags. The first plain word is the
+    /// pattern unless `-e` gave one, and the words after it go to `files`.
+    /// `Err(None)` means there was no pattern at all, which calls for the
+    /// usage; `Err(Some(message))` is a bad flag or value.
+    fn parse_args(args: &[String], files: &mut Vec<String>) -> Result<Options, Option<String>> {
+        let mut options = Options {
+            color: colored::control::SHOULD_COLORIZE.should_colorize(),
+            ..Options::default()
+        };
+        let mut words = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    words.extend(args.by_ref().cloned());
+                    break;
+                }
+                long if long.starts_with("--") => {
+                    let (name, inline) = match long[2..].split_once('=') {
+                        Some((name, value)) => (name, Some(value.to_string())),
+                        None => (&long[2..], None),
+                    };
+                    let mut value = || {
+                        inline
+                            .clone()
+                            .or_else(|| args.next().cloned())
+                            .ok_or_else(|| format!("option '--{}' requires an argument", name))
+                    };
+                    match name {
+                        "ignore-case" => options.ignore_case = true,
+                        "invert-match" => options.invert = true,
+                        "count" => options.count = true,
+                        "files-with-matches" => options.files_with_matches = true,
+                        "files-without-match" => options.files_without_match = true,
+                        "line-number" => options.line_numbers = true,
+                        "word-regexp" => options.word = true,
+                        "line-regexp" => options.whole_line = true,
+                        "fixed-strings" => options.fixed = true,
+                        "extended-regexp" => {}
+                        "only-matching" => options.only_matching = true,
+                        "quiet" | "silent" => options.quiet = true,
+                        "recursive" => options.recursive = true,
+                        "dereference-recursive" => {
+                            options.recursive = true;
+                            options.follow_links = true;
+                        }
+                        "no-ignore" => options.no_ignore = true,
+                        "with-filename" => options.with_filename = Some(true),
+                        "no-filename" => options.with_filename = Some(false),
+                        "include" => options.include.push(value()?),
+                        "exclude" => options.exclude.push(value()?),
+                        "exclude-dir" => options.exclude_dir.push(value()?),
+                        "regexp" => options.set('e', &value()?)?,
+                        "max-count" => options.set('m', &value()?)?,
+                        "after-context" => options.set('A', &value()?)?,
+                        "before-context" => options.set('B', &value()?)?,
+                        "context" => options.set('C', &value()?)?,
+                        "color" | "colour" => {
+                            options.color = match inline.as_deref() {
+                                None | Some("always" | "yes" | "force") => true,
+                                Some("never" | "no" | "none") => false,
+                                Some("auto" | "tty" | "if-tty") => options.color,
+                                Some(other) => {
+                                    return Err(Some(format!(
+                                        "invalid argument '{}' for '--color'",
+                                        other
+                                    )));
+                                }
+                            }
+                        }
+                        _ => return Err(Some(format!("unrecognized option '{}'", long))),
+                    }
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 => {
+                    for (at, flag) in flags.char_indices().skip(1) {
+                        // The rest of the word, or the next one, is the value
+                        if matches!(flag, 'e' | 'm' | 'A' | 'B' | 'C') {
+                            let rest = &flags[at + 1..];
+                            let value = if rest.is_empty() {
+                                args.next().cloned().ok_or_else(|| {
+                                    format!("option requires an argument -- '{}'", flag)
+                                })?
+                            } else {
+                                rest.to_string()
+                            };
+                            options.set(flag, &value)?;
+                            break;
+                        }
+                        match flag {
+                            'i' => options.ignore_case = true,
+                            'v' => options.invert = true,
+                            'c' => options.count = true,
+                            'l' => options.files_with_matches = true,
+                            'L' => options.files_without_match = true,
+                            'n' => options.line_numbers = true,
+                            'w' => options.word = true,
+                            'x' => options.whole_line = true,
+                            'F' => options.fixed = true,
+                            'E' => {}
+                            'o' => options.only_matching = true,
+                            'q' => options.quiet = true,
+                            'r' => options.recursive = true,
+                            'R' => {
+                                options.recursive = true;
+                                options.follow_links = true;
+                            }
+                            'H' => options.with_filename = Some(true),
+                            'h' => options.with_filename = Some(false),
+                            _ => return Err(Some(format!("invalid option -- '{}'", flag))),
+                        }
+                    }
+                }
+                _ => words.push(arg.clone()),
+            }
+        }
+
+        if options.patterns.is_empty() {
+            if words.is_empty() {
+                return Err(None);
+            }
+            options.patterns.push(words.remove(0));
+        }
+        files.extend(words);
+        Ok(options)
+    }
+}
+
+impl Command for Grep {
+    fn name(&self) -> &'static str {
+        "grep"
+    }
+
+    fn usage(&self) -> &'static str {
+        "grep [OPTION]... PATTERN [FILE]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Print lines matching a regular expression, searching directories with -r"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let mut files = Vec::new();
+        let mut options = match Self::parse_args(args, &mut files) {
+            Ok(options) => options,
+            Err(message) => {
+                if let Some(message) = message {
+                    commands::report_error(stderr, self.name(), message);
+                }
+                return commands::usage_error(stderr, self.usage());
+            }
+        };
+        // With no files, -r searches the working directory and names files
+        // from there
+        let implicit_dir = files.is_empty() && options.recursive;
+        let with_filename = files.len() > 1 || options.recursive;
+        options.with_filename.get_or_insert(with_filename);
+        let searcher = match Searcher::new(options) {
+            Ok(searcher) => searcher,
+            Err(e) => {
+                commands::report_error(stderr, self.name(), e);
+                return 2;
+            }
+        };
+
+        let mut run = Run {
+            searcher,
+            stdout,
+            stderr,
+            matched: false,
+            failed: false,
+        };
+        let mut result = Ok(());
+        if implicit_dir {
+            result = run.operand(Path::new("."), "");
+        } else if files.is_empty() {
+            result = run.stdin(stdin);
+        }
+        for file in &files {
+            if result.is_err() || run.done() {
+                break;
+            }
+            result = match file.as_str() {
+                "-" => run.stdin(stdin),
+                _ => run.operand(Path::new(file), file),
+            };
+        }
+        if let Err(e) = result.and_then(|()| run.stdout.flush()) {
+            // The reader went away, as with `grep x file | head -1`
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                commands::report_error(run.stderr, self.name(), e);
+            }
+            return 2;
+        }
+
+        match (run.matched, run.failed) {
+            (true, _) if run.searcher.options.quiet => 0,
+            (_, true) => 2,
+            (true, false) => 0,
+            (false, false) => 1,
+        }
+    }
+
+    /// Runs on a blocking thread like other built-ins, so errors about each
+    /// file reach stderr as they happen. Bad arguments are caught here, when
+    /// the pipeline is built.
+    fn stage(&self, args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        let checked = match Self::parse_args(args, &mut Vec::new()) {
+            Ok(options) => Matcher::new(&options)
+                .map(|_| ())
+                .map_err(|e| format!("grep: {}", e)),
+            Err(Some(message)) => Err(format!("grep: {}", message)),
+            Err(None) => Err(format!("Usage: {}", self.usage())),
+        };
+        Some(checked.map(|()| Box::new(BuiltinStage::new(&Grep, args.to_vec())) as Box<dyn Stage>))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,10 +1066,185 @@ mod tests {
 
         tokio::fs::write(file_path, content).await.unwrap();
 
-        let result = grep_async_to_string("hello", vec![file_path]).await.unwrap();
+        let result = grep_async_to_string("hello", vec![file_path])
+            .await
+            .unwrap();
         assert!(result.contains("hello world"));
         assert!(result.contains("hello again"));
 
         tokio::fs::remove_file(file_path).await.unwrap();
     }
-} 
\ No newline at end of file
+
+    fn grep(args: &[&str], input: &str) -> (i32, String, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        let code = Grep.run(&args, &mut input.as_bytes(), &mut output, &mut errors);
+        (
+            code,
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_grep_async_reads_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (one, two) = (dir.path().join("one"), dir.path().join("two"));
+        std::fs::write(&one, "hello one\n").unwrap();
+        std::fs::write(&two, "skip\nhello two\n").unwrap();
+
+        let result = grep_async_to_string("hello", vec![one.clone(), two.clone()])
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            format!(
+                "{}:1:hello one\n{}:2:hello two\n",
+                one.display(),
+                two.display()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grep_async_streams_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let (missing, file) = (dir.path().join("missing"), dir.path().join("file"));
+        std::fs::write(&file, "hello one\nskip\r\nhello two\r\n").unwrap();
+
+        let chunks: Vec<io::Result<Bytes>> = grep_async("hello", vec![missing, file.clone()])
+            .await
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].is_err());
+        let lines: Vec<String> = chunks[1..]
+            .iter()
+            .map(|chunk| String::from_utf8_lossy(chunk.as_ref().unwrap()).into_owned())
+            .collect();
+        let file = file.display();
+        assert_eq!(
+            lines,
+            vec![
+                format!("{}:1:hello one\n", file),
+                format!("{}:3:hello two\n", file)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selection() {
+        let input = "Apple pie\napple\npineapple\nbanana\n";
+        assert_eq!(
+            grep(&["apple"], input),
+            (0, "apple\npineapple\n".into(), String::new())
+        );
+        assert_eq!(grep(&["-i", "-w", "apple"], input).1, "Apple pie\napple\n");
+        assert_eq!(grep(&["-x", "apple"], input).1, "apple\n");
+        assert_eq!(grep(&["-vn", "apple"], input).1, "1:Apple pie\n4:banana\n");
+        assert_eq!(grep(&["-c", "-iv", "APPLE"], input).1, "1\n");
+        assert_eq!(grep(&["-o", "-E", "an+"], input).1, "an\nan\n");
+        assert_eq!(
+            grep(&["-F", "-e", "a.", "-e", "z"], "a.b\naxb\n").1,
+            "a.b\n"
+        );
+        assert_eq!(grep(&["-m1", "apple"], input).1, "apple\n");
+        assert_eq!(
+            grep(&["-q", "pie"], input),
+            (0, String::new(), String::new())
+        );
+        assert_eq!(grep(&["cherry"], input).0, 1);
+        assert_eq!(
+            grep(&["-e", "pie", "-e", "nana"], input).1,
+            "Apple pie\nbanana\n"
+        );
+
+        assert_eq!(grep(&[], input).0, 2);
+        assert_eq!(grep(&["-z", "x"], input).0, 2);
+        assert_eq!(grep(&["("], input).0, 2);
+    }
+
+    #[test]
+    fn test_context() {
+        let input = "1\n2\nmatch a\n4\n5\n6\n7\nmatch b\n9\n";
+        assert_eq!(
+            grep(&["-n", "-C1", "match"], input).1,
+            "2-2\n3:match a\n4-4\n--\n7-7\n8:match b\n9-9\n"
+        );
+        assert_eq!(
+            grep(&["-A", "3", "match"], input).1,
+            "match a\n4\n5\n6\n--\nmatch b\n9\n"
+        );
+        assert_eq!(grep(&["-B2", "-m1", "match"], input).1, "1\n2\nmatch a\n");
+    }
+
+    #[test]
+    fn test_colors() {
+        let (_, output, _) = grep(&["--color=always", "-Hn", "b", "-"], "abc\n");
+        assert_eq!(
+            output,
+            "\x1b[35m(standard input)\x1b[0m\x1b[36m:\x1b[0m\x1b[32m1\x1b[0m\x1b[36m:\x1b[0m\
+             a\x1b[01;31mb\x1b[0mc\n"
+        );
+        assert_eq!(grep(&["--color=never", "b"], "abc\n").1, "abc\n");
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(path(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        std::fs::write(path("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(path("src/nested/lib.rs"), "fn lib() {}\n").unwrap();
+        std::fs::write(path("src/notes.txt"), "fn in notes\n").unwrap();
+        std::fs::write(path("target/out.rs"), "fn built() {}\n").unwrap();
+        std::fs::write(path("debug.log"), "fn logged\n").unwrap();
+        std::fs::write(path("keep.log"), "fn kept\n").unwrap();
+        std::fs::write(path("binary"), b"fn\0").unwrap();
+
+        let root = path("");
+        let root = root.trim_end_matches('/');
+        let (code, output, _) = grep(&["-rl", "fn", root], "");
+        assert_eq!(code, 0);
+        let listed: Vec<&str> = output.lines().map(|line| &line[root.len() + 1..]).collect();
+        assert_eq!(
+            listed,
+            vec![
+                "binary",
+                "keep.log",
+                "src/main.rs",
+                "src/nested/lib.rs",
+                "src/notes.txt"
+            ]
+        );
+
+        let (_, output, _) = grep(
+            &["-r", "--no-ignore", "-c", "--include=*.rs", "fn", root],
+            "",
+        );
+        assert_eq!(output.lines().count(), 3);
+        let (_, output, _) = grep(&["-r", "--exclude-dir=nested", "-L", "main", root], "");
+        assert!(!output.contains("lib.rs"));
+        assert!(output.contains("notes.txt"));
+
+        assert_eq!(
+            grep(&["fn", &path("binary")], "").1,
+            format!("Binary file {} matches\n", path("binary"))
+        );
+        assert_eq!(
+            grep(&["main", &path("src/main.rs"), &path("src/notes.txt")], "").1,
+            format!("{}:fn main() {{}}\n", path("src/main.rs"))
+        );
+
+        let (code, output, errors) = grep(&["fn", &path("missing"), &path("src/main.rs")], "");
+        assert_eq!(code, 2);
+        assert!(output.contains("fn main"));
+        assert!(errors.contains("missing: No such file or directory"));
+        let (code, _, errors) = grep(&["fn", &path("src")], "");
+        assert_eq!(code, 2);
+        assert!(errors.contains("Is a directory"));
+    }
+}
@@ -1,9 +1,11 @@
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Read, Write};
 use std::path::Path;
 use tokio::fs::File as TokioFile;
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use futures::stream::{self, Stream, StreamExt};
 use bytes::Bytes;
+use crate::commands::Command;
+use crate::pipeline::{self, ByteStream, HeadStage, Stage};
 
 // Sync version for benchmarking
 pub fn head_sync<S: AsRef<Path>>(files: Vec<S>, lines: usize) -> io::Result<String> {
@@ -78,6 +80,15 @@ pub async fn head_async<S: AsRef<Path> + Send + 'static>(
     }.await
 }
 
+// Takes the first lines of an incoming byte stream; once they have been
+// produced the input is dropped so upstream stages can stop early
+pub fn head_stream(input: ByteStream, lines: usize) -> ByteStream {
+    pipeline::lines(input)
+        .take(lines)
+        .map(|line| line.map(|line| Bytes::from(line + "\n")))
+        .boxed()
+}
+
 // Convenience function that collects the stream into a String
 pub async fn head_async_to_string<S: AsRef<Path> + Send + 'static>(
     files: Vec<S>,
@@ -100,6 +111,43 @@ pub async fn head_async_to_string<S: AsRef<Path> + Send + 'static>(
     Ok(result)
 }
 
+pub struct Head;
+
+impl Head {
+    fn build(args: &[String]) -> Result<Box<dyn Stage>, String> {
+        let (lines, files) = pipeline::parse_line_count("head", args)?;
+        Ok(Box::new(HeadStage::new(lines, files)))
+    }
+}
+
+impl Command for Head {
+    fn name(&self) -> &'static str {
+        "head"
+    }
+
+    fn usage(&self) -> &'static str {
+        "head [-n N] [FILE]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the first lines of the input"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        pipeline::run_stage(self.name(), Self::build(args), stdin, stdout, stderr)
+    }
+
+    fn stage(&self, args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        Some(Self::build(args))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
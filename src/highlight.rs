@@ -0,0 +1,320 @@
+//! Syntax highlighting for the command line being typed. The line is split
+//! by the parser's own tokenizer, so what is coloured as a string, a
+//! variable or an operator is exactly what the shell will run as one.
+
+use crate::alias;
+use crate::commands;
+use crate::external;
+use crate::parser::{self, ParseErrorKind, RegionKind, TokenKind, WordPart};
+use crate::shell;
+use crate::vars;
+use colored::{Color, Colorize};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Plain,
+    Command,
+    /// A command name that is not a built-in, alias, function or program
+    UnknownCommand,
+    Keyword,
+    Operator,
+    Redirect,
+    String,
+    Variable,
+    Comment,
+}
+
+impl Style {
+    fn color(self) -> Option<Color> {
+        match self {
+            Style::Plain => None,
+            Style::Command => Some(Color::Green),
+            Style::UnknownCommand => Some(Color::Red),
+            Style::Keyword => Some(Color::Magenta),
+            Style::Operator => Some(Color::Blue),
+            Style::Redirect => Some(Color::BrightBlue),
+            Style::String => Some(Color::Yellow),
+            Style::Variable => Some(Color::Cyan),
+            Style::Comment => Some(Color::BrightBlack),
+        }
+    }
+}
+
+/// Whether `name` can be run as a command without being a function: a
+/// built-in, an alias or a program.
+pub fn is_command(name: &str) -> bool {
+    commands::registry().get(name).is_some()
+        || shell::SPECIAL_BUILTINS.contains(&name)
+        || alias::get(name).is_some()
+        || is_program(name)
+}
+
+/// Whether a program by this name is on the PATH. Searching the PATH on
+/// every key press is slow, so the answers are kept until the PATH changes.
+/// A path such as `./build.sh` depends on the working directory instead,
+/// so it is looked up each time.
+fn is_program(name: &str) -> bool {
+    if name.contains(['/', '\\']) {
+        return external::find_program(name).is_some();
+    }
+    static CACHE: OnceLock<Mutex<ProgramCache>> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    cache.is_program(vars::get("PATH"), name, || {
+        external::find_program(name).is_some()
+    })
+}
+
+/// Which names were found on the PATH, for one value of the PATH.
+#[derive(Debug, Default)]
+struct ProgramCache {
+    path: Option<String>,
+    found: HashMap<String, bool>,
+}
+
+impl ProgramCache {
+    fn is_program(
+        &mut self,
+        path: Option<String>,
+        name: &str,
+        find: impl FnOnce() -> bool,
+    ) -> bool {
+        if path != self.path {
+            self.path = path;
+            self.found.clear();
+        }
+        *self.found.entry(name.to_string()).or_insert_with(find)
+    }
+}
+
+/// The line with ANSI colours added. `is_command` decides whether a command
+/// name is coloured as known or unknown.
+pub fn highlight(line: &str, is_command: &dyn Fn(&str) -> bool) -> String {
+    let mut highlighted = String::new();
+    for (span, style) in styles(line, is_command) {
+        match style.color() {
+            Some(color) => highlighted.push_str(&line[span].color(color).to_string()),
+            None => highlighted.push_str(&line[span]),
+        }
+    }
+    highlighted
+}
+
+/// The line split into stretches of the same style, in order.
+pub fn styles(line: &str, is_command: &dyn Fn(&str) -> bool) -> Vec<(Range<usize>, Style)> {
+    let mut styles = vec![Style::Plain; line.len()];
+    paint(line, 0, is_command, &mut styles);
+
+    let mut spans: Vec<(Range<usize>, Style)> = Vec::new();
+    for (i, style) in styles.into_iter().enumerate() {
+        match spans.last_mut() {
+            Some((span, last)) if *last == style => span.end = i + 1,
+            _ => spans.push((i..i + 1, style)),
+        }
+    }
+    spans
+}
+
+/// Styles the bytes of `line`, which starts `offset` bytes into the whole
+/// line when it is the inside of a substitution.
+fn paint(line: &str, offset: usize, is_command: &dyn Fn(&str) -> bool, styles: &mut [Style]) {
+    let set = |styles: &mut [Style], span: Range<usize>, style: Style| {
+        styles[offset + span.start..offset + span.end].fill(style);
+    };
+    let (tokens, regions, error) = parser::scan(line);
+
+    let mut command_position = true;
+    let mut redirect_target = false;
+    for (index, token) in tokens.iter().enumerate() {
+        let style = match &token.kind {
+            // The file a redirection names is not the command
+            TokenKind::Word(_) if redirect_target => {
+                redirect_target = false;
+                Style::Plain
+            }
+            TokenKind::Word(_) if !command_position => Style::Plain,
+            TokenKind::Word(word) => {
+                let text = word.text();
+                let plain = word.parts.iter().all(|part| {
+                    matches!(
+                        part,
+                        WordPart::Literal(_) | WordPart::SingleQuoted(_) | WordPart::Escaped(_)
+                    )
+                });
+                match word.reserved_word() {
+                    Some(keyword) => {
+                        // The words after these are names and patterns
+                        command_position = !matches!(
+                            keyword,
+                            "for" | "case" | "function" | "fi" | "done" | "esac" | "}"
+                        );
+                        Style::Keyword
+                    }
+                    None if vars::parse_assignment(&text).is_some() => Style::Plain,
+                    None => {
+                        command_position = false;
+                        let defines_function = matches!(
+                            tokens.get(index + 1).map(|token| &token.kind),
+                            Some(TokenKind::LParen)
+                        );
+                        if !plain {
+                            // What runs is not known until the word is expanded
+                            Style::Plain
+                        } else if defines_function || is_command(&text) {
+                            Style::Command
+                        } else {
+                            Style::UnknownCommand
+                        }
+                    }
+                }
+            }
+            TokenKind::Redirect(..) => {
+                redirect_target = true;
+                Style::Redirect
+            }
+            TokenKind::Newline => {
+                command_position = true;
+                Style::Plain
+            }
+            _ => {
+                command_position = true;
+                Style::Operator
+            }
+        };
+        set(styles, token.span.clone(), style);
+    }
+
+    // Strings first, so the parameters inside them show over them
+    let (strings, others): (Vec<_>, Vec<_>) = regions
+        .into_iter()
+        .partition(|region| region.kind == RegionKind::Quoted);
+    for region in strings.into_iter().chain(others) {
+        let span = region.span;
+        match region.kind {
+            RegionKind::Comment => set(styles, span, Style::Comment),
+            RegionKind::Quoted => set(styles, span, Style::String),
+            RegionKind::Parameter => set(styles, span, Style::Variable),
+            RegionKind::Substitution => {
+                set(styles, span.start..span.start + 2, Style::Variable);
+                set(styles, span.end - 1..span.end, Style::Variable);
+                let inner = span.start + 2..span.end - 1;
+                paint(
+                    &line[inner.clone()],
+                    offset + inner.start,
+                    is_command,
+                    styles,
+                );
+            }
+        }
+    }
+
+    // The rest of a line that is still being typed
+    match error.map(|error| (error.kind, error.pos)) {
+        Some((ParseErrorKind::UnterminatedQuote(_), pos)) => {
+            set(styles, pos..line.len(), Style::String);
+        }
+        Some((ParseErrorKind::UnterminatedSubstitution, pos)) => {
+            set(styles, pos..pos + 2, Style::Variable);
+            paint(&line[pos + 2..], offset + pos + 2, is_command, styles);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(line: &str) -> Vec<(&str, Style)> {
+        let known = |name: &str| ["echo", "grep"].contains(&name);
+        styles(line, &known)
+            .into_iter()
+            .filter(|(_, style)| *style != Style::Plain)
+            .map(|(span, style)| (&line[span], style))
+            .collect()
+    }
+
+    #[test]
+    fn test_commands_and_operators() {
+        assert_eq!(
+            styled("echo hi | nope 2>err.log && X=1 grep x # done"),
+            vec![
+                ("echo", Style::Command),
+                ("|", Style::Operator),
+                ("nope", Style::UnknownCommand),
+                ("2>", Style::Redirect),
+                ("&&", Style::Operator),
+                ("grep", Style::Command),
+                ("# done", Style::Comment),
+            ]
+        );
+        assert_eq!(
+            styled("if echo; then nope; fi"),
+            vec![
+                ("if", Style::Keyword),
+                ("echo", Style::Command),
+                (";", Style::Operator),
+                ("then", Style::Keyword),
+                ("nope", Style::UnknownCommand),
+                (";", Style::Operator),
+                ("fi", Style::Keyword),
+            ]
+        );
+        assert_eq!(styled("greet() { echo; }")[0], ("greet", Style::Command));
+    }
+
+    #[test]
+    fn test_strings_and_variables() {
+        assert_eq!(
+            styled("echo 'a b' \"x $HOME y\" $(nope)"),
+            vec![
+                ("echo", Style::Command),
+                ("'a b'", Style::String),
+                ("\"x ", Style::String),
+                ("$HOME", Style::Variable),
+                (" y\"", Style::String),
+                ("$(", Style::Variable),
+                ("nope", Style::UnknownCommand),
+                (")", Style::Variable),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_program_cache() {
+        let mut cache = ProgramCache::default();
+        let lookups = std::cell::Cell::new(0);
+        let find = || {
+            lookups.set(lookups.get() + 1);
+            true
+        };
+        let path = || Some("/bin".to_string());
+        assert!(cache.is_program(path(), "ls", find));
+        assert!(cache.is_program(path(), "ls", find));
+        assert_eq!(lookups.get(), 1);
+        // A new PATH may hold different programs
+        assert!(cache.is_program(Some("/usr/bin".to_string()), "ls", find));
+        assert_eq!(lookups.get(), 2);
+    }
+
+    #[test]
+    fn test_incomplete_lines() {
+        assert_eq!(
+            styled("echo \"unfinished $x"),
+            vec![("echo", Style::Command), ("\"unfinished $x", Style::String)]
+        );
+        assert_eq!(
+            styled("echo $(grep"),
+            vec![
+                ("echo", Style::Command),
+                ("$(", Style::Variable),
+                ("grep", Style::Command),
+            ]
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! The command history shared by every session of a user, with the
+//! `history` built-in and `!` expansion.
+//!
+//! Each command is appended to one file in the user's data directory once
+//! it has run, together with when and where it ran and its exit status.
+//! Sessions only ever append whole lines to the file while holding a lock
+//! on it, so several shells can run at once without losing entries.
+
+use crate::commands::{self, Command};
+use crate::localtime;
+use crate::vars;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
+
+/// How many of the most recent entries are loaded at startup.
+pub const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// When the command ran, in seconds since the Unix epoch
+    pub time: i64,
+    /// The directory it ran in
+    pub cwd: String,
+    pub status: i32,
+    pub command: String,
+}
+
+impl Entry {
+    /// The entry as a line of the history file: its fields separated by
+    /// tabs, with tabs, newlines and backslashes escaped.
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.time,
+            self.status,
+            escape(&self.cwd),
+            escape(&self.command)
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '\t');
+        Some(Self {
+            time: fields.next()?.parse().ok()?,
+            status: fields.next()?.parse().ok()?,
+            cwd: unescape(fields.next()?),
+            command: unescape(fields.next()?),
+        })
+    }
+
+    /// When the command ran, like `2024-05-01 14:03:59`, in local time.
+    fn format_time(&self) -> String {
+        let time = localtime::to_local(
+            OffsetDateTime::from_unix_timestamp(self.time).unwrap_or(OffsetDateTime::UNIX_EPOCH),
+        );
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+            time.year(),
+            time.month() as u8,
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', r"\\")
+        .replace('\t', r"\t")
+        .replace('\n', r"\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The history file: `$HISTFILE` if it is set, and otherwise `history` in
+/// winix's folder of the user's data directory.
+pub fn path() -> Option<PathBuf> {
+    match vars::get("HISTFILE") {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => dirs::data_dir().map(|dir| dir.join("winix").join("history")),
+    }
+}
+
+fn store() -> &'static Mutex<Vec<Entry>> {
+    static STORE: OnceLock<Mutex<Vec<Entry>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn lock() -> MutexGuard<'static, Vec<Entry>> {
+    store().lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Reads the entries of a history file, skipping any lines that are not
+/// entries.
+fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let text = fs::read_to_string(path)?;
+    Ok(text.lines().filter_map(Entry::from_line).collect())
+}
+
+/// Appends an entry to a history file, creating it if need be. The line is
+/// written in one go under an exclusive lock, so that it cannot end up
+/// mixed with one from another session.
+fn append(path: &Path, entry: &Entry) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.lock()?;
+    file.write_all(entry.to_line().as_bytes())
+}
+
+/// Loads the most recent entries of the history file, as the CLI and the
+/// TUI do when they start. A missing file is an empty history.
+pub fn load() -> io::Result<()> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    let mut entries = match read(&path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    entries.drain(..entries.len().saturating_sub(MAX_ENTRIES));
+    *lock() = entries;
+    Ok(())
+}
+
+/// The commands in the history, oldest first.
+pub fn commands() -> Vec<String> {
+    lock().iter().map(|entry| entry.command.clone()).collect()
+}
+
+/// Adds a command that has just run to the history and to the history file.
+/// Blank lines and repeats of the command before are left out.
+pub fn record(command: &str, status: i32) -> io::Result<()> {
+    if command.trim().is_empty() || lock().last().is_some_and(|last| last.command == command) {
+        return Ok(());
+    }
+    let entry = Entry {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64),
+        cwd: std::env::current_dir()
+            .map(|cwd| cwd.display().to_string())
+            .unwrap_or_default(),
+        status,
+        command: command.to_string(),
+    };
+    lock().push(entry.clone());
+    match path() {
+        Some(path) => append(&path, &entry),
+        None => Ok(()),
+    }
+}
+
+/// Replaces the history events in a line with the commands they refer to:
+/// `!!` for the last command, `!N` for command N, `!-N` for the Nth most
+/// recent and `!PREFIX` for the last one starting with PREFIX. Returns the
+/// new line, or `None` if there was nothing to expand.
+pub fn expand(line: &str) -> Result<Option<String>, String> {
+    expand_with(line, &commands())
+}
+
+fn expand_with(line: &str, commands: &[String]) -> Result<Option<String>, String> {
+    let mut expanded = String::new();
+    let mut changed = false;
+    let mut single_quoted = false;
+    let mut double_quoted = false;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' if !double_quoted => single_quoted = !single_quoted,
+            '"' if !single_quoted => double_quoted = !double_quoted,
+            '\\' if !single_quoted => {
+                expanded.push(c);
+                if let Some((_, next)) = chars.next() {
+                    expanded.push(next);
+                }
+                continue;
+            }
+            '!' if !single_quoted => {
+                let rest = &line[i + 1..];
+                // `!` before a space, `=`, `(` or an operator is just an exclamation mark
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || "=();|&<>\"'".contains(c))
+                    .unwrap_or(rest.len());
+                let event = if rest.starts_with('!') {
+                    "!"
+                } else {
+                    &rest[..end]
+                };
+                if event.is_empty() {
+                    expanded.push(c);
+                    continue;
+                }
+                let command = find_event(event, commands)
+                    .ok_or_else(|| format!("!{}: event not found", event))?;
+                expanded.push_str(command);
+                changed = true;
+                while chars.next_if(|&(j, _)| j <= i + event.len()).is_some() {}
+                continue;
+            }
+            _ => {}
+        }
+        expanded.push(c);
+    }
+    Ok(changed.then_some(expanded))
+}
+
+/// The command an event such as `!`, `3`, `-2` or `git` refers to.
+fn find_event<'a>(event: &str, commands: &'a [String]) -> Option<&'a str> {
+    let command = if event == "!" {
+        commands.last()
+    } else if let Some(back) = event.strip_prefix('-')
+        && let Ok(back) = back.parse::<usize>()
+    {
+        commands
+            .len()
+            .checked_sub(back)
+            .and_then(|i| commands.get(i))
+    } else if let Ok(number) = event.parse::<usize>() {
+        number.checked_sub(1).and_then(|i| commands.get(i))
+    } else {
+        commands
+            .iter()
+            .rev()
+            .find(|command| command.starts_with(event))
+    };
+    command.map(String::as_str)
+}
+
+/// `history [-c] [-v] [N]`
+pub struct History;
+
+impl Command for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn usage(&self) -> &'static str {
+        "history [-c] [-v] [N]"
+    }
+
+    fn description(&self) -> &'static str {
+        "List past commands with when they ran (-v adds status and directory), or clear them"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let mut verbose = false;
+        let mut count = None;
+        for arg in args {
+            match arg.as_str() {
+                // Like bash, this forgets this session's history but leaves
+                // the file alone
+                "-c" => {
+                    lock().clear();
+                    return 0;
+                }
+                "-v" => verbose = true,
+                arg => match arg.parse::<usize>() {
+                    Ok(n) if count.is_none() => count = Some(n),
+                    _ => return commands::usage_error(stderr, self.usage()),
+                },
+            }
+        }
+
+        let entries = lock().clone();
+        let skip = count.map_or(0, |count| entries.len().saturating_sub(count));
+        let listed: io::Result<()> =
+            entries
+                .iter()
+                .enumerate()
+                .skip(skip)
+                .try_for_each(|(index, entry)| {
+                    let command = entry.command.replace('\n', "\n       ");
+                    if verbose {
+                        writeln!(
+                            stdout,
+                            "{:>5}  {}  {:>3}  {}  {}",
+                            index + 1,
+                            entry.format_time(),
+                            entry.status,
+                            entry.cwd,
+                            command
+                        )
+                    } else {
+                        writeln!(
+                            stdout,
+                            "{:>5}  {}  {}",
+                            index + 1,
+                            entry.format_time(),
+                            command
+                        )
+                    }
+                });
+        commands::exit_code(stderr, self.name(), listed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(list: &[&str]) -> Vec<String> {
+        list.iter().map(|command| command.to_string()).collect()
+    }
+
+    #[test]
+    fn test_entry_lines() {
+        let entry = Entry {
+            time: 1_700_000_000,
+            cwd: r"C:\Users\me".to_string(),
+            status: 1,
+            command: "for x in a\tb\ndo echo \\n; done".to_string(),
+        };
+        let line = entry.to_line();
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(Entry::from_line(line.trim_end_matches('\n')), Some(entry));
+        assert_eq!(Entry::from_line("not an entry"), None);
+    }
+
+    #[test]
+    fn test_concurrent_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("winix").join("history");
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for n in 0..50 {
+                        let entry = Entry {
+                            time: n,
+                            cwd: "/tmp".to_string(),
+                            status: 0,
+                            command: format!("echo {} {}", writer, "x".repeat(500)),
+                        };
+                        append(&path, &entry).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 400);
+        assert_eq!(read(&path).unwrap().len(), 400);
+    }
+
+    #[test]
+    fn test_expand() {
+        let history = commands(&["ls -l", "git status", "echo hi"]);
+        let expand = |line| expand_with(line, &history);
+        assert_eq!(expand("!! | wc"), Ok(Some("echo hi | wc".to_string())));
+        assert_eq!(expand("!1 /tmp"), Ok(Some("ls -l /tmp".to_string())));
+        assert_eq!(expand("!-2"), Ok(Some("git status".to_string())));
+        assert_eq!(
+            expand("sudo !git;"),
+            Ok(Some("sudo git status;".to_string()))
+        );
+        assert_eq!(expand("echo 'hi!!' wow! \\!!"), Ok(None));
+        assert_eq!(
+            expand("echo \"it's !!\""),
+            Ok(Some("echo \"it's echo hi\"".to_string()))
+        );
+        assert_eq!(expand("!x"), Err("!x: event not found".to_string()));
+        assert_eq!(expand("!9"), Err("!9: event not found".to_string()));
+    }
+}
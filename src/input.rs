@@ -1,35 +1,115 @@
-use rustyline::completion::{Completer,Pair};
+use crate::bind;
+use crate::completion;
+use crate::highlight;
+use crate::history;
+use crate::options::{self, ShellOption};
+use crate::prompt;
+use crate::shell;
+use colored::Colorize;
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::Configurer;
 use rustyline::highlight::Highlighter;
-use rustyline::hint::Hinter;
-use rustyline::validate::Validator;
-use rustyline::{Editor, Config, Helper, Context, error::ReadlineError};
+use rustyline::hint::{Hinter, HistoryHinter};
 use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{
+    Cmd, ConditionalEventHandler, EditMode, Event, EventContext, EventHandler, RepeatCount,
+};
+use rustyline::{Config, Context, Editor, Helper, error::ReadlineError};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 
-#[derive(Clone)]
-pub struct MyHelper;
+#[derive(Default)]
+pub struct MyHelper {
+    hinter: HistoryHinter,
+    /// The shell's functions, which count as known commands when highlighting
+    functions: HashSet<String>,
+}
 
 impl Completer for MyHelper {
     type Candidate = Pair;
 
     fn complete(
         &self,
-        _line: &str,
-        _pos: usize,
+        line: &str,
+        pos: usize,
         _ctx: &Context<'_>,
     ) -> Result<(usize, Vec<Pair>), ReadlineError> {
-        Ok((0, vec![])) // Stub for tab-completion
+        let (start, candidates) = completion::complete(line, pos);
+        let candidates = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.display,
+                replacement: candidate.replacement,
+            })
+            .collect();
+        Ok((start, candidates))
     }
 }
 
+// Suggests the rest of the last history entry starting with the line, as
+// fish does; Right-arrow at the end of the line accepts it
 impl Hinter for MyHelper {
     type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        self.hinter.hint(line, pos, ctx)
+    }
+}
+
+impl Highlighter for MyHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let is_command = |name: &str| self.functions.contains(name) || highlight::is_command(name);
+        Cow::Owned(highlight::highlight(line, &is_command))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        // Any edit can change how the rest of the line is coloured
+        true
+    }
 }
-impl Highlighter for MyHelper {}
 impl Validator for MyHelper {}
 impl Helper for MyHelper {}
 
+/// What a key made with `bind` does: ends the read so that its command runs
+/// in place of the line.
+struct RunCommand {
+    command: String,
+    pending: Arc<Mutex<Option<String>>>,
+}
+
+impl ConditionalEventHandler for RunCommand {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        _ctx: &EventContext,
+    ) -> Option<Cmd> {
+        *self.pending.lock().unwrap_or_else(|e| e.into_inner()) = Some(self.command.clone());
+        Some(Cmd::AcceptLine)
+    }
+}
+
 pub struct LineEditor {
     rl: Editor<MyHelper, DefaultHistory>, // Corrected type and name
+    /// The bindings the editor was last given
+    bindings: Vec<(String, String)>,
+    /// The command of a bound key pressed during the current read
+    pending: Arc<Mutex<Option<String>>>,
+    /// A line put aside to run a bound key's command, typed again for the user
+    set_aside: Option<String>,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LineEditor {
@@ -37,24 +117,119 @@ impl LineEditor {
         let config = Config::builder()
             .history_ignore_dups(true)
             .unwrap()
+            .max_history_size(history::MAX_ENTRIES)
+            .unwrap()
             .build();
 
-        let helper = MyHelper;
+        let helper = MyHelper::default();
         let mut rl = Editor::with_config(config).expect("Failed to create Editor");
         rl.set_helper(Some(helper));
-        rl.load_history(".history.txt").ok(); // Optional: load history
+        // Ctrl-R searches back through these
+        for command in history::commands() {
+            let _ = rl.add_history_entry(command);
+        }
+
+        LineEditor {
+            rl,
+            bindings: Vec::new(),
+            pending: Arc::default(),
+            set_aside: None,
+        }
+    }
+
+    /// Brings the editing mode and key bindings up to date with `set -o` and
+    /// `bind`, which may have changed since the last line was read.
+    fn configure(&mut self) {
+        let mode = if options::is_set(ShellOption::Vi) {
+            EditMode::Vi
+        } else {
+            EditMode::Emacs
+        };
+        self.rl.set_edit_mode(mode);
+
+        let bindings = bind::all();
+        if bindings == self.bindings {
+            return;
+        }
+        for (key, _) in &self.bindings {
+            if let Some((_, key)) = bind::parse_key(key) {
+                self.rl.unbind_sequence(key);
+            }
+        }
+        for (key, command) in &bindings {
+            if let Some((_, key)) = bind::parse_key(key) {
+                let handler = RunCommand {
+                    command: command.clone(),
+                    pending: Arc::clone(&self.pending),
+                };
+                self.rl
+                    .bind_sequence(key, EventHandler::Conditional(Box::new(handler)));
+            }
+        }
+        self.bindings = bindings;
+    }
 
-        LineEditor { rl }
+    /// Reads one line, or the command of a bound key if one was pressed. The
+    /// line typed so far is then offered again at the next prompt.
+    fn read_one(&mut self, prompt: &str) -> Result<String, ReadlineError> {
+        let line = match self.set_aside.take() {
+            Some(line) => self.rl.readline_with_initial(prompt, (&line, "")),
+            None => self.rl.readline(prompt),
+        }?;
+        match self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            Some(command) => {
+                if !line.is_empty() {
+                    self.set_aside = Some(line);
+                }
+                Ok(command)
+            }
+            None => Ok(line),
+        }
     }
 
-    pub fn read_line(&mut self) -> Result<String, ReadlineError> {
-        self.rl.readline(">> ")
+    /// Reads a command, which may take several lines. rustyline cannot show
+    /// a prompt on the lines after the first, so rather than leaving that to
+    /// its validator, lines are read one at a time with the `PS2` prompt for
+    /// as long as the command is incomplete or the line ends with a backslash.
+    pub fn read_line(&mut self, state: &prompt::State) -> Result<String, ReadlineError> {
+        self.configure();
+        let mut command = self.read_one(&prompt::primary(state))?;
+        loop {
+            let trailing_backslashes = command.len() - command.trim_end_matches('\\').len();
+            if trailing_backslashes % 2 == 1 {
+                command.pop();
+            } else if shell::is_incomplete(&command) {
+                command.push('\n');
+            } else {
+                return Ok(command);
+            }
+            match self.read_one(&prompt::continuation(state)) {
+                Ok(line) => command.push_str(&line),
+                // Ctrl-C drops the whole command, as in other shells
+                Err(ReadlineError::Interrupted) => return Ok(String::new()),
+                // Running what there is reports why it is incomplete
+                Err(ReadlineError::Eof) => return Ok(command),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Tells the editor which functions the shell has, so that calls to them
+    /// are highlighted as known commands.
+    pub fn set_functions(&mut self, functions: impl IntoIterator<Item = String>) {
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.functions = functions.into_iter().collect();
+        }
     }
 
     pub fn add_history_entry(&mut self, line: &str) {
         if let Err(e) = self.rl.add_history_entry(line) {
             eprintln!("Failed to add history entry: {}", e);
         }
-        self.rl.save_history(".history.txt").ok(); // Optional: save history
     }
 }
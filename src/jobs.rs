@@ -0,0 +1,438 @@
+//! The table of background jobs started with `&`, which the `jobs`, `fg`,
+//! `bg`, `wait` and `disown` built-ins work on.
+//!
+//! Each job is a winix process of its own, in a process group of its own, so
+//! a `cd` or assignment in a job stays in the job, Ctrl-C at the prompt does
+//! not reach it, and a disowned job keeps running after the shell exits.
+
+use std::io::{self, Write};
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+/// The status of a command stopped by Ctrl-Z, 128 plus `SIGTSTP`, as in
+/// POSIX shells.
+pub const STOPPED_STATUS: i32 = 148;
+
+/// Somewhere a job's output can be copied to.
+pub type Output = Box<dyn Write + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: usize,
+    /// The commands as they were typed, without the `&`
+    pub text: String,
+    child: Child,
+    /// Threads copying the job's stdout and stderr, when they go to a buffer
+    copies: Vec<JoinHandle<()>>,
+    state: State,
+}
+
+impl Job {
+    /// The process ID, which `$!` expands to.
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The job's state, checked without waiting.
+    pub fn state(&mut self) -> State {
+        if !matches!(self.state, State::Done(_))
+            && let Some(state) = sys::poll(&mut self.child, false)
+        {
+            self.state = state;
+        }
+        self.state
+    }
+
+    /// Waits for the job to finish or be stopped, returning its state.
+    pub fn wait(&mut self) -> State {
+        while !matches!(self.state, State::Done(_)) {
+            match sys::poll(&mut self.child, true) {
+                Some(State::Running) | None => continue,
+                Some(state) => {
+                    self.state = state;
+                    break;
+                }
+            }
+        }
+        if let State::Done(_) = self.state {
+            // Everything the job wrote arrives before anything after it
+            for copy in self.copies.drain(..) {
+                let _ = copy.join();
+            }
+        }
+        self.state
+    }
+
+    /// Waits for the job like [`Job::wait`], with the terminal handed to it
+    /// meanwhile so that Ctrl-C and Ctrl-Z go to the job rather than the
+    /// shell.
+    pub fn wait_in_foreground(&mut self) -> State {
+        let pid = self.child.id();
+        sys::with_terminal(pid, || self.wait())
+    }
+
+    /// Lets a stopped job carry on.
+    pub fn resume(&mut self) {
+        if self.state == State::Stopped {
+            sys::resume(&self.child);
+            self.state = State::Running;
+        }
+    }
+
+    /// The job's line in the output of `jobs`, such as
+    /// `[1]+  Running                 sleep 10 &`. The marker is `+` for the
+    /// current job and `-` for the one before it.
+    pub fn describe(&mut self, marker: char) -> String {
+        let (state, text) = match self.state() {
+            State::Running => ("Running".to_string(), format!("{} &", self.text)),
+            State::Stopped => ("Stopped".to_string(), self.text.clone()),
+            State::Done(0) => ("Done".to_string(), self.text.clone()),
+            State::Done(code) => (format!("Exit {}", code), self.text.clone()),
+        };
+        format!("[{}]{}  {:<24}{}", self.id, marker, state, text)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct JobTable {
+    /// Oldest first, so the last job is the current one
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    /// Starts `command` as a new job with no input, returning its number.
+    /// Its output goes to `output` if given, and otherwise to the shell's
+    /// own stdout and stderr. Numbers are reused once the jobs holding them
+    /// are gone, as in other shells.
+    pub fn start(
+        &mut self,
+        text: String,
+        mut command: Command,
+        output: Option<(Output, Output)>,
+    ) -> io::Result<usize> {
+        command.stdin(Stdio::null());
+        if output.is_some() {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        sys::separate_group(&mut command);
+        let mut child = command.spawn()?;
+
+        let mut copies = Vec::new();
+        if let Some((mut stdout, mut stderr)) = output {
+            if let Some(mut pipe) = child.stdout.take() {
+                copies.push(thread::spawn(move || {
+                    let _ = io::copy(&mut pipe, &mut stdout);
+                }));
+            }
+            if let Some(mut pipe) = child.stderr.take() {
+                copies.push(thread::spawn(move || {
+                    let _ = io::copy(&mut pipe, &mut stderr);
+                }));
+            }
+        }
+
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            text,
+            child,
+            copies,
+            state: State::Running,
+        });
+        Ok(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// The `+` or `-` shown next to the job at `index`, or a space.
+    pub fn marker(&self, index: usize) -> char {
+        match self.jobs.len() - index {
+            1 => '+',
+            2 => '-',
+            _ => ' ',
+        }
+    }
+
+    /// The position of the job a job spec names: `%N` for job N, `N` for the
+    /// job with process ID N, `%%`, `%+` or no spec for the current job, `%-`
+    /// for the previous one, and `%TEXT` for the job whose commands start
+    /// with TEXT.
+    pub fn find(&self, spec: Option<&str>) -> Result<usize, String> {
+        let no_such_job = || format!("{}: no such job", spec.unwrap_or("current"));
+        if let Some(Ok(pid)) = spec.map(str::parse::<u32>) {
+            return self
+                .jobs
+                .iter()
+                .position(|job| job.pid() == pid)
+                .ok_or_else(no_such_job);
+        }
+        let spec = spec.map(|spec| spec.strip_prefix('%').unwrap_or(spec));
+        let index = match spec {
+            None | Some("%" | "+" | "") => self.jobs.len().checked_sub(1),
+            Some("-") => self.jobs.len().checked_sub(2),
+            Some(spec) => match spec.parse::<usize>() {
+                Ok(id) => self.jobs.iter().position(|job| job.id == id),
+                Err(_) => self.jobs.iter().position(|job| job.text.starts_with(spec)),
+            },
+        };
+        index.ok_or_else(no_such_job)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Job {
+        &mut self.jobs[index]
+    }
+
+    /// Takes a job out of the table, after which it is no longer waited for
+    /// or hung up when the shell exits.
+    pub fn remove(&mut self, index: usize) -> Job {
+        self.jobs.remove(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.jobs.clear();
+    }
+
+    /// Lines describing every job, like `jobs` prints. Jobs that have
+    /// finished are listed one last time and then forgotten.
+    pub fn describe_all(&mut self) -> Vec<String> {
+        let indices: Vec<usize> = (0..self.jobs.len()).collect();
+        self.describe_jobs(&indices)
+    }
+
+    /// Like [`JobTable::describe_all`], but only for the jobs at `indices`,
+    /// as `jobs %1 %3` prints.
+    pub fn describe_jobs(&mut self, indices: &[usize]) -> Vec<String> {
+        let lines = indices
+            .iter()
+            .map(|&index| {
+                let marker = self.marker(index);
+                self.jobs[index].describe(marker)
+            })
+            .collect();
+        let mut index = 0;
+        self.jobs.retain(|job| {
+            let listed = indices.contains(&index);
+            index += 1;
+            !(listed && matches!(job.state, State::Done(_)))
+        });
+        lines
+    }
+
+    /// Removes the jobs that have finished since the last call, returning a
+    /// line announcing each.
+    pub fn take_finished(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut index = 0;
+        while index < self.jobs.len() {
+            if let State::Done(_) = self.jobs[index].state() {
+                let marker = self.marker(index);
+                lines.push(self.jobs[index].describe(marker));
+                self.jobs.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        lines
+    }
+}
+
+/// Like other shells when they hang up, the jobs still in the table end with
+/// the shell. Disowned jobs have left it, so they keep running.
+impl Drop for JobTable {
+    fn drop(&mut self) {
+        for job in &mut self.jobs {
+            if !matches!(job.state(), State::Done(_)) {
+                sys::hang_up(&mut job.child);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::State;
+    use std::io;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Child, Command};
+
+    pub fn separate_group(command: &mut Command) {
+        command.process_group(0);
+    }
+
+    /// The job's new state, or `None` if it has not changed. Unlike
+    /// `Child::try_wait`, this sees the job being stopped and continued.
+    pub fn poll(child: &mut Child, block: bool) -> Option<State> {
+        let flags = libc::WUNTRACED | libc::WCONTINUED | if block { 0 } else { libc::WNOHANG };
+        let mut status = 0;
+        let pid = loop {
+            // SAFETY: waitpid only writes the status it is given
+            let pid = unsafe { libc::waitpid(child.id() as libc::pid_t, &mut status, flags) };
+            if pid != -1 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                break pid;
+            }
+        };
+        match pid {
+            0 => None,
+            // Someone else collected it, so its status is lost
+            -1 => Some(State::Done(1)),
+            _ if libc::WIFSTOPPED(status) => Some(State::Stopped),
+            _ if libc::WIFCONTINUED(status) => Some(State::Running),
+            _ if libc::WIFSIGNALED(status) => Some(State::Done(128 + libc::WTERMSIG(status))),
+            _ => Some(State::Done(libc::WEXITSTATUS(status))),
+        }
+    }
+
+    fn signal_group(child: &Child, signal: libc::c_int) {
+        // SAFETY: kill only sends a signal, here to the job's process group
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), signal);
+        }
+    }
+
+    pub fn resume(child: &Child) {
+        signal_group(child, libc::SIGCONT);
+    }
+
+    pub fn hang_up(child: &mut Child) {
+        signal_group(child, libc::SIGHUP);
+        // A stopped job only sees the hang-up once it runs again
+        signal_group(child, libc::SIGCONT);
+    }
+
+    /// Makes the job's process group the terminal's foreground group while
+    /// `run` runs, if the shell has the terminal to give.
+    pub fn with_terminal<T>(pid: u32, run: impl FnOnce() -> T) -> T {
+        // SAFETY: these calls only read and change the terminal's foreground
+        // process group and how SIGTTOU is handled
+        unsafe {
+            let shell = libc::getpgrp();
+            if libc::isatty(libc::STDIN_FILENO) == 0 || libc::tcgetpgrp(libc::STDIN_FILENO) != shell
+            {
+                return run();
+            }
+            // Taking the terminal back from the job would otherwise stop the shell
+            let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::tcsetpgrp(libc::STDIN_FILENO, pid as libc::pid_t);
+            let result = run();
+            libc::tcsetpgrp(libc::STDIN_FILENO, shell);
+            libc::signal(libc::SIGTTOU, previous);
+            result
+        }
+    }
+}
+
+/// Windows has no signals to stop a job with, so jobs only ever run or finish.
+#[cfg(not(unix))]
+mod sys {
+    use super::State;
+    use crate::external;
+    use std::os::windows::process::CommandExt;
+    use std::process::{Child, Command};
+
+    pub fn separate_group(command: &mut Command) {
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    pub fn poll(child: &mut Child, block: bool) -> Option<State> {
+        let status = if block {
+            child.wait().map(Some)
+        } else {
+            child.try_wait()
+        };
+        match status {
+            Ok(Some(status)) => Some(State::Done(external::exit_code(status))),
+            Ok(None) => None,
+            Err(_) => Some(State::Done(1)),
+        }
+    }
+
+    pub fn resume(_child: &Child) {}
+
+    pub fn hang_up(child: &mut Child) {
+        let _ = child.kill();
+    }
+
+    pub fn with_terminal<T>(_pid: u32, run: impl FnOnce() -> T) -> T {
+        run()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn start(table: &mut JobTable, text: &str) -> usize {
+        let mut words = text.split_whitespace();
+        let mut command = Command::new(words.next().unwrap());
+        command.args(words);
+        table.start(text.to_string(), command, None).unwrap()
+    }
+
+    #[test]
+    fn test_job_table() {
+        let mut table = JobTable::default();
+        start(&mut table, "sleep 10");
+        start(&mut table, "false");
+        assert_eq!(table.find(None), Ok(1));
+        assert_eq!(table.find(Some("%-")), Ok(0));
+        assert_eq!(table.find(Some("%sle")), Ok(0));
+        assert_eq!(table.find(Some("%2")), Ok(1));
+        let pid = table.get_mut(0).pid().to_string();
+        assert_eq!(table.find(Some(&pid)), Ok(0));
+        assert_eq!(table.find(Some("%3")), Err("%3: no such job".to_string()));
+
+        assert_eq!(table.get_mut(1).wait(), State::Done(1));
+        assert_eq!(
+            table.take_finished(),
+            vec!["[2]+  Exit 1                  false"]
+        );
+        assert_eq!(
+            table.describe_all(),
+            vec!["[1]+  Running                 sleep 10 &"]
+        );
+
+        // The number of a finished job is free again
+        assert_eq!(start(&mut table, "true"), 2);
+        table.get_mut(1).wait();
+        assert_eq!(
+            table.describe_all(),
+            vec![
+                "[1]-  Running                 sleep 10 &",
+                "[2]+  Done                    true"
+            ]
+        );
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_stop_and_resume() {
+        let mut table = JobTable::default();
+        start(&mut table, "sleep 10");
+        let job = table.get_mut(0);
+        sys::hang_up(&mut job.child);
+        // The hang-up ends it, as it would when the shell exits
+        assert_eq!(job.wait(), State::Done(128 + libc::SIGHUP));
+
+        start(&mut table, "sleep 10");
+        let job = table.get_mut(1);
+        unsafe { libc::kill(job.pid() as libc::pid_t, libc::SIGSTOP) };
+        assert_eq!(job.wait(), State::Stopped);
+        assert!(job.describe('+').contains("Stopped"));
+        job.resume();
+        assert_eq!(job.state(), State::Running);
+    }
+}
@@ -31,6 +31,7 @@ Key things addressed:
 #![cfg(windows)]
 
 use colored::Colorize;
+use std::io::Write;
 use std::thread;
 use std::time::Duration;
 use winapi::shared::minwindef::{BOOL, DWORD, LPARAM, TRUE};
@@ -88,6 +89,38 @@ impl Default for KillOptions {
     }
 }
 
+pub struct Kill;
+
+impl crate::commands::Command for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn usage(&self) -> &'static str {
+        "kill [-signal] <pid|name>..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Terminate processes by PID or name"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn std::io::Read,
+        _stdout: &mut dyn std::io::Write,
+        stderr: &mut dyn std::io::Write,
+    ) -> i32 {
+        match execute(&args.iter().map(String::as_str).collect::<Vec<_>>()) {
+            Ok(()) => 0,
+            Err(e) => {
+                let _ = writeln!(stderr, "{}", e.red());
+                1
+            }
+        }
+    }
+}
+
 pub fn execute(args: &[&str]) -> Result<(), String> {
     if args.is_empty() {
         return Err(format!(
@@ -1,15 +1,17 @@
 pub mod process;
 pub mod kill; 
 pub mod echo;
+pub mod printf;
+pub mod test;
 pub mod touch;
 pub mod ansi;
 pub mod cat;
 pub mod rm;
+pub mod trash;
 pub mod input;
 #[cfg(windows)]
 pub mod chmod;
 pub mod chown;
-pub mod disown;
 pub mod df;
 pub mod free;
 pub mod git;
@@ -24,6 +26,28 @@ pub mod grep;
 pub mod head;
 pub mod tail;
 pub mod pipeline;
+pub mod parser;
+pub mod external;
+pub mod redirect;
+pub mod commands;
+pub mod shell;
+pub mod cd;
+pub mod pwd;
+pub mod ls;
+pub mod cp;
+pub mod mv;
+pub mod mkdir;
+pub mod vars;
+pub mod options;
+pub mod glob;
+pub mod alias;
+pub mod bind;
+pub mod jobs;
+pub mod completion;
+pub mod highlight;
+pub mod history;
+pub mod localtime;
+pub mod prompt;
 
 #[cfg(test)]
 mod tests {
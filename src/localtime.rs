@@ -0,0 +1,67 @@
+//! Converting between UTC and the local time zone, with the offset the zone
+//! has on the day in question rather than today's, so that times on the
+//! other side of a change to or from summer time come out right.
+
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+/// The current time in the local time zone.
+pub fn now() -> OffsetDateTime {
+    to_local(OffsetDateTime::now_utc())
+}
+
+/// `time` as the local clock showed it.
+pub fn to_local(time: OffsetDateTime) -> OffsetDateTime {
+    time.to_offset(offset_at(time))
+}
+
+/// The moment at which the local clock shows `local`. The offset is guessed
+/// from `local` read as UTC and then looked up again for the moment that
+/// gives, which settles it for all but the hour around a change of offset.
+pub fn assume_local(local: PrimitiveDateTime) -> OffsetDateTime {
+    let guess = local.assume_offset(offset_at(local.assume_utc()));
+    local.assume_offset(offset_at(guess))
+}
+
+/// The local time zone's offset from UTC at `time`, or UTC if it cannot be
+/// found. The C library is asked directly, since `time` refuses to look the
+/// offset up once the shell has started other threads.
+#[cfg(unix)]
+fn offset_at(time: OffsetDateTime) -> UtcOffset {
+    let seconds = time.unix_timestamp() as libc::time_t;
+    // SAFETY: localtime_r only writes the broken-down time it is given, for
+    // which all zeroes is a valid starting value
+    let offset = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&seconds, &mut tm).is_null() {
+            return UtcOffset::UTC;
+        }
+        tm.tm_gmtoff
+    };
+    i32::try_from(offset)
+        .ok()
+        .and_then(|seconds| UtcOffset::from_whole_seconds(seconds).ok())
+        .unwrap_or(UtcOffset::UTC)
+}
+
+#[cfg(not(unix))]
+fn offset_at(time: OffsetDateTime) -> UtcOffset {
+    UtcOffset::local_offset_at(time).unwrap_or(UtcOffset::UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        // Noon UTC in January and in July, which are on different sides of
+        // summer time wherever it is kept
+        for timestamp in [1_704_110_400, 1_719_835_200] {
+            let time = OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
+            let local = to_local(time);
+            assert_eq!(local, time);
+            let clock = PrimitiveDateTime::new(local.date(), local.time());
+            assert_eq!(assume_local(clock), time);
+        }
+    }
+}
@@ -0,0 +1,884 @@
+//! `ls`, and the directory listings the TUI's file browser shows.
+//!
+//! Names are coloured from `LS_COLORS` in the format `dircolors` writes,
+//! such as `di=01;34:ln=01;36:*.tar=01;31`. Without it, directories, links,
+//! executables and special files get the colours GNU `ls` gives them.
+
+use crate::commands::{self, Command};
+use crate::localtime;
+use crate::vars;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+
+/// The colours used when `LS_COLORS` is not set.
+const DEFAULT_COLORS: &str =
+    "di=01;34:ln=01;36:pi=40;33:so=01;35:bd=40;33;01:cd=40;33;01:or=40;31;01:ex=01;32";
+
+/// How long ago a file may have changed for `-l` to show the time rather
+/// than the year, as other `ls`es do: about six months.
+const RECENT: Duration = Duration::from_secs(31_556_952 / 2);
+
+/// What to list entries by.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SortBy {
+    #[default]
+    Name,
+    /// Newest first
+    Time,
+    /// Largest first
+    Size,
+}
+
+/// What to list and how.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// `-a`: names starting with `.`, and `.` and `..` themselves
+    pub all: bool,
+    /// `-A`: names starting with `.`, but not `.` and `..`
+    pub almost_all: bool,
+    /// `-l`
+    pub long: bool,
+    /// `-h`: sizes such as `4.0K` and `12M`
+    pub human: bool,
+    /// `-R`
+    pub recursive: bool,
+    pub sort: SortBy,
+    /// `-r`
+    pub reverse: bool,
+    /// The width to lay names out in columns in. Without one, as for `-1`,
+    /// they go one per line.
+    pub width: Option<usize>,
+    /// Whether to colour names
+    pub color: bool,
+}
+
+/// The kinds of file `LS_COLORS` tells apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    File,
+    Executable,
+    Directory,
+    Symlink,
+    /// A link to something that does not exist
+    BrokenSymlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+/// A file to list.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The name shown for it
+    pub name: String,
+    pub path: PathBuf,
+    /// The file's own metadata, not that of what it links to
+    pub metadata: fs::Metadata,
+    /// Where a symbolic link points
+    pub target: Option<PathBuf>,
+    /// The metadata of what a symbolic link points to, if that exists
+    target_metadata: Option<fs::Metadata>,
+}
+
+impl Entry {
+    pub fn new(path: impl Into<PathBuf>, name: impl Into<String>) -> io::Result<Entry> {
+        let path = path.into();
+        let metadata = fs::symlink_metadata(&path)?;
+        let (target, target_metadata) = if metadata.file_type().is_symlink() {
+            (fs::read_link(&path).ok(), fs::metadata(&path).ok())
+        } else {
+            (None, None)
+        };
+        Ok(Entry {
+            name: name.into(),
+            path,
+            metadata,
+            target,
+            target_metadata,
+        })
+    }
+
+    pub fn kind(&self) -> Kind {
+        if self.metadata.file_type().is_symlink() {
+            return match self.target_metadata {
+                Some(_) => Kind::Symlink,
+                None => Kind::BrokenSymlink,
+            };
+        }
+        kind_of(&self.path, &self.metadata)
+    }
+
+    /// Whether this is a directory, or a link to one.
+    pub fn is_dir(&self) -> bool {
+        self.target_metadata
+            .as_ref()
+            .unwrap_or(&self.metadata)
+            .is_dir()
+    }
+
+    fn modified(&self) -> SystemTime {
+        self.metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+fn kind_of(path: &Path, metadata: &fs::Metadata) -> Kind {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        return Kind::Directory;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        if file_type.is_fifo() {
+            return Kind::Fifo;
+        } else if file_type.is_socket() {
+            return Kind::Socket;
+        } else if file_type.is_block_device() {
+            return Kind::BlockDevice;
+        } else if file_type.is_char_device() {
+            return Kind::CharDevice;
+        } else if metadata.permissions().mode() & 0o111 != 0 {
+            return Kind::Executable;
+        }
+    }
+    #[cfg(windows)]
+    {
+        let extension = path.extension().map(|e| e.to_ascii_lowercase());
+        if let Some(extension) = extension
+            && ["exe", "bat", "cmd", "com", "ps1"]
+                .iter()
+                .any(|e| extension == *e)
+        {
+            return Kind::Executable;
+        }
+    }
+    let _ = path;
+    Kind::File
+}
+
+/// The entries of a directory that `options` asks for, sorted.
+pub fn read_dir(dir: &Path, options: &Options) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    if options.all {
+        entries.push(Entry::new(dir.join("."), ".")?);
+        entries.push(Entry::new(dir.join(".."), "..")?);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') && !options.all && !options.almost_all {
+            continue;
+        }
+        // It may have gone since the directory was read
+        if let Ok(entry) = Entry::new(entry.path(), name) {
+            entries.push(entry);
+        }
+    }
+    sort(&mut entries, options);
+    Ok(entries)
+}
+
+/// Sorts entries as `options` asks.
+pub fn sort(entries: &mut [Entry], options: &Options) {
+    entries.sort_by(|a, b| {
+        let order = match options.sort {
+            SortBy::Name => Ordering::Equal,
+            SortBy::Time => b.modified().cmp(&a.modified()),
+            SortBy::Size => b.metadata.len().cmp(&a.metadata.len()),
+        };
+        order.then_with(|| compare_names(&a.name, &b.name))
+    });
+    if options.reverse {
+        entries.reverse();
+    }
+}
+
+/// Orders names ignoring case and leading dots, as `ls` does in most
+/// locales, so that `.bashrc` sorts with `bin`.
+fn compare_names(a: &str, b: &str) -> Ordering {
+    let key = |name: &str| name.trim_start_matches('.').to_lowercase();
+    key(a).cmp(&key(b)).then_with(|| a.cmp(b))
+}
+
+/// A size as `-h` shows it: `1023`, `4.0K`, `12M`.
+pub fn human_size(size: u64) -> String {
+    let mut value = size as f64;
+    if value < 1024.0 {
+        return size.to_string();
+    }
+    for unit in ['K', 'M', 'G', 'T', 'P', 'E'] {
+        value /= 1024.0;
+        if value < 1024.0 || unit == 'E' {
+            // Rounded up, so that a size is never shown as less than it is
+            let tenths = (value * 10.0).ceil() / 10.0;
+            return if tenths < 10.0 {
+                format!("{:.1}{}", tenths, unit)
+            } else {
+                format!("{}{}", value.ceil(), unit)
+            };
+        }
+    }
+    unreachable!()
+}
+
+/// The colours `LS_COLORS` gives each kind of file and file extension.
+struct Colors {
+    kinds: HashMap<String, String>,
+    /// Extensions with their leading `.`, in lower case
+    extensions: Vec<(String, String)>,
+}
+
+impl Colors {
+    fn parse(spec: &str) -> Colors {
+        let mut colors = Colors {
+            kinds: HashMap::new(),
+            extensions: Vec::new(),
+        };
+        for (key, code) in spec.split(':').filter_map(|item| item.split_once('=')) {
+            match key.strip_prefix('*') {
+                Some(suffix) => colors
+                    .extensions
+                    .push((suffix.to_lowercase(), code.to_string())),
+                None => {
+                    colors.kinds.insert(key.to_string(), code.to_string());
+                }
+            }
+        }
+        colors
+    }
+
+    fn from_env() -> Colors {
+        match vars::get("LS_COLORS") {
+            Some(spec) if !spec.is_empty() => Colors::parse(&spec),
+            _ => Colors::parse(DEFAULT_COLORS),
+        }
+    }
+
+    /// The SGR code for an entry, if it has one.
+    fn code(&self, name: &str, kind: Kind) -> Option<&str> {
+        let key = match kind {
+            Kind::Directory => "di",
+            Kind::Symlink => "ln",
+            Kind::BrokenSymlink if self.kinds.contains_key("or") => "or",
+            Kind::BrokenSymlink => "ln",
+            Kind::Fifo => "pi",
+            Kind::Socket => "so",
+            Kind::BlockDevice => "bd",
+            Kind::CharDevice => "cd",
+            Kind::Executable => "ex",
+            Kind::File => {
+                let name = name.to_lowercase();
+                let by_extension = self
+                    .extensions
+                    .iter()
+                    .find(|(suffix, _)| name.ends_with(suffix.as_str()));
+                if let Some((_, code)) = by_extension {
+                    return Some(code);
+                }
+                "fi"
+            }
+        };
+        self.kinds
+            .get(key)
+            .map(String::as_str)
+            .filter(|code| !code.is_empty())
+    }
+
+    fn paint(&self, text: &str, name: &str, kind: Kind) -> String {
+        match self.code(name, kind) {
+            Some(code) => format!("\x1b[{}m{}\x1b[0m", code, text),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// An entry's name, coloured if `colors` is given.
+fn shown_name(entry: &Entry, colors: Option<&Colors>) -> String {
+    match colors {
+        Some(colors) => colors.paint(&entry.name, &entry.name, entry.kind()),
+        None => entry.name.clone(),
+    }
+}
+
+/// The `total` line `-l` starts a directory with: the space its files take.
+fn total(entries: &[Entry], human: bool) -> String {
+    #[cfg(unix)]
+    let bytes: u64 = {
+        use std::os::unix::fs::MetadataExt;
+        entries
+            .iter()
+            .map(|entry| entry.metadata.blocks() * 512)
+            .sum()
+    };
+    #[cfg(not(unix))]
+    let bytes: u64 = entries
+        .iter()
+        .map(|entry| entry.metadata.len().div_ceil(1024) * 1024)
+        .sum();
+    if human {
+        format!("total {}", human_size(bytes))
+    } else {
+        format!("total {}", bytes / 1024)
+    }
+}
+
+/// The lines `ls -l` shows for some entries, with their columns lined up.
+/// Names are coloured when `options.color` is set.
+pub fn long_lines(entries: &[Entry], options: &Options) -> Vec<String> {
+    let colors = options.color.then(Colors::from_env);
+    let now = SystemTime::now();
+    let rows: Vec<[String; 6]> = entries
+        .iter()
+        .map(|entry| {
+            let (links, owner, group) = ownership(&entry.metadata);
+            let size = entry.metadata.len();
+            [
+                mode_string(&entry.metadata),
+                links.to_string(),
+                owner,
+                group,
+                if options.human {
+                    human_size(size)
+                } else {
+                    size.to_string()
+                },
+                format_time(entry.modified(), now),
+            ]
+        })
+        .collect();
+
+    let width = |column: usize| {
+        rows.iter()
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let widths = [width(1), width(2), width(3), width(4)];
+    entries
+        .iter()
+        .zip(&rows)
+        .map(|(entry, [mode, links, owner, group, size, time])| {
+            let mut line = format!(
+                "{} {:>w1$} {:<w2$} {:<w3$} {:>w4$} {} {}",
+                mode,
+                links,
+                owner,
+                group,
+                size,
+                time,
+                shown_name(entry, colors.as_ref()),
+                w1 = widths[0],
+                w2 = widths[1],
+                w3 = widths[2],
+                w4 = widths[3],
+            );
+            if let Some(target) = &entry.target {
+                let target = target.to_string_lossy();
+                let target = match (&colors, &entry.target_metadata) {
+                    (Some(colors), Some(metadata)) => {
+                        colors.paint(&target, &target, kind_of(&entry.path, metadata))
+                    }
+                    (Some(colors), None) => colors.paint(&target, &target, Kind::BrokenSymlink),
+                    (None, _) => target.into_owned(),
+                };
+                line.push_str(" -> ");
+                line.push_str(&target);
+            }
+            line
+        })
+        .collect()
+}
+
+/// `drwxr-xr-x` and the like.
+#[cfg(unix)]
+fn mode_string(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    let file_type = metadata.file_type();
+    let mode = metadata.permissions().mode();
+    let type_char = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else {
+        '-'
+    };
+
+    let mut string = String::from(type_char);
+    // Each class's bits, with the setuid, setgid or sticky bit shown in
+    // place of its execute bit
+    for (shift, special, set_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        string.push(if bits & 4 != 0 { 'r' } else { '-' });
+        string.push(if bits & 2 != 0 { 'w' } else { '-' });
+        string.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set_char,
+            (false, true) => set_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    string
+}
+
+#[cfg(not(unix))]
+fn mode_string(metadata: &fs::Metadata) -> String {
+    let type_char = if metadata.is_dir() {
+        'd'
+    } else if metadata.file_type().is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+    let permissions = if metadata.permissions().readonly() {
+        "r--r--r--"
+    } else {
+        "rw-rw-rw-"
+    };
+    format!("{}{}", type_char, permissions)
+}
+
+/// The link count, owner and group of a file.
+#[cfg(unix)]
+fn ownership(metadata: &fs::Metadata) -> (u64, String, String) {
+    use std::os::unix::fs::MetadataExt;
+    let users = names("/etc/passwd");
+    let groups = names("/etc/group");
+    let name = |names: &HashMap<u32, String>, id: u32| {
+        names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    };
+    (
+        metadata.nlink(),
+        name(users, metadata.uid()),
+        name(groups, metadata.gid()),
+    )
+}
+
+#[cfg(not(unix))]
+fn ownership(_metadata: &fs::Metadata) -> (u64, String, String) {
+    (1, "-".to_string(), "-".to_string())
+}
+
+/// The names in `/etc/passwd` or `/etc/group` by their ids, read once.
+#[cfg(unix)]
+fn names(file: &'static str) -> &'static HashMap<u32, String> {
+    use std::sync::OnceLock;
+    static USERS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    static GROUPS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    let cell = if file == "/etc/passwd" {
+        &USERS
+    } else {
+        &GROUPS
+    };
+    cell.get_or_init(|| {
+        fs::read_to_string(file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let id = fields.nth(1)?.parse().ok()?;
+                Some((id, name.to_string()))
+            })
+            .collect()
+    })
+}
+
+/// `Oct 17 14:05` for recent times, `Oct 17  2024` for others.
+fn format_time(time: SystemTime, now: SystemTime) -> String {
+    let date = localtime::to_local(OffsetDateTime::from(time));
+    let month = &date.month().to_string()[..3];
+    let recent = now.duration_since(time).is_ok_and(|age| age < RECENT);
+    if recent {
+        format!(
+            "{} {:>2} {:02}:{:02}",
+            month,
+            date.day(),
+            date.hour(),
+            date.minute()
+        )
+    } else {
+        format!("{} {:>2} {:>5}", month, date.day(), date.year())
+    }
+}
+
+/// Lays names out in columns filled from top to bottom, using as few rows
+/// as fit in `width`. Each cell is its text and the width it takes on
+/// screen, which colours do not add to.
+fn columns(cells: &[(String, usize)], width: usize) -> Vec<String> {
+    const GAP: usize = 2;
+    if cells.is_empty() {
+        return Vec::new();
+    }
+    let mut rows = 1;
+    let widths = loop {
+        let column_count = cells.len().div_ceil(rows);
+        let widths: Vec<usize> = cells
+            .chunks(rows)
+            .map(|column| column.iter().map(|(_, w)| *w).max().unwrap_or(0))
+            .collect();
+        let total = widths.iter().sum::<usize>() + GAP * (column_count - 1);
+        if total <= width || rows >= cells.len() {
+            break widths;
+        }
+        rows += 1;
+    };
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            let row_cells: Vec<_> = cells.iter().skip(row).step_by(rows).collect();
+            for (column, (text, cell_width)) in row_cells.iter().enumerate() {
+                line.push_str(text);
+                if column + 1 < row_cells.len() {
+                    line.push_str(&" ".repeat(widths[column] - cell_width + GAP));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// The terminal's width, from `COLUMNS` or the terminal itself.
+fn terminal_width() -> usize {
+    vars::get("COLUMNS")
+        .and_then(|columns| columns.parse().ok())
+        .or_else(|| crossterm::terminal::size().ok().map(|(w, _)| w as usize))
+        .filter(|width| *width > 0)
+        .unwrap_or(80)
+}
+
+/// Writes entries as names in columns, one per line, or in long format.
+fn write_entries(out: &mut dyn Write, entries: &[Entry], options: &Options) -> io::Result<()> {
+    if options.long {
+        for line in long_lines(entries, options) {
+            writeln!(out, "{}", line)?;
+        }
+        return Ok(());
+    }
+    let colors = options.color.then(Colors::from_env);
+    let cells: Vec<(String, usize)> = entries
+        .iter()
+        .map(|entry| {
+            (
+                shown_name(entry, colors.as_ref()),
+                entry.name.chars().count(),
+            )
+        })
+        .collect();
+    match options.width {
+        Some(width) => {
+            for line in columns(&cells, width) {
+                writeln!(out, "{}", line)?;
+            }
+        }
+        None => {
+            for (name, _) in cells {
+                writeln!(out, "{}", name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `ls [-1aAhlRrSt] [--color[=WHEN]] [FILE]...`
+pub struct Ls;
+
+impl Ls {
+    /// Reads the flags and `--color`, which defaults to whether stdout is a
+    /// terminal, and collects the files and directories to list in `paths`.
+    /// An unknown flag or `--color` value gives `None`.
+    fn parse_args(args: &[String], paths: &mut Vec<String>) -> Option<Options> {
+        let interactive = colored::control::SHOULD_COLORIZE.should_colorize();
+        let mut options = Options {
+            color: interactive,
+            width: interactive.then(terminal_width),
+            ..Options::default()
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    paths.extend(args.cloned());
+                    break;
+                }
+                "--all" => options.all = true,
+                "--almost-all" => options.almost_all = true,
+                "--human-readable" => options.human = true,
+                "--recursive" => options.recursive = true,
+                "--reverse" => options.reverse = true,
+                "--color" => options.color = true,
+                long if long.starts_with("--color=") => {
+                    options.color = match &long["--color=".len()..] {
+                        "always" | "yes" | "force" => true,
+                        "never" | "no" | "none" => false,
+                        "auto" | "tty" | "if-tty" => interactive,
+                        _ => return None,
+                    }
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                    for flag in flags[1..].chars() {
+                        match flag {
+                            'a' => options.all = true,
+                            'A' => options.almost_all = true,
+                            'l' => options.long = true,
+                            'h' => options.human = true,
+                            'R' => options.recursive = true,
+                            'r' => options.reverse = true,
+                            't' => options.sort = SortBy::Time,
+                            'S' => options.sort = SortBy::Size,
+                            '1' => options.width = None,
+                            _ => return None,
+                        }
+                    }
+                }
+                _ if arg.starts_with("--") => return None,
+                _ => paths.push(arg.clone()),
+            }
+        }
+        Some(options)
+    }
+
+    /// Lists a directory, and those below it for `-R`. Returns the exit
+    /// status, 1 if anything could not be read.
+    fn list_dir(
+        &self,
+        dir: &Path,
+        shown: &str,
+        header: bool,
+        options: &Options,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> io::Result<i32> {
+        if header {
+            writeln!(stdout, "{}:", shown)?;
+        }
+        let entries = match read_dir(dir, options) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let message = format!(
+                    "cannot open directory '{}': {}",
+                    shown,
+                    commands::io_message(&e)
+                );
+                return Ok(commands::report_error(stderr, self.name(), message));
+            }
+        };
+        if options.long {
+            writeln!(stdout, "{}", total(&entries, options.human))?;
+        }
+        write_entries(stdout, &entries, options)?;
+
+        let mut status = 0;
+        if options.recursive {
+            let below = entries
+                .iter()
+                .filter(|entry| entry.metadata.is_dir() && entry.name != "." && entry.name != "..");
+            for entry in below {
+                writeln!(stdout)?;
+                let shown = Path::new(shown).join(&entry.name);
+                let code = self.list_dir(
+                    &entry.path,
+                    &shown.to_string_lossy(),
+                    true,
+                    options,
+                    stdout,
+                    stderr,
+                )?;
+                status = status.max(code);
+            }
+        }
+        Ok(status)
+    }
+
+    fn list(
+        &self,
+        paths: &[String],
+        options: &Options,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> io::Result<i32> {
+        let mut status = 0;
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        for path in paths {
+            match Entry::new(path, path.as_str()) {
+                // A link to a directory is listed as the link with -l
+                Ok(entry) if entry.is_dir() && !(options.long && entry.target.is_some()) => {
+                    dirs.push(entry)
+                }
+                Ok(entry) => files.push(entry),
+                Err(e) => {
+                    let message = format!("cannot access '{}': {}", path, commands::io_message(&e));
+                    commands::report_error(stderr, self.name(), message);
+                    status = 2;
+                }
+            }
+        }
+        sort(&mut files, options);
+        sort(&mut dirs, options);
+
+        write_entries(stdout, &files, options)?;
+        let header = paths.len() > 1 || options.recursive;
+        for (index, dir) in dirs.iter().enumerate() {
+            if index > 0 || !files.is_empty() {
+                writeln!(stdout)?;
+            }
+            let code = self.list_dir(&dir.path, &dir.name, header, options, stdout, stderr)?;
+            status = status.max(code);
+        }
+        Ok(status)
+    }
+}
+
+impl Command for Ls {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn usage(&self) -> &'static str {
+        "ls [-1aAhlRrSt] [--color[=WHEN]] [FILE]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "List directory contents, in long format (-l), sorted by time (-t) or size (-S)"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let mut paths = Vec::new();
+        let Some(options) = Self::parse_args(args, &mut paths) else {
+            return commands::usage_error(stderr, self.usage());
+        };
+        if paths.is_empty() {
+            paths.push(".".to_string());
+        }
+        match self.list(&paths, &options, stdout, stderr) {
+            Ok(status) => status,
+            Err(e) => commands::report_error(stderr, self.name(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ls(args: &[&str]) -> (i32, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut output = Vec::new();
+        let code = Ls.run(&args, &mut io::empty(), &mut output, &mut io::sink());
+        (code, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1536), "1.5K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(20 * 1024 * 1024 - 1), "20M");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0G");
+    }
+
+    #[test]
+    fn test_columns() {
+        let cells: Vec<(String, usize)> = ["a", "bbb", "cc", "d", "eeeee"]
+            .iter()
+            .map(|name| (name.to_string(), name.len()))
+            .collect();
+        assert_eq!(columns(&cells, 80), vec!["a  bbb  cc  d  eeeee"]);
+        assert_eq!(columns(&cells, 12), vec!["a    d", "bbb  eeeee", "cc"]);
+        assert_eq!(columns(&cells, 1).len(), 5);
+    }
+
+    #[test]
+    fn test_colors() {
+        let colors = Colors::parse("di=01;34:ex=01;32:*.tar=01;31:fi=");
+        assert_eq!(colors.code("src", Kind::Directory), Some("01;34"));
+        assert_eq!(colors.code("X.TAR", Kind::File), Some("01;31"));
+        assert_eq!(colors.code("run.tar", Kind::Executable), Some("01;32"));
+        assert_eq!(colors.code("notes", Kind::File), None);
+        assert_eq!(colors.code("gone", Kind::BrokenSymlink), None);
+        assert_eq!(
+            colors.paint("src", "src", Kind::Directory),
+            "\x1b[01;34msrc\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("b.txt"), "12345").unwrap();
+        fs::write(path("A.txt"), "1").unwrap();
+        fs::write(path(".hidden"), "").unwrap();
+        fs::create_dir(path("sub")).unwrap();
+        fs::write(path("sub/inner"), "").unwrap();
+        let dir_arg = dir.path().to_str().unwrap();
+
+        assert_eq!(ls(&["-1", dir_arg]), (0, "A.txt\nb.txt\nsub\n".to_string()));
+        assert_eq!(ls(&["-1r", dir_arg]).1, "sub\nb.txt\nA.txt\n");
+        assert_eq!(ls(&["-1S", dir_arg]).1.lines().nth(1), Some("b.txt"));
+        assert_eq!(
+            ls(&["-1a", dir_arg]).1,
+            ".\n..\nA.txt\nb.txt\n.hidden\nsub\n"
+        );
+        assert_eq!(ls(&["-1A", dir_arg]).1.lines().count(), 4);
+
+        let recursive = ls(&["-1R", dir_arg]).1;
+        assert!(recursive.starts_with(&format!("{}:\nA.txt\n", dir_arg)));
+        assert!(recursive.ends_with(&format!("\n\n{}:\ninner\n", path("sub").display())));
+
+        let long = ls(&["-l", &path("b.txt").to_string_lossy()]).1;
+        assert!(long.starts_with("-rw"), "{}", long);
+        assert_eq!(long.split_whitespace().nth(4), Some("5"));
+
+        let (code, _) = ls(&[&path("missing").to_string_lossy()]);
+        assert_eq!(code, 2);
+        assert_eq!(ls(&["-Z"]).0, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink("real", dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("nowhere", dir.path().join("broken")).unwrap();
+
+        let entries = read_dir(dir.path(), &Options::default()).unwrap();
+        let kinds: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.kind()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("broken", Kind::BrokenSymlink),
+                ("link", Kind::Symlink),
+                ("real", Kind::Directory)
+            ]
+        );
+        let long = long_lines(&entries, &Options::default());
+        assert!(long[1].starts_with('l') && long[1].ends_with("link -> real"));
+    }
+}
@@ -1,39 +1,26 @@
 use colored::Colorize;
-use rm::rm;
 use rustyline::error::ReadlineError;
 use std::env;
 use std::fs;
-use std::io::{self};
-use winix::{echo, touch};
-
-mod cat;
-mod cd;
-#[cfg(windows)]
-mod chmod;
-#[cfg(windows)]
-mod chown;
-mod df;
-mod disown;
-mod free;
-mod git;
-mod input;
-#[cfg(windows)]
-mod kill;
-mod powershell;
-mod ps;
-mod rm;
-mod sensors;
-mod sudo;
-mod tui;
-mod uname;
-mod uptime;
+use std::io::{self, Write};
+use std::process;
+use winix::shell::{Console, Shell};
+use winix::{commands, git, history, input, prompt, tui};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.contains(&"--interactive".to_string()) {
         git::interactive_mode();
     }
-    let _ = rm(vec!["test.txt"]).expect("Failed to remove file");
+    // `winix -c COMMANDS [ARG]...`, with any arguments as `$1`, `$2` and so on
+    if args.len() > 2 && args[1] == "-c" {
+        let mut shell = Shell::new(Console::Terminal);
+        shell.set_positional(args[3..].to_vec());
+        process::exit(shell.run_script(&args[2]));
+    }
+    if args.len() > 1 && !args[1].starts_with('-') {
+        process::exit(run_script_file(&args[1], &args[2..]));
+    }
     if args.len() > 1 && args[1] == "--cli" {
         run_cli();
     } else {
@@ -45,17 +32,64 @@ fn main() {
     }
 }
 
+/// Runs a script such as `build.wx` with `args` as `$1`, `$2` and so on,
+/// returning the status to exit with.
+fn run_script_file(path: &str, args: &[String]) -> i32 {
+    match fs::read_to_string(path) {
+        Ok(script) => {
+            let mut shell = Shell::new(Console::Terminal);
+            shell.set_positional(args.to_vec());
+            shell.run_script(&script)
+        }
+        Err(e) => {
+            eprintln!("{}", format!("winix: {}: {}", path, e).red());
+            127
+        }
+    }
+}
+
 fn run_cli() {
+    let mut shell = Shell::new(Console::Terminal);
+    let _ = show_splash_screen(&mut io::stdout());
+    shell.source_rc();
+    if let Some(status) = shell.exit_status() {
+        process::exit(status);
+    }
+    // After the rc file, which may set HISTFILE
+    if let Err(e) = history::load() {
+        eprintln!("winix: could not read history: {}", e);
+    }
     let mut editor = input::LineEditor::new();
-    show_splash_screen();
 
     loop {
-        let readline = editor.read_line();
+        shell.report_finished_jobs();
+        editor.set_functions(shell.function_names().cloned());
+        let state = prompt::State {
+            status: shell.last_status(),
+            jobs: shell.job_count(),
+        };
+        let readline = editor.read_line(&state);
         match readline {
             Ok(line) => {
+                let line = match history::expand(&line) {
+                    Ok(Some(expanded)) => {
+                        println!("{}", expanded);
+                        expanded
+                    }
+                    Ok(None) => line,
+                    Err(e) => {
+                        eprintln!("winix: {}", e);
+                        editor.add_history_entry(line.as_str());
+                        continue;
+                    }
+                };
                 editor.add_history_entry(line.as_str());
 
-                if line.trim() == "exit" || line.trim() == "quit" {
+                shell.run_line(&line);
+                if let Err(e) = history::record(&line, shell.last_status()) {
+                    eprintln!("winix: could not save history: {}", e);
+                }
+                if shell.exit_status().is_some() {
                     println!("{}", "Goodbye!".bold().blue());
                     println!(
                         "{}{}",
@@ -64,8 +98,6 @@ fn run_cli() {
                     );
                     break;
                 }
-
-                handle_command(&line);
             }
             Err(ReadlineError::Interrupted) => {
                 println!("^C");
@@ -83,130 +115,9 @@ fn run_cli() {
     }
 }
 
-fn handle_command(line: &str) {
-    let parts: Vec<&str> = line.trim().split_whitespace().collect();
-    if parts.is_empty() {
-        return;
-    }
-
-    let command = parts[0].to_lowercase();
-    let args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
-
-    match command.as_str() {
-        "cd" => {
-            if args.is_empty() {
-                println!("{}", "Usage: cd <directory>".red());
-            } else if let Err(e) = cd_command(&args[0]) {
-                println!("{}", format!("cd: {}", e).red());
-            }
-        }
-
-        "pwd" => {
-            if let Err(e) = pwd_command() {
-                println!("{}", format!("pwd: {}", e).red());
-            }
-        }
-
-        "ls" => {
-            let dir = if args.is_empty() { "." } else { &args[0] };
-            if let Err(e) = ls_command(dir) {
-                println!("{}", format!("ls: {}", e).red());
-            }
-        }
-
-        "echo" => echo::run(&args),
-        "touch" => touch::run(&args),
-        "uname" => uname::execute(),
-        "ps" => ps::execute(),
-        "sensors" => sensors::execute(),
-        "free" => free::execute(),
-        "uptime" => uptime::execute(),
-        "df" => df::execute(),
-
-        #[cfg(windows)]
-        "kill" => {
-            if args.is_empty() {
-                println!("{}", "Usage: kill <pid|name> [options]".red());
-            } else if let Err(e) =
-                kill::execute(&args.iter().map(String::as_str).collect::<Vec<_>>())
-            {
-                println!("{}", format!("kill: {}", e).red());
-            }
-        }
-
-        #[cfg(windows)]
-        "chmod" => {
-            if args.is_empty() {
-                println!("{}", "Usage: chmod <mode> <file>...".red());
-            } else {
-                let mode = &args[0];
-                let files: Vec<&str> = args[1..].iter().map(String::as_str).collect();
-                if files.is_empty() {
-                    println!("{}", "Usage: chmod <mode> <file>...".red());
-                } else {
-                    // Call into library implementation for each file
-                    for f in files {
-                        let _ = chmod::execute(&[mode, f]);
-                    }
-                }
-            }
-        }
-        #[cfg(windows)]
-        "chown" => {
-            if args.is_empty() {
-                println!("{}", "Usage: chown <owner>[:group] <file>...".red());
-            } else {
-                let mode = &args[0];
-                let files: Vec<&str> = args[1..].iter().map(String::as_str).collect();
-                if files.is_empty() {
-                    println!("{}", "Usage: chown <owner>[:group] <file>...".red());
-                } else {
-                    chown::execute(
-                        &std::iter::once(mode.as_str())
-                            .chain(files.into_iter())
-                            .collect::<Vec<&str>>(),
-                    );
-                }
-            }
-        }
-
-        "rm" => {
-            if args.is_empty() {
-                println!("{}", "Usage: rm <file1> [file2] ...".red());
-            } else {
-                for file in &args {
-                    match fs::remove_file(file) {
-                        Ok(_) => println!("Deleted {}", file),
-                        Err(e) => eprintln!("Failed to delete {}: {}", file, e),
-                    }
-                }
-            }
-        }
-        "git" => {
-            let git_args = &["status"]; // Replace with real input
-            git::execute(git_args);
-        }
-        "psh" | "powershell" => {
-            if args.get(0).map(String::as_str) == Some("--interactive") {
-                powershell::interactive_mode();
-            } else {
-                powershell::execute(&args.iter().map(String::as_str).collect::<Vec<_>>());
-            }
-        }
-
-        "help" => {
-            show_splash_screen();
-        }
-
-        _ => {
-            println!("{}", format!("Unknown command: '{}'", command).red());
-            println!("{}", "Type 'help' for available commands".dimmed());
-        }
-    }
-}
-
-fn show_splash_screen() {
-    println!(
+fn show_splash_screen(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
         "{}",
         r#"██     ██ ██ ███    ██ ██ ██   ██
 ██     ██ ██ ████   ██ ██  ██ ██
@@ -214,72 +125,33 @@ fn show_splash_screen() {
 ██ ███ ██ ██ ██  ██ ██ ██  ██ ██
  ███ ███  ██ ██   ████ ██ ██   ██"#
             .bold()
-    );
+    )?;
 
-    println!(
+    writeln!(
+        out,
         "{}",
         "-----------Your Most Useful Linux Commands directly on Your Windows without WSL or a Linux Distro-------------"
             .bold()
             .blue()
-    );
-    println!();
-    println!(
+    )?;
+    writeln!(out)?;
+    writeln!(
+        out,
         "{}",
         "💡 RECOMMENDED: Launch the beautiful TUI interface with: winix --tui"
             .bold()
             .green()
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "{}",
         "   Experience all commands in a modern, responsive terminal interface!"
             .bold()
             .cyan()
-    );
-    println!();
-    println!("{}", "Available Commands:".bold().white());
-    println!(
-        "  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}\n  {}",
-        "cd".bold().yellow(),
-        "chmod".bold().yellow(),
-        "chown".bold().yellow(),
-        "df".bold().yellow(),
-        "exit".bold().red(),
-        "free".bold().yellow(),
-        "git".bold().yellow(),
-        "kill".bold().yellow(),
-        "ls".bold().yellow(),
-        "ps".bold().yellow(),
-        "psh/powershell".bold().cyan(),
-        "pwd".bold().yellow(),
-        "sensors".bold().yellow(),
-        "uptime".bold().yellow(),
-        "uname".bold().yellow(),
-    );
-    println!();
-}
-
-// Utility commands
-fn cd_command(path: &str) -> io::Result<()> {
-    env::set_current_dir(path)
-}
-
-fn pwd_command() -> io::Result<()> {
-    let cwd = env::current_dir()?;
-    println!("{}", cwd.display().to_string().bold().cyan());
-    Ok(())
-}
-
-fn ls_command(path: &str) -> io::Result<()> {
-    let entries = fs::read_dir(path)?;
-    for entry in entries {
-        let entry = entry?;
-        let file_name = entry.file_name();
-        let file_name = file_name.to_string_lossy();
-        if entry.file_type()?.is_dir() {
-            println!("{}", file_name.blue().bold());
-        } else {
-            println!("{}", file_name.white());
-        }
-    }
+    )?;
+    writeln!(out)?;
+    commands::write_help(out)?;
+    writeln!(out, "  {}", "exit".bold().red())?;
+    writeln!(out)?;
     Ok(())
 }
@@ -0,0 +1,263 @@
+//! `mkdir`, with `-p` for parents and `-m` for the new directory's mode.
+
+use crate::commands::{self, Command};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// A mode given to `-m`, either octal like `750` or symbolic like
+/// `u=rwx,go-w`. Symbolic modes change `a=rwx`, the mode `mkdir` would
+/// otherwise ask for.
+pub fn parse_mode(mode: &str) -> Option<u32> {
+    if mode.chars().all(|c| c.is_ascii_digit()) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|_| (1..=4).contains(&mode.len()));
+    }
+
+    let mut bits = 0o777;
+    for clause in mode.split(',') {
+        let op_at = clause.find(['+', '-', '='])?;
+        let (who, rest) = clause.split_at(op_at);
+        let (op, perms) = rest.split_at(1);
+        let mut mask = 0;
+        for c in who.chars() {
+            mask |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => return None,
+            };
+        }
+        if who.is_empty() {
+            mask = 0o777;
+        }
+        let mut set = 0;
+        for c in perms.chars() {
+            set |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' | 'X' => 0o111,
+                _ => return None,
+            };
+        }
+        match op {
+            "+" => bits |= set & mask,
+            "-" => bits &= !(set & mask),
+            _ => bits = (bits & !mask) | (set & mask),
+        }
+    }
+    Some(bits)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Windows has no modes; a directory without write permission for its
+/// owner is made read-only.
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+/// `mkdir [-pv] [-m MODE] DIRECTORY...`
+pub struct Mkdir;
+
+impl Mkdir {
+    /// Makes one directory, and with `parents` those above it that are
+    /// missing. Returns the message to report if it cannot.
+    fn make(
+        &self,
+        dir: &Path,
+        parents: bool,
+        mode: Option<u32>,
+        stdout: &mut dyn Write,
+        verbose: bool,
+    ) -> Result<(), String> {
+        let cannot_create = |path: &Path, e: io::Error| {
+            format!(
+                "cannot create directory '{}': {}",
+                path.display(),
+                commands::io_message(&e)
+            )
+        };
+        if parents {
+            if dir.is_dir() {
+                return Ok(());
+            }
+            // Outermost first, stopping at the first that exists
+            let missing: Vec<&Path> = dir
+                .ancestors()
+                .filter(|path| !path.as_os_str().is_empty())
+                .take_while(|path| fs::symlink_metadata(path).is_err())
+                .collect();
+            for path in missing.into_iter().rev() {
+                match fs::create_dir(path) {
+                    Ok(()) if verbose => {
+                        let _ = writeln!(stdout, "mkdir: created directory '{}'", path.display());
+                    }
+                    Ok(()) => {}
+                    // Made by someone else in the meantime
+                    Err(_) if path.is_dir() => {}
+                    Err(e) => return Err(cannot_create(path, e)),
+                }
+            }
+        } else {
+            fs::create_dir(dir).map_err(|e| cannot_create(dir, e))?;
+            if verbose {
+                let _ = writeln!(stdout, "mkdir: created directory '{}'", dir.display());
+            }
+        }
+        match mode {
+            Some(mode) => set_mode(dir, mode).map_err(|e| {
+                format!(
+                    "cannot set permissions of '{}': {}",
+                    dir.display(),
+                    commands::io_message(&e)
+                )
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Command for Mkdir {
+    fn name(&self) -> &'static str {
+        "mkdir"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mkdir [-pv] [-m MODE] DIRECTORY..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Create directories, with their parents if -p is given"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let (mut parents, mut verbose, mut mode) = (false, false, None);
+        let mut dirs = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    dirs.extend(args.by_ref());
+                    break;
+                }
+                "--parents" => parents = true,
+                "--verbose" => verbose = true,
+                "-m" | "--mode" => {
+                    let given = args.next().map(String::as_str).unwrap_or_default();
+                    match parse_mode(given) {
+                        Some(parsed) => mode = Some(parsed),
+                        None => {
+                            let message = format!("invalid mode '{}'", given);
+                            return commands::report_error(stderr, self.name(), message);
+                        }
+                    }
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                    for flag in flags[1..].chars() {
+                        match flag {
+                            'p' => parents = true,
+                            'v' => verbose = true,
+                            _ => return commands::usage_error(stderr, self.usage()),
+                        }
+                    }
+                }
+                _ if arg.starts_with("--") => return commands::usage_error(stderr, self.usage()),
+                _ => dirs.push(arg),
+            }
+        }
+        if dirs.is_empty() {
+            return commands::usage_error(stderr, self.usage());
+        }
+
+        let mut status = 0;
+        for dir in dirs {
+            if let Err(message) = self.make(Path::new(dir), parents, mode, stdout, verbose) {
+                status = commands::report_error(stderr, self.name(), message);
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mkdir(args: &[&str]) -> (i32, String, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        let code = Mkdir.run(&args, &mut io::empty(), &mut output, &mut errors);
+        (
+            code,
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("750"), Some(0o750));
+        assert_eq!(parse_mode("0700"), Some(0o700));
+        assert_eq!(parse_mode("u=rwx,go=rx"), Some(0o755));
+        assert_eq!(parse_mode("go-w"), Some(0o755));
+        assert_eq!(parse_mode("=r"), Some(0o444));
+        assert_eq!(parse_mode("8"), None);
+        assert_eq!(parse_mode("12345"), None);
+        assert_eq!(parse_mode("z+r"), None);
+    }
+
+    #[test]
+    fn test_mkdir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+
+        assert_eq!(mkdir(&[&path("one")]).0, 0);
+        let (code, _, errors) = mkdir(&[&path("one")]);
+        assert_eq!(code, 1);
+        assert!(errors.contains(&format!(
+            "cannot create directory '{}': File exists",
+            path("one")
+        )));
+        let (code, _, errors) = mkdir(&[&path("a/b")]);
+        assert_eq!(code, 1);
+        assert!(errors.contains("No such file or directory"));
+
+        let (code, output, _) = mkdir(&["-pv", &path("a/b")]);
+        assert_eq!(code, 0);
+        assert_eq!(
+            output,
+            format!(
+                "mkdir: created directory '{}'\nmkdir: created directory '{}'\n",
+                path("a"),
+                path("a/b")
+            )
+        );
+        assert_eq!(mkdir(&["-p", &path("a/b")]).0, 0);
+
+        assert_eq!(mkdir(&["-m", "700", &path("private")]).0, 0);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path("private")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        assert_eq!(mkdir(&["-m", "rwx", &path("bad")]).0, 1);
+        assert_eq!(mkdir(&[]).0, 2);
+    }
+}
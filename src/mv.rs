@@ -0,0 +1,219 @@
+//! `mv`, which renames files and directories, copying them when they are
+//! going to another filesystem.
+
+use crate::commands::{self, Command};
+use crate::cp;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Renames a file or directory, or copies it and removes the original when
+/// it is going to another filesystem, where renaming cannot take it.
+pub fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            cp::copy_all(from, to)?;
+            if fs::symlink_metadata(from)?.is_dir() {
+                fs::remove_dir_all(from)
+            } else {
+                fs::remove_file(from)
+            }
+        }
+        result => result,
+    }
+}
+
+/// `mv [-fnuv] SOURCE... DEST`
+pub struct Mv;
+
+impl Mv {
+    /// Moves one source, returning the message to report if it cannot be.
+    fn move_one(
+        &self,
+        source: &Path,
+        destination: &Path,
+        no_clobber: bool,
+        update: bool,
+    ) -> Result<bool, String> {
+        let metadata = fs::symlink_metadata(source).map_err(|e| {
+            format!(
+                "cannot access '{}': {}",
+                source.display(),
+                commands::io_message(&e)
+            )
+        })?;
+        if metadata.is_dir() && cp::is_within(destination, source) {
+            return Err(format!(
+                "cannot move '{}' to a subdirectory of itself, '{}'",
+                source.display(),
+                destination.display()
+            ));
+        }
+        if let Ok(existing) = fs::symlink_metadata(destination) {
+            if cp::same_file(source, destination) {
+                return Err(format!(
+                    "'{}' and '{}' are the same file",
+                    source.display(),
+                    destination.display()
+                ));
+            }
+            if cp::keep_existing(&existing, &metadata, no_clobber, update) {
+                return Ok(false);
+            }
+            match (existing.is_dir(), metadata.is_dir()) {
+                (true, false) => {
+                    return Err(format!(
+                        "cannot overwrite directory '{}' with non-directory",
+                        destination.display()
+                    ));
+                }
+                (false, true) => {
+                    return Err(format!(
+                        "cannot overwrite non-directory '{}' with directory '{}'",
+                        destination.display(),
+                        source.display()
+                    ));
+                }
+                _ => {}
+            }
+        }
+        move_path(source, destination).map_err(|e| {
+            format!(
+                "cannot move '{}' to '{}': {}",
+                source.display(),
+                destination.display(),
+                commands::io_message(&e)
+            )
+        })?;
+        Ok(true)
+    }
+}
+
+impl Command for Mv {
+    fn name(&self) -> &'static str {
+        "mv"
+    }
+
+    fn usage(&self) -> &'static str {
+        "mv [-fnuv] SOURCE... DEST"
+    }
+
+    fn description(&self) -> &'static str {
+        "Move or rename files and directories"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let (mut no_clobber, mut update, mut verbose) = (false, false, false);
+        let mut operands = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(args.cloned());
+                    break;
+                }
+                "--force" => no_clobber = false,
+                "--no-clobber" => no_clobber = true,
+                "--update" => update = true,
+                "--verbose" => verbose = true,
+                flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                    for flag in flags[1..].chars() {
+                        match flag {
+                            // Nothing is asked anyway, so -f only undoes -n
+                            'f' => no_clobber = false,
+                            'n' => no_clobber = true,
+                            'u' => update = true,
+                            'v' => verbose = true,
+                            _ => return commands::usage_error(stderr, self.usage()),
+                        }
+                    }
+                }
+                _ if arg.starts_with("--") => return commands::usage_error(stderr, self.usage()),
+                _ => operands.push(arg.clone()),
+            }
+        }
+        if operands.len() < 2 {
+            return commands::usage_error(stderr, self.usage());
+        }
+
+        let pairs = match cp::destinations(&operands) {
+            Ok(pairs) => pairs,
+            Err(message) => return commands::report_error(stderr, self.name(), message),
+        };
+        let mut status = 0;
+        for (source, destination) in pairs {
+            match self.move_one(&source, &destination, no_clobber, update) {
+                Ok(true) if verbose => {
+                    let _ = writeln!(
+                        stdout,
+                        "renamed '{}' -> '{}'",
+                        source.display(),
+                        destination.display()
+                    );
+                }
+                Ok(_) => {}
+                Err(message) => status = commands::report_error(stderr, self.name(), message),
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(args: &[&Path]) -> (i32, String, String) {
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        let code = Mv.run(&args, &mut io::empty(), &mut output, &mut errors);
+        (
+            code,
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("a"), "a").unwrap();
+        fs::write(path("b"), "b").unwrap();
+        fs::create_dir_all(path("d/sub")).unwrap();
+
+        let (code, output, _) = mv(&[Path::new("-v"), &path("a"), &path("c")]);
+        assert_eq!(code, 0);
+        assert_eq!(
+            output,
+            format!(
+                "renamed '{}' -> '{}'\n",
+                path("a").display(),
+                path("c").display()
+            )
+        );
+        assert_eq!(mv(&[Path::new("-n"), &path("c"), &path("b")]).0, 0);
+        assert_eq!(fs::read_to_string(path("b")).unwrap(), "b");
+
+        assert_eq!(mv(&[&path("b"), &path("c"), &path("d")]).0, 0);
+        assert!(path("d/b").exists() && path("d/c").exists() && !path("b").exists());
+
+        let (code, _, errors) = mv(&[&path("d"), &path("d/sub")]);
+        assert_eq!(code, 1);
+        assert!(errors.contains("to a subdirectory of itself"));
+        let (_, _, errors) = mv(&[&path("d/b"), &path("d/sub/..")]);
+        assert!(errors.contains("are the same file"));
+        let (_, _, errors) = mv(&[&path("gone"), &path("x")]);
+        assert!(errors.contains(&format!("cannot access '{}'", path("gone").display())));
+        assert_eq!(mv(&[&path("d")]).0, 2);
+    }
+}
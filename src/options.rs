@@ -0,0 +1,188 @@
+//! Shell options that change how command lines are expanded and run, with
+//! the `shopt` built-in to turn them on and off.
+
+use crate::commands::{self, Command};
+use std::io::{Read, Write};
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellOption {
+    /// A failing command ends the shell, as with `set -e`
+    ErrExit,
+    /// Patterns matching no files expand to nothing instead of themselves
+    NullGlob,
+    /// Patterns match file names regardless of case
+    NoCaseGlob,
+    /// The CLI edits lines with vi's keys
+    Vi,
+    /// The CLI edits lines with Emacs's keys, as it does unless `vi` is set
+    Emacs,
+}
+
+impl ShellOption {
+    pub const ALL: [ShellOption; 5] = [
+        ShellOption::Emacs,
+        ShellOption::ErrExit,
+        ShellOption::NoCaseGlob,
+        ShellOption::NullGlob,
+        ShellOption::Vi,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShellOption::ErrExit => "errexit",
+            ShellOption::NullGlob => "nullglob",
+            ShellOption::NoCaseGlob => "nocaseglob",
+            ShellOption::Vi => "vi",
+            ShellOption::Emacs => "emacs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|option| option.name() == name)
+    }
+
+    fn default_value(self) -> bool {
+        match self {
+            ShellOption::ErrExit | ShellOption::NullGlob | ShellOption::Vi => false,
+            // File names on Windows are case-insensitive
+            ShellOption::NoCaseGlob => cfg!(windows),
+            ShellOption::Emacs => true,
+        }
+    }
+}
+
+fn enabled() -> &'static Mutex<Vec<ShellOption>> {
+    static ENABLED: OnceLock<Mutex<Vec<ShellOption>>> = OnceLock::new();
+    ENABLED.get_or_init(|| {
+        let defaults = ShellOption::ALL
+            .into_iter()
+            .filter(|option| option.default_value())
+            .collect();
+        Mutex::new(defaults)
+    })
+}
+
+pub fn is_set(option: ShellOption) -> bool {
+    enabled()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(&option)
+}
+
+/// Turns an option on or off. Exactly one of `vi` and `emacs` is always on,
+/// so changing either changes the other too.
+pub fn set(option: ShellOption, on: bool) {
+    let mut enabled = enabled().lock().unwrap_or_else(|e| e.into_inner());
+    let mut change = |option: ShellOption, on: bool| {
+        enabled.retain(|&enabled| enabled != option);
+        if on {
+            enabled.push(option);
+        }
+    };
+    change(option, on);
+    match option {
+        ShellOption::Vi => change(ShellOption::Emacs, !on),
+        ShellOption::Emacs => change(ShellOption::Vi, !on),
+        _ => {}
+    }
+}
+
+/// Which options are on now, to put back with [`restore`].
+#[derive(Debug)]
+pub struct Saved(Vec<ShellOption>);
+
+pub fn save() -> Saved {
+    Saved(enabled().lock().unwrap_or_else(|e| e.into_inner()).clone())
+}
+
+pub fn restore(saved: Saved) {
+    *enabled().lock().unwrap_or_else(|e| e.into_inner()) = saved.0;
+}
+
+/// `shopt [-s|-u] [OPTION]...`
+pub struct Shopt;
+
+impl Command for Shopt {
+    fn name(&self) -> &'static str {
+        "shopt"
+    }
+
+    fn usage(&self) -> &'static str {
+        "shopt [-s|-u] [OPTION]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Set (-s) or unset (-u) shell options such as nullglob"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let (change, names) = match args.first().map(String::as_str) {
+            Some("-s") => (Some(true), &args[1..]),
+            Some("-u") => (Some(false), &args[1..]),
+            Some(flag) if flag.starts_with('-') => {
+                return commands::usage_error(stderr, self.usage());
+            }
+            _ => (None, args),
+        };
+
+        let mut options = Vec::new();
+        for name in names {
+            match ShellOption::from_name(name) {
+                Some(option) => options.push(option),
+                None => {
+                    return commands::report_error(
+                        stderr,
+                        self.name(),
+                        format!("{}: invalid shell option name", name),
+                    );
+                }
+            }
+        }
+
+        if let Some(on) = change {
+            options.iter().for_each(|&option| set(option, on));
+            return 0;
+        }
+        // Querying named options fails if any of them is off
+        let querying = !options.is_empty();
+        if !querying {
+            options.extend(ShellOption::ALL);
+        }
+        let mut status = 0;
+        for option in options {
+            let on = is_set(option);
+            let state = if on { "on" } else { "off" };
+            if let Err(e) = writeln!(stdout, "{:<15} {}", option.name(), state) {
+                return commands::report_error(stderr, self.name(), e);
+            }
+            if querying && !on {
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editing_modes() {
+        set(ShellOption::Vi, true);
+        assert!(is_set(ShellOption::Vi) && !is_set(ShellOption::Emacs));
+        set(ShellOption::Vi, false);
+        assert!(!is_set(ShellOption::Vi) && is_set(ShellOption::Emacs));
+        set(ShellOption::Emacs, false);
+        assert!(is_set(ShellOption::Vi));
+        set(ShellOption::Emacs, true);
+        assert!(!is_set(ShellOption::Vi));
+    }
+}
@@ -0,0 +1,1974 @@
+use std::fmt;
+use std::ops::Range;
+
+/// A single piece of a word, remembering how it was quoted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    /// Unquoted text
+    Literal(String),
+    /// Text inside '...'
+    SingleQuoted(String),
+    /// The text and parameters inside "..."
+    DoubleQuoted(Vec<WordPart>),
+    /// A character escaped with a backslash outside of quotes
+    Escaped(char),
+    /// A parameter reference such as `$HOME` or `${NAME:-default}`
+    Parameter(Parameter),
+    /// An unquoted `~` starting a word, or the value of an assignment, that
+    /// stands alone or comes before a `/`, which expands to `$HOME`
+    Tilde,
+    /// `$(COMMANDS)`, which expands to the output of the commands
+    CommandSubstitution(String),
+    /// `<(COMMANDS)`, which expands to the path of a file holding the output
+    /// of the commands
+    ProcessSubstitution(String),
+}
+
+/// Supplies the values that the parameters and substitutions in a word
+/// expand to. Any `Fn(&str) -> Option<String>` can act as one, looking up
+/// parameters by name, with substitutions expanding to nothing.
+pub trait Expand {
+    /// The value of a parameter, or `None` if it is unset.
+    fn parameter(&self, name: &str) -> Option<String>;
+
+    /// The output of the commands in a `$(...)`, without trailing newlines.
+    fn command_output(&self, _source: &str) -> String {
+        String::new()
+    }
+
+    /// A path to a file holding the output of the commands in a `<(...)`.
+    fn output_file(&self, _source: &str) -> String {
+        String::new()
+    }
+}
+
+impl<F: Fn(&str) -> Option<String>> Expand for F {
+    fn parameter(&self, name: &str) -> Option<String> {
+        self(name)
+    }
+}
+
+/// A parameter reference: `$NAME`, `${NAME}`, `${NAME:-default}` or `$?`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    /// What `${NAME:-default}` expands to when the parameter is unset or empty
+    pub default: Option<Vec<WordPart>>,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.default {
+            Some(default) => write!(f, "${{{}:-{}}}", self.name, parts_text(default)),
+            None => write!(f, "${}", self.name),
+        }
+    }
+}
+
+/// A shell word such as `"hello world"` or `C:\Users\me`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+    pub span: Range<usize>,
+}
+
+impl Word {
+    /// A word standing for exactly `text`, such as the result of expanding another word.
+    pub fn quoted(text: String) -> Self {
+        Self {
+            parts: vec![WordPart::SingleQuoted(text)],
+            span: 0..0,
+        }
+    }
+
+    /// The reserved word this is, such as `if` or `done`, if it is one and
+    /// is not quoted.
+    pub fn reserved_word(&self) -> Option<&'static str> {
+        match &self.parts[..] {
+            [WordPart::Literal(text)] => RESERVED_WORDS.into_iter().find(|k| k == text),
+            _ => None,
+        }
+    }
+
+    /// The name and value of the word if it is a `NAME=value` assignment,
+    /// which needs the name and the `=` to be written unquoted.
+    pub fn assignment(&self) -> Option<(&str, Word)> {
+        let Some(WordPart::Literal(text)) = self.parts.first() else {
+            return None;
+        };
+        let (name, value) = text.split_once('=').filter(|(name, _)| is_name(name))?;
+        let mut parts = vec![WordPart::Literal(value.to_string())];
+        parts.extend(self.parts[1..].iter().cloned());
+        let value = Word {
+            parts: mark_tilde(parts),
+            span: self.span.clone(),
+        };
+        Some((name, value))
+    }
+
+    /// The word with all quoting removed and parameters left as written.
+    pub fn text(&self) -> String {
+        parts_text(&self.parts)
+    }
+
+    /// The word with all quoting removed and each parameter and substitution
+    /// replaced by its value from `env`, with unset parameters expanding to
+    /// nothing.
+    pub fn expand(&self, env: &dyn Expand) -> String {
+        self.expand_chars(env).into_iter().map(|(c, _)| c).collect()
+    }
+
+    /// Like [`Word::expand`], but pairs each character with whether it was
+    /// quoted, since only unquoted characters take part in globbing.
+    pub fn expand_chars(&self, env: &dyn Expand) -> Vec<(char, bool)> {
+        let mut chars = Vec::new();
+        expand_parts(&self.parts, env, false, &mut chars);
+        chars
+    }
+}
+
+fn parts_text(parts: &[WordPart]) -> String {
+    let mut text = String::new();
+    for part in parts {
+        match part {
+            WordPart::Literal(s) | WordPart::SingleQuoted(s) => text.push_str(s),
+            WordPart::DoubleQuoted(parts) => text.push_str(&parts_text(parts)),
+            WordPart::Escaped(c) => text.push(*c),
+            WordPart::Parameter(parameter) => text.push_str(&parameter.to_string()),
+            WordPart::Tilde => text.push('~'),
+            WordPart::CommandSubstitution(source) => text.push_str(&format!("$({})", source)),
+            WordPart::ProcessSubstitution(source) => text.push_str(&format!("<({})", source)),
+        }
+    }
+    text
+}
+
+fn expand_parts(parts: &[WordPart], env: &dyn Expand, quoted: bool, chars: &mut Vec<(char, bool)>) {
+    fn push(chars: &mut Vec<(char, bool)>, text: &str, quoted: bool) {
+        chars.extend(text.chars().map(|c| (c, quoted)));
+    }
+
+    for part in parts {
+        match part {
+            WordPart::Literal(s) => push(chars, s, quoted),
+            WordPart::SingleQuoted(s) => push(chars, s, true),
+            WordPart::DoubleQuoted(parts) => expand_parts(parts, env, true, chars),
+            WordPart::Escaped(c) => chars.push((*c, true)),
+            WordPart::Parameter(parameter) => {
+                match (env.parameter(&parameter.name), &parameter.default) {
+                    (Some(value), _) if !value.is_empty() => push(chars, &value, quoted),
+                    (_, Some(default)) => expand_parts(default, env, quoted, chars),
+                    (value, None) => push(chars, &value.unwrap_or_default(), quoted),
+                }
+            }
+            // The home directory is not globbed or split, and a `~` is left as
+            // it is when `HOME` is unset
+            WordPart::Tilde => match env.parameter("HOME") {
+                Some(home) => push(chars, &home, true),
+                None => push(chars, "~", quoted),
+            },
+            WordPart::CommandSubstitution(source) => {
+                push(chars, &env.command_output(source), quoted)
+            }
+            // The path is a single argument however it was written
+            WordPart::ProcessSubstitution(source) => push(chars, &env.output_file(source), true),
+        }
+    }
+}
+
+/// The redirection operators `<`, `>`, `>>`, `<<`, `<<<` and `>&`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectOp {
+    Input,
+    Output,
+    Append,
+    /// `<<` or `<<-`, whose target is the text of the here-document
+    HereDoc,
+    HereString,
+    DupOutput,
+}
+
+impl RedirectOp {
+    /// The file descriptor redirected when no number is written before the operator.
+    pub fn default_fd(self) -> u32 {
+        match self {
+            RedirectOp::Input | RedirectOp::HereDoc | RedirectOp::HereString => 0,
+            RedirectOp::Output | RedirectOp::Append | RedirectOp::DupOutput => 1,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RedirectOp::Input => "<",
+            RedirectOp::Output => ">",
+            RedirectOp::Append => ">>",
+            RedirectOp::HereDoc => "<<",
+            RedirectOp::HereString => "<<<",
+            RedirectOp::DupOutput => ">&",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Word(Word),
+    Pipe,
+    AndIf,
+    OrIf,
+    Semi,
+    /// `;;`, ending an arm of a `case`
+    DoubleSemi,
+    Amp,
+    LParen,
+    RParen,
+    Newline,
+    /// A redirection operator, with the file descriptor written before it (as in `2>`)
+    Redirect(Option<u32>, RedirectOp),
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Word(word) => word.text(),
+            TokenKind::Pipe => "|".to_string(),
+            TokenKind::AndIf => "&&".to_string(),
+            TokenKind::OrIf => "||".to_string(),
+            TokenKind::Semi => ";".to_string(),
+            TokenKind::DoubleSemi => ";;".to_string(),
+            TokenKind::Amp => "&".to_string(),
+            TokenKind::LParen => "(".to_string(),
+            TokenKind::RParen => ")".to_string(),
+            TokenKind::Newline => "newline".to_string(),
+            TokenKind::Redirect(Some(fd), op) => format!("{}{}", fd, op.as_str()),
+            TokenKind::Redirect(None, op) => op.as_str().to_string(),
+        }
+    }
+}
+
+/// A token together with its byte range in the input line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnterminatedQuote(char),
+    /// A `$(` or `<(` without its closing `)`
+    UnterminatedSubstitution,
+    /// A here-document without the line ending it, which holds the delimiter
+    UnterminatedHereDoc(String),
+    BadSubstitution,
+    UnexpectedToken(String),
+    UnexpectedEnd(String),
+}
+
+/// A syntax error and the byte offset in the line where it was detected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub pos: usize,
+}
+
+impl ParseError {
+    /// The 1-based column of the error within its line of `input`.
+    pub fn column(&self, input: &str) -> usize {
+        let pos = self.pos.min(input.len());
+        input[self.line_start(input)..pos].chars().count() + 1
+    }
+
+    /// Renders the error followed by the offending line and a `^` marker under the column.
+    pub fn render(&self, input: &str) -> String {
+        let start = self.line_start(input);
+        let line = input[start..].lines().next().unwrap_or_default();
+        format!(
+            "winix: syntax error at column {}: {}\n  {}\n  {}^",
+            self.column(input),
+            self,
+            line,
+            " ".repeat(self.column(input) - 1)
+        )
+    }
+
+    /// Whether more input could complete the command, as with an open quote
+    /// or an `if` still waiting for its `fi`.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self.kind,
+            ParseErrorKind::UnterminatedQuote(_)
+                | ParseErrorKind::UnterminatedSubstitution
+                | ParseErrorKind::UnterminatedHereDoc(_)
+                | ParseErrorKind::UnexpectedEnd(_)
+        )
+    }
+
+    fn line_start(&self, input: &str) -> usize {
+        let pos = self.pos.min(input.len());
+        input[..pos].rfind('\n').map_or(0, |i| i + 1)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnterminatedQuote('\'') => write!(f, "unterminated single quote"),
+            ParseErrorKind::UnterminatedQuote(_) => write!(f, "unterminated double quote"),
+            ParseErrorKind::UnterminatedSubstitution => {
+                write!(f, "unterminated command substitution")
+            }
+            ParseErrorKind::UnterminatedHereDoc(delimiter) => {
+                write!(f, "here-document not ended by '{}'", delimiter)
+            }
+            ParseErrorKind::BadSubstitution => write!(f, "bad substitution"),
+            ParseErrorKind::UnexpectedToken(token) => write!(f, "unexpected token '{}'", token),
+            ParseErrorKind::UnexpectedEnd(after) => {
+                write!(f, "unexpected end of input after '{}'", after)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How two pipelines in an and-or list are joined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+/// A redirection such as `2>&1` or `> out.txt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub fd: u32,
+    pub op: RedirectOp,
+    pub target: Word,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleCommand {
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+impl SimpleCommand {
+    /// The command name followed by its arguments, with quoting removed.
+    pub fn argv(&self) -> Vec<String> {
+        self.words.iter().map(Word::text).collect()
+    }
+}
+
+/// One arm of a `case`: `PATTERN | PATTERN) LIST ;;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseArm {
+    pub patterns: Vec<Word>,
+    pub body: CommandLine,
+}
+
+/// A command in a pipeline, either simple or built from other commands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// `if LIST; then LIST; [elif LIST; then LIST;]... [else LIST;] fi`
+    If {
+        branches: Vec<(CommandLine, CommandLine)>,
+        otherwise: Option<CommandLine>,
+    },
+    /// `for NAME [in WORD...]; do LIST; done`, looping over the positional
+    /// parameters when there is no `in`
+    For {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: CommandLine,
+    },
+    /// `while LIST; do LIST; done`, or `until` when `until` is set
+    While {
+        condition: CommandLine,
+        body: CommandLine,
+        until: bool,
+    },
+    /// `case WORD in ARM... esac`
+    Case {
+        word: Word,
+        arms: Vec<CaseArm>,
+    },
+    /// `{ LIST; }`
+    Group(CommandLine),
+    /// `NAME() COMMAND` or `function NAME COMMAND`
+    Function {
+        name: String,
+        body: Box<Command>,
+        /// The definition as it was written, for defining it again in
+        /// background jobs
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    /// The list as it was written, for showing background jobs
+    pub text: String,
+}
+
+/// An and-or list terminated by `;`, `&`, a newline or the end of the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub and_or: AndOrList,
+    pub background: bool,
+}
+
+/// A fully parsed command line, or the body of a compound command.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandLine {
+    pub items: Vec<ListItem>,
+}
+
+/// Characters that end an unquoted word.
+fn is_metachar(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')' | '\'' | '"')
+}
+
+/// Outside of quotes a backslash only escapes characters that would otherwise
+/// mean something to the shell, so Windows paths like `C:\Users` survive as typed.
+pub fn is_escapable(c: char) -> bool {
+    is_metachar(c) || matches!(c, '\\' | '$' | '`' | '*' | '?' | '[' | ']' | '#' | '~')
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Parses the parameter reference starting with the `$` at byte `dollar`,
+/// returning it and the byte offset just past it. Returns `None` when the `$`
+/// does not start a reference and so stands for itself.
+fn parse_parameter(line: &str, dollar: usize) -> Result<Option<(Parameter, usize)>, ParseError> {
+    let start = dollar + 1;
+    let rest = &line[start..];
+    let bad_substitution = ParseError {
+        kind: ParseErrorKind::BadSubstitution,
+        pos: dollar,
+    };
+    // Positional parameters past `$9` need braces, as in `${10}`
+    let name_len = |text: &str, braced: bool| match text.chars().next() {
+        Some('?' | '#' | '@' | '*' | '!') => 1,
+        Some(c) if c.is_ascii_digit() && !braced => 1,
+        Some(c) if c.is_ascii_digit() => text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len()),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            text.find(|c| !is_name_char(c)).unwrap_or(text.len())
+        }
+        _ => 0,
+    };
+
+    if !rest.starts_with('{') {
+        let len = name_len(rest, false);
+        if len == 0 {
+            return Ok(None);
+        }
+        let parameter = Parameter {
+            name: rest[..len].to_string(),
+            default: None,
+        };
+        return Ok(Some((parameter, start + len)));
+    }
+
+    let len = name_len(&rest[1..], true);
+    if len == 0 {
+        return Err(bad_substitution);
+    }
+    let name = rest[1..1 + len].to_string();
+    let after_name = start + 1 + len;
+    if line[after_name..].starts_with('}') {
+        return Ok(Some((
+            Parameter {
+                name,
+                default: None,
+            },
+            after_name + 1,
+        )));
+    }
+    if !line[after_name..].starts_with(":-") {
+        return Err(bad_substitution);
+    }
+
+    // The default is read like the inside of double quotes, up to the closing brace
+    let mut default = Vec::new();
+    let mut literal = String::new();
+    let mut chars = line[after_name + 2..].char_indices().peekable();
+    let offset = after_name + 2;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '}' => {
+                if !literal.is_empty() {
+                    default.push(WordPart::Literal(literal));
+                }
+                return Ok(Some((
+                    Parameter {
+                        name,
+                        default: Some(default),
+                    },
+                    offset + i + 1,
+                )));
+            }
+            '$' => {
+                if let Some((parameter, end)) = parse_parameter(line, offset + i)? {
+                    if !literal.is_empty() {
+                        default.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    default.push(WordPart::Parameter(parameter));
+                    while chars.next_if(|&(j, _)| offset + j < end).is_some() {}
+                } else {
+                    literal.push(c);
+                }
+            }
+            '\\' => match chars.next() {
+                Some((_, next)) => literal.push(next),
+                None => literal.push(c),
+            },
+            '\'' | '"' => {
+                let mut closed = false;
+                for (_, q) in chars.by_ref() {
+                    if q == c {
+                        closed = true;
+                        break;
+                    }
+                    literal.push(q);
+                }
+                if !closed {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::UnterminatedQuote(c),
+                        pos: offset + i,
+                    });
+                }
+            }
+            c => literal.push(c),
+        }
+    }
+    Err(bad_substitution)
+}
+
+/// Reads the commands of a `$(...)` or `<(...)` whose `(` is at byte
+/// `open`, returning them and the byte offset just past the closing `)`.
+/// The commands are parsed here so that their syntax errors show up with
+/// the rest of the line's.
+fn parse_substitution(line: &str, open: usize) -> Result<(String, usize), ParseError> {
+    let start = open + 1;
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = line[start..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') if depth > 0 => depth -= 1,
+            (None, ')') => {
+                let source = &line[start..start + i];
+                if let Err(mut e) = parse(source) {
+                    // Only the closing `)` can end the commands early
+                    if !e.is_incomplete() {
+                        e.pos += start;
+                        return Err(e);
+                    }
+                    e.kind = ParseErrorKind::UnexpectedToken(")".to_string());
+                    e.pos = start + i;
+                    return Err(e);
+                }
+                return Ok((source.to_string(), start + i + 1));
+            }
+            _ => {}
+        }
+    }
+    Err(ParseError {
+        kind: ParseErrorKind::UnterminatedSubstitution,
+        pos: open - 1,
+    })
+}
+
+/// What a stretch of a line is beyond the tokens it belongs to, for syntax
+/// highlighting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionKind {
+    Comment,
+    /// A string in '...' or "...", including the quotes
+    Quoted,
+    Parameter,
+    /// A `$(...)` or `<(...)`
+    Substitution,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub span: Range<usize>,
+}
+
+/// Splits a command line into words and operators.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    tokenize_into(line, &mut tokens, &mut Vec::new())?;
+    Ok(tokens)
+}
+
+/// Tokenizes as much of a line as possible, for showing it while it is
+/// still being typed. Along with the tokens come the comments, strings,
+/// parameters and substitutions found, and the error that stopped the
+/// tokenizer, if any.
+pub fn scan(line: &str) -> (Vec<Token>, Vec<Region>, Option<ParseError>) {
+    let mut tokens = Vec::new();
+    let mut regions = Vec::new();
+    let error = tokenize_into(line, &mut tokens, &mut regions).err();
+    (tokens, regions, error)
+}
+
+fn tokenize_into(
+    line: &str,
+    tokens: &mut Vec<Token>,
+    regions: &mut Vec<Region>,
+) -> Result<(), ParseError> {
+    let mut chars = line.char_indices().peekable();
+    // A number written right before `<` or `>`, like the 2 in `2>err.log`
+    let mut io_number: Option<(usize, u32)> = None;
+    // Set after `<<` or `<<-` until their delimiter, to whether tabs are
+    // stripped from the here-document
+    let mut delimiter_next: Option<bool> = None;
+    // The delimiters of here-documents whose text starts on the next line,
+    // by token index
+    let mut here_docs: Vec<(usize, bool)> = Vec::new();
+
+    while let Some(&(mut start, c)) = chars.peek() {
+        if c == '\n' {
+            chars.next();
+            tokens.push(Token {
+                kind: TokenKind::Newline,
+                span: start..start + 1,
+            });
+            // The texts of here-documents follow one another
+            let mut body_start = start + 1;
+            for (index, strip_tabs) in here_docs.drain(..) {
+                let (word, end) = read_here_doc(line, body_start, &tokens[index], strip_tabs)?;
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+                regions.push(Region {
+                    kind: RegionKind::Quoted,
+                    span: body_start..end,
+                });
+                tokens[index].kind = TokenKind::Word(word);
+                body_start = end;
+            }
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        // A `#` starting a word comments out the rest of the line
+        if c == '#' {
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            regions.push(Region {
+                kind: RegionKind::Comment,
+                span: start..chars.peek().map_or(line.len(), |&(i, _)| i),
+            });
+            continue;
+        }
+
+        let operator = match c {
+            '|' | '&' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, next)) if next == c => {
+                        chars.next();
+                        Some(if c == '|' {
+                            TokenKind::OrIf
+                        } else {
+                            TokenKind::AndIf
+                        })
+                    }
+                    _ => Some(if c == '|' {
+                        TokenKind::Pipe
+                    } else {
+                        TokenKind::Amp
+                    }),
+                }
+            }
+            ';' => {
+                chars.next();
+                Some(match chars.next_if(|&(_, c)| c == ';') {
+                    Some(_) => TokenKind::DoubleSemi,
+                    None => TokenKind::Semi,
+                })
+            }
+            '(' | ')' => {
+                chars.next();
+                Some(if c == '(' {
+                    TokenKind::LParen
+                } else {
+                    TokenKind::RParen
+                })
+            }
+            '>' | '<' if !line[start..].starts_with("<(") => {
+                chars.next();
+                let fd = io_number.take().map(|(number_start, fd)| {
+                    start = number_start;
+                    fd
+                });
+                let op = if c == '>' {
+                    match chars.peek() {
+                        Some(&(_, '>')) => {
+                            chars.next();
+                            RedirectOp::Append
+                        }
+                        Some(&(_, '&')) => {
+                            chars.next();
+                            RedirectOp::DupOutput
+                        }
+                        _ => RedirectOp::Output,
+                    }
+                } else if line[start..]
+                    .trim_start_matches(char::is_numeric)
+                    .starts_with("<<<")
+                {
+                    chars.next();
+                    chars.next();
+                    RedirectOp::HereString
+                } else if chars.next_if(|&(_, c)| c == '<').is_some() {
+                    delimiter_next = Some(chars.next_if(|&(_, c)| c == '-').is_some());
+                    RedirectOp::HereDoc
+                } else {
+                    RedirectOp::Input
+                };
+                Some(TokenKind::Redirect(fd, op))
+            }
+            _ => None,
+        };
+
+        if let Some(kind) = operator {
+            let end = chars.peek().map_or(line.len(), |&(i, _)| i);
+            tokens.push(Token {
+                kind,
+                span: start..end,
+            });
+            continue;
+        }
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+
+        while let Some(&(pos, c)) = chars.peek() {
+            match c {
+                '\'' | '"' => {
+                    chars.next();
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    let mut quoted = String::new();
+                    let mut quoted_parts = Vec::new();
+                    let mut closed = false;
+                    while let Some((i, q)) = chars.next() {
+                        if q == c {
+                            closed = true;
+                            regions.push(Region {
+                                kind: RegionKind::Quoted,
+                                span: pos..i + 1,
+                            });
+                            break;
+                        }
+                        if c == '"' && q == '$' && line[i + 1..].starts_with('(') {
+                            let (source, end) = parse_substitution(line, i + 1)?;
+                            while chars.next_if(|&(j, _)| j < end).is_some() {}
+                            regions.push(Region {
+                                kind: RegionKind::Substitution,
+                                span: i..end,
+                            });
+                            if !quoted.is_empty() {
+                                quoted_parts.push(WordPart::Literal(std::mem::take(&mut quoted)));
+                            }
+                            quoted_parts.push(WordPart::CommandSubstitution(source));
+                            continue;
+                        }
+                        if c == '"'
+                            && q == '$'
+                            && let Some((parameter, end)) = parse_parameter(line, i)?
+                        {
+                            while chars.next_if(|&(j, _)| j < end).is_some() {}
+                            regions.push(Region {
+                                kind: RegionKind::Parameter,
+                                span: i..end,
+                            });
+                            if !quoted.is_empty() {
+                                quoted_parts.push(WordPart::Literal(std::mem::take(&mut quoted)));
+                            }
+                            quoted_parts.push(WordPart::Parameter(parameter));
+                            continue;
+                        }
+                        if c == '"'
+                            && q == '\\'
+                            && let Some(&(_, next)) = chars.peek()
+                            && matches!(next, '"' | '\\' | '$' | '`')
+                        {
+                            chars.next();
+                            quoted.push(next);
+                            continue;
+                        }
+                        quoted.push(q);
+                    }
+                    if !closed {
+                        return Err(ParseError {
+                            kind: ParseErrorKind::UnterminatedQuote(c),
+                            pos,
+                        });
+                    }
+                    parts.push(if c == '\'' {
+                        WordPart::SingleQuoted(quoted)
+                    } else {
+                        if !quoted.is_empty() {
+                            quoted_parts.push(WordPart::Literal(quoted));
+                        }
+                        WordPart::DoubleQuoted(quoted_parts)
+                    });
+                }
+                '\\' => {
+                    chars.next();
+                    match chars.peek() {
+                        Some(&(_, next)) if is_escapable(next) => {
+                            chars.next();
+                            if !literal.is_empty() {
+                                parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                            }
+                            parts.push(WordPart::Escaped(next));
+                        }
+                        _ => literal.push('\\'),
+                    }
+                }
+                '$' | '<' if line[pos + 1..].starts_with('(') => {
+                    let (source, end) = parse_substitution(line, pos + 1)?;
+                    while chars.next_if(|&(j, _)| j < end).is_some() {}
+                    regions.push(Region {
+                        kind: RegionKind::Substitution,
+                        span: pos..end,
+                    });
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(if c == '$' {
+                        WordPart::CommandSubstitution(source)
+                    } else {
+                        WordPart::ProcessSubstitution(source)
+                    });
+                }
+                '$' => match parse_parameter(line, pos)? {
+                    Some((parameter, end)) => {
+                        while chars.next_if(|&(j, _)| j < end).is_some() {}
+                        regions.push(Region {
+                            kind: RegionKind::Parameter,
+                            span: pos..end,
+                        });
+                        if !literal.is_empty() {
+                            parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                        }
+                        parts.push(WordPart::Parameter(parameter));
+                    }
+                    None => {
+                        chars.next();
+                        literal.push('$');
+                    }
+                },
+                c if is_metachar(c) => break,
+                c => {
+                    chars.next();
+                    literal.push(c);
+                }
+            }
+        }
+
+        let end = chars.peek().map_or(line.len(), |&(i, _)| i);
+        if parts.is_empty()
+            && matches!(chars.peek(), Some(&(_, '<' | '>')))
+            && let Ok(fd) = literal.parse()
+        {
+            io_number = Some((start, fd));
+            continue;
+        }
+        if !literal.is_empty() {
+            parts.push(WordPart::Literal(literal));
+        }
+        let parts = mark_tilde(parts);
+        if let Some(strip_tabs) = delimiter_next.take() {
+            here_docs.push((tokens.len(), strip_tabs));
+        }
+        tokens.push(Token {
+            kind: TokenKind::Word(Word {
+                parts,
+                span: start..end,
+            }),
+            span: start..end,
+        });
+    }
+
+    match here_docs.first() {
+        Some(&(index, _)) => Err(unterminated_here_doc(&tokens[index])),
+        None => Ok(()),
+    }
+}
+
+/// Turns a leading `~` that is the whole of `parts` or is followed by a `/`
+/// into a [`WordPart::Tilde`]. Other users' homes, as in `~name`, are not
+/// looked up, so those words are left as they are.
+fn mark_tilde(mut parts: Vec<WordPart>) -> Vec<WordPart> {
+    let rest = match parts.first() {
+        Some(WordPart::Literal(text)) if text == "~" && parts.len() == 1 => String::new(),
+        Some(WordPart::Literal(text)) if text.starts_with("~/") => text[1..].to_string(),
+        _ => return parts,
+    };
+    if rest.is_empty() {
+        parts[0] = WordPart::Tilde;
+    } else {
+        parts[0] = WordPart::Literal(rest);
+        parts.insert(0, WordPart::Tilde);
+    }
+    parts
+}
+
+fn unterminated_here_doc(delimiter: &Token) -> ParseError {
+    let delimiter_text = match &delimiter.kind {
+        TokenKind::Word(word) => word.text(),
+        kind => kind.describe(),
+    };
+    ParseError {
+        kind: ParseErrorKind::UnterminatedHereDoc(delimiter_text),
+        pos: delimiter.span.start,
+    }
+}
+
+/// Reads the text of a here-document, which starts at byte `start` of the
+/// line and runs up to a line holding just the delimiter. Returns the text
+/// as a word, and the byte just past the delimiter's line.
+///
+/// With `<<-`, tabs at the start of each line are removed. Unless the
+/// delimiter was quoted, parameters and substitutions in the text are
+/// expanded, as they are between double quotes.
+fn read_here_doc(
+    line: &str,
+    start: usize,
+    delimiter: &Token,
+    strip_tabs: bool,
+) -> Result<(Word, usize), ParseError> {
+    let TokenKind::Word(word) = &delimiter.kind else {
+        return Err(unterminated_here_doc(delimiter));
+    };
+    let delimiter_text = word.text();
+    let quoted = word
+        .parts
+        .iter()
+        .any(|part| !matches!(part, WordPart::Literal(_)));
+
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut pos = start;
+    while pos < line.len() {
+        let end = line[pos..].find('\n').map_or(line.len(), |i| pos + i);
+        let mut text_start = pos;
+        if strip_tabs {
+            text_start = end - line[pos..end].trim_start_matches('\t').len();
+        }
+        let next = (end + 1).min(line.len());
+        if line[text_start..end] == delimiter_text {
+            let parts = if quoted {
+                vec![WordPart::SingleQuoted(literal)]
+            } else {
+                if !literal.is_empty() {
+                    parts.push(WordPart::Literal(literal));
+                }
+                vec![WordPart::DoubleQuoted(parts)]
+            };
+            let span = delimiter.span.clone();
+            return Ok((Word { parts, span }, next));
+        }
+
+        let mut chars = line[text_start..end].char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let i = text_start + i;
+            if quoted {
+                literal.push(c);
+                continue;
+            }
+            let part = match c {
+                '\\' => match chars.next_if(|&(_, next)| matches!(next, '\\' | '$' | '`')) {
+                    Some((_, next)) => {
+                        literal.push(next);
+                        continue;
+                    }
+                    None => None,
+                },
+                '$' if line[i + 1..].starts_with('(') => {
+                    let (source, end) = parse_substitution(line, i + 1)?;
+                    while chars.next_if(|&(j, _)| text_start + j < end).is_some() {}
+                    Some(WordPart::CommandSubstitution(source))
+                }
+                '$' => match parse_parameter(line, i)? {
+                    Some((parameter, end)) => {
+                        while chars.next_if(|&(j, _)| text_start + j < end).is_some() {}
+                        Some(WordPart::Parameter(parameter))
+                    }
+                    None => None,
+                },
+                _ => None,
+            };
+            match part {
+                Some(part) => {
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                None => literal.push(c),
+            }
+        }
+        literal.push('\n');
+        pos = next;
+        if end == line.len() {
+            break;
+        }
+    }
+    Err(unterminated_here_doc(delimiter))
+}
+
+/// Words that start or end a compound command when they appear where a
+/// command name would.
+const RESERVED_WORDS: [&str; 16] = [
+    "if", "then", "elif", "else", "fi", "for", "in", "do", "done", "while", "until", "case",
+    "esac", "function", "{", "}",
+];
+
+/// Reserved words that end the list before them.
+const LIST_TERMINATORS: [&str; 8] = ["then", "elif", "else", "fi", "do", "done", "esac", "}"];
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(is_name_char)
+}
+
+/// Parses a command line, or a whole script, into lists, and-or chains,
+/// pipelines and compound commands.
+pub fn parse(line: &str) -> Result<CommandLine, ParseError> {
+    parse_with_aliases(line, &|_| None)
+}
+
+/// Like [`parse`], but replaces each command name that `aliases` knows with
+/// the commands it stands for.
+pub fn parse_with_aliases(
+    line: &str,
+    aliases: &dyn Fn(&str) -> Option<String>,
+) -> Result<CommandLine, ParseError> {
+    let tokens = tokenize(line)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        line,
+        aliases,
+        expanding: Vec::new(),
+    };
+    let command_line = parser.parse_list()?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
+    }
+    Ok(command_line)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: &'a str,
+    aliases: &'a dyn Fn(&str) -> Option<String>,
+    /// The aliases whose values are being parsed, each with the index of the
+    /// token just past its value
+    expanding: Vec<(String, usize)>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_kind(&self) -> Option<&TokenKind> {
+        self.peek().map(|t| &t.kind)
+    }
+
+    /// The current token if it is an unquoted reserved word.
+    fn keyword(&self) -> Option<&'static str> {
+        match self.peek_kind() {
+            Some(TokenKind::Word(word)) => word.reserved_word(),
+            _ => None,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        self.skip_newlines();
+        if self.keyword() != Some(keyword) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while let Some(TokenKind::Newline) = self.peek_kind() {
+            self.pos += 1;
+        }
+    }
+
+    /// Whether the list being parsed ends before the current token.
+    fn at_list_end(&self) -> bool {
+        match self.peek_kind() {
+            None | Some(TokenKind::RParen | TokenKind::DoubleSemi) => true,
+            _ => self
+                .keyword()
+                .is_some_and(|keyword| LIST_TERMINATORS.contains(&keyword)),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<CommandLine, ParseError> {
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.at_list_end() {
+                break;
+            }
+            let and_or = self.parse_and_or()?;
+            let background = match self.peek_kind() {
+                Some(TokenKind::Semi | TokenKind::Newline) => {
+                    self.pos += 1;
+                    false
+                }
+                Some(TokenKind::Amp) => {
+                    self.pos += 1;
+                    true
+                }
+                // Whatever follows is for the caller to make sense of
+                _ => {
+                    items.push(ListItem {
+                        and_or,
+                        background: false,
+                    });
+                    break;
+                }
+            };
+            items.push(ListItem { and_or, background });
+        }
+
+        Ok(CommandLine { items })
+    }
+
+    /// A list that must hold at least one command, as in the body of a loop.
+    fn parse_body(&mut self) -> Result<CommandLine, ParseError> {
+        let body = self.parse_list()?;
+        if body.items.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(body)
+    }
+
+    fn parse_and_or(&mut self) -> Result<AndOrList, ParseError> {
+        let start = self
+            .peek()
+            .map_or(self.line.len(), |token| token.span.start);
+        let first = self.parse_pipeline()?;
+        let mut rest = Vec::new();
+
+        loop {
+            let connector = match self.peek_kind() {
+                Some(TokenKind::AndIf) => Connector::And,
+                Some(TokenKind::OrIf) => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+            rest.push((connector, self.parse_pipeline()?));
+        }
+
+        let end = self.tokens[self.pos - 1].span.end;
+        let text = self.line.get(start..end).unwrap_or_default().to_string();
+        Ok(AndOrList { first, rest, text })
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut commands = vec![self.parse_command()?];
+
+        while let Some(TokenKind::Pipe) = self.peek_kind() {
+            self.pos += 1;
+            self.skip_newlines();
+            commands.push(self.parse_command()?);
+        }
+
+        Ok(Pipeline { commands })
+    }
+
+    /// Replaces the command name at the current token with the tokens of its
+    /// alias, over and over while the new name is an alias too. An alias is
+    /// not expanded again inside its own value, so `alias ls='ls -F'` works.
+    fn expand_alias(&mut self) -> Result<(), ParseError> {
+        loop {
+            let pos = self.pos;
+            self.expanding.retain(|&(_, end)| end > pos);
+            if self.keyword().is_some() {
+                return Ok(());
+            }
+            let Some(Token {
+                kind: TokenKind::Word(word),
+                span,
+            }) = self.peek()
+            else {
+                return Ok(());
+            };
+            let [WordPart::Literal(name)] = &word.parts[..] else {
+                return Ok(());
+            };
+            if self
+                .expanding
+                .iter()
+                .any(|(expanding, _)| expanding == name)
+            {
+                return Ok(());
+            }
+            let Some(value) = (self.aliases)(name) else {
+                return Ok(());
+            };
+
+            let (name, span) = (name.clone(), span.clone());
+            // Errors in the value are reported at the alias
+            let tokens: Vec<Token> = tokenize(&value)
+                .map_err(|e| ParseError {
+                    pos: span.start,
+                    ..e
+                })?
+                .into_iter()
+                .map(|token| Token {
+                    span: span.clone(),
+                    ..token
+                })
+                .collect();
+            let count = tokens.len();
+            for (_, end) in &mut self.expanding {
+                *end = *end + count - 1;
+            }
+            self.tokens.splice(pos..pos + 1, tokens);
+            self.expanding.push((name, pos + count));
+        }
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        self.expand_alias()?;
+        match self.keyword() {
+            Some("if") => return self.parse_if(),
+            Some("for") => return self.parse_for(),
+            Some(keyword @ ("while" | "until")) => {
+                self.pos += 1;
+                let condition = self.parse_body()?;
+                self.expect_keyword("do")?;
+                let body = self.parse_body()?;
+                self.expect_keyword("done")?;
+                return Ok(Command::While {
+                    condition,
+                    body,
+                    until: keyword == "until",
+                });
+            }
+            Some("case") => return self.parse_case(),
+            Some("{") => {
+                self.pos += 1;
+                let body = self.parse_body()?;
+                self.expect_keyword("}")?;
+                return Ok(Command::Group(body));
+            }
+            Some("function") => {
+                let start = self.tokens[self.pos].span.start;
+                self.pos += 1;
+                let name = self.parse_name()?;
+                if let Some(TokenKind::LParen) = self.peek_kind() {
+                    self.expect_parens()?;
+                }
+                return self.parse_function_body(name, start);
+            }
+            Some(_) => return Err(self.unexpected()),
+            None => {}
+        }
+
+        if let Some(TokenKind::LParen) = self.tokens.get(self.pos + 1).map(|t| &t.kind) {
+            let start = self.tokens[self.pos].span.start;
+            let name = self.parse_name()?;
+            self.expect_parens()?;
+            return self.parse_function_body(name, start);
+        }
+        Ok(Command::Simple(self.parse_simple_command()?))
+    }
+
+    fn parse_if(&mut self) -> Result<Command, ParseError> {
+        self.pos += 1;
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        loop {
+            let condition = self.parse_body()?;
+            self.expect_keyword("then")?;
+            branches.push((condition, self.parse_body()?));
+            match self.keyword() {
+                Some("elif") => self.pos += 1,
+                Some("else") => {
+                    self.pos += 1;
+                    otherwise = Some(self.parse_body()?);
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+                _ => {
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+            }
+        }
+        Ok(Command::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<Command, ParseError> {
+        self.pos += 1;
+        let name = self.parse_name()?;
+        if let Some(TokenKind::Semi) = self.peek_kind() {
+            self.pos += 1;
+        }
+        self.skip_newlines();
+
+        let mut words = None;
+        if self.keyword() == Some("in") {
+            self.pos += 1;
+            let mut list = Vec::new();
+            while let Some(TokenKind::Word(word)) = self.peek_kind() {
+                list.push(word.clone());
+                self.pos += 1;
+            }
+            match self.peek_kind() {
+                Some(TokenKind::Semi | TokenKind::Newline) => self.pos += 1,
+                _ => return Err(self.unexpected()),
+            }
+            words = Some(list);
+        }
+
+        self.expect_keyword("do")?;
+        let body = self.parse_body()?;
+        self.expect_keyword("done")?;
+        Ok(Command::For { name, words, body })
+    }
+
+    fn parse_case(&mut self) -> Result<Command, ParseError> {
+        self.pos += 1;
+        let word = match self.peek_kind() {
+            Some(TokenKind::Word(word)) => word.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        self.expect_keyword("in")?;
+
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.keyword() == Some("esac") {
+                self.pos += 1;
+                break;
+            }
+            if let Some(TokenKind::LParen) = self.peek_kind() {
+                self.pos += 1;
+            }
+            let mut patterns = Vec::new();
+            loop {
+                match self.peek_kind() {
+                    Some(TokenKind::Word(word)) => patterns.push(word.clone()),
+                    _ => return Err(self.unexpected()),
+                }
+                self.pos += 1;
+                match self.peek_kind() {
+                    Some(TokenKind::Pipe) => self.pos += 1,
+                    Some(TokenKind::RParen) => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.unexpected()),
+                }
+            }
+
+            let body = self.parse_list()?;
+            arms.push(CaseArm { patterns, body });
+            match self.peek_kind() {
+                Some(TokenKind::DoubleSemi) => self.pos += 1,
+                _ if self.keyword() == Some("esac") => {}
+                _ => return Err(self.unexpected()),
+            }
+        }
+        Ok(Command::Case { word, arms })
+    }
+
+    /// A name for a variable or function, which must be a plain word.
+    fn parse_name(&mut self) -> Result<String, ParseError> {
+        if let Some(TokenKind::Word(word)) = self.peek_kind()
+            && let [WordPart::Literal(name)] = &word.parts[..]
+            && is_name(name)
+        {
+            let name = name.clone();
+            self.pos += 1;
+            return Ok(name);
+        }
+        Err(self.unexpected())
+    }
+
+    fn expect_parens(&mut self) -> Result<(), ParseError> {
+        for expected in [TokenKind::LParen, TokenKind::RParen] {
+            if self.peek_kind() != Some(&expected) {
+                return Err(self.unexpected());
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// A function's body, which like in other shells must be a compound command.
+    fn parse_function_body(&mut self, name: String, start: usize) -> Result<Command, ParseError> {
+        self.skip_newlines();
+        if self.keyword().is_none() {
+            return Err(self.unexpected());
+        }
+        let body = self.parse_command()?;
+        let end = self.tokens[self.pos - 1].span.end;
+        Ok(Command::Function {
+            name,
+            body: Box::new(body),
+            text: self.line.get(start..end).unwrap_or_default().to_string(),
+        })
+    }
+
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut words = Vec::new();
+        let mut redirects = Vec::new();
+
+        while let Some(token) = self.peek() {
+            match &token.kind {
+                TokenKind::Word(word) => {
+                    words.push(word.clone());
+                    self.pos += 1;
+                }
+                TokenKind::Redirect(fd, op) => {
+                    let (fd, op) = (*fd, *op);
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(Token {
+                            kind: TokenKind::Word(target),
+                            ..
+                        }) => {
+                            redirects.push(Redirect {
+                                fd: fd.unwrap_or(op.default_fd()),
+                                op,
+                                target: target.clone(),
+                            });
+                            self.pos += 1;
+                        }
+                        _ => return Err(self.unexpected()),
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        if words.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(SimpleCommand { words, redirects })
+    }
+
+    /// Error for the current token, or for running out of tokens after an operator.
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError {
+                kind: ParseErrorKind::UnexpectedToken(token.kind.describe()),
+                pos: token.span.start,
+            },
+            None => {
+                // Blank lines after a dangling operator do not end the command,
+                // so the error is about the operator and the line it is on
+                let previous = self
+                    .tokens
+                    .iter()
+                    .rev()
+                    .find(|token| !matches!(token.kind, TokenKind::Newline));
+                ParseError {
+                    kind: ParseErrorKind::UnexpectedEnd(
+                        previous.map(|t| t.kind.describe()).unwrap_or_default(),
+                    ),
+                    pos: previous.map_or(self.line.len(), |t| t.span.end),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argvs(line: &str) -> Vec<Vec<String>> {
+        let parsed = parse(line).unwrap();
+        let mut result = Vec::new();
+        for item in parsed.items {
+            let mut pipelines = vec![item.and_or.first];
+            pipelines.extend(item.and_or.rest.into_iter().map(|(_, p)| p));
+            for pipeline in pipelines {
+                for command in &pipeline.commands {
+                    result.push(simple(command).argv());
+                }
+            }
+        }
+        result
+    }
+
+    fn simple(command: &Command) -> &SimpleCommand {
+        match command {
+            Command::Simple(command) => command,
+            other => panic!("expected a simple command, got {:?}", other),
+        }
+    }
+
+    /// The only command of a one-line, one-command input.
+    fn only_command(line: &str) -> Command {
+        let mut parsed = parse(line).unwrap();
+        assert_eq!(parsed.items.len(), 1, "{}", line);
+        parsed.items.remove(0).and_or.first.commands.remove(0)
+    }
+
+    #[test]
+    fn test_quotes_and_escapes() {
+        assert_eq!(
+            argvs(r#"git commit -m "fix bug" 'it''s' a\ b"#),
+            vec![vec!["git", "commit", "-m", "fix bug", "its", "a b"]]
+        );
+        assert_eq!(
+            argvs(r#"echo "say \"hi\"" C:\Users\me"#),
+            vec![vec!["echo", "say \"hi\"", r"C:\Users\me"]]
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        let parsed = parse("cat a | grep x && echo ok || echo no; sleep 1 &").unwrap();
+        assert_eq!(parsed.items.len(), 2);
+
+        let first = &parsed.items[0];
+        assert!(!first.background);
+        assert_eq!(first.and_or.first.commands.len(), 2);
+        assert_eq!(first.and_or.rest[0].0, Connector::And);
+        assert_eq!(first.and_or.rest[1].0, Connector::Or);
+
+        assert!(parsed.items[1].background);
+        assert_eq!(parsed.items[1].and_or.text, "sleep 1");
+    }
+
+    #[test]
+    fn test_redirects() {
+        let parsed = parse("ps > out.txt 2>&1 | grep x 2>>err.log <<< 'a b'").unwrap();
+        let commands: Vec<&SimpleCommand> = parsed.items[0]
+            .and_or
+            .first
+            .commands
+            .iter()
+            .map(simple)
+            .collect();
+
+        assert_eq!(commands[0].argv(), vec!["ps"]);
+        let redirects: Vec<_> = commands[0]
+            .redirects
+            .iter()
+            .map(|r| (r.fd, r.op, r.target.text()))
+            .collect();
+        assert_eq!(
+            redirects,
+            vec![
+                (1, RedirectOp::Output, "out.txt".to_string()),
+                (2, RedirectOp::DupOutput, "1".to_string()),
+            ]
+        );
+
+        assert_eq!(commands[1].argv(), vec!["grep", "x"]);
+        assert_eq!(commands[1].redirects[0].fd, 2);
+        assert_eq!(commands[1].redirects[0].op, RedirectOp::Append);
+        assert_eq!(commands[1].redirects[1].op, RedirectOp::HereString);
+        assert_eq!(commands[1].redirects[1].target.text(), "a b");
+
+        // A number only names a descriptor when it touches the operator
+        assert_eq!(argvs("echo 2 > f"), vec![vec!["echo", "2"]]);
+    }
+
+    #[test]
+    fn test_missing_redirect_target() {
+        let err = parse("cat <").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd("<".to_string()));
+
+        let err = parse("ls > | wc").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedToken("|".to_string()));
+    }
+
+    #[test]
+    fn test_parameters() {
+        let parsed =
+            parse(r#"echo $? "status $?" '$?' \$? $ $HOME/bin "${USER}s" a$EMPTY.b"#).unwrap();
+        let words = &simple(&parsed.items[0].and_or.first.commands[0]).words;
+        let lookup = |name: &str| match name {
+            "EMPTY" => Some(String::new()),
+            _ => Some(format!("<{}>", name)),
+        };
+
+        let expanded: Vec<String> = words.iter().map(|w| w.expand(&lookup)).collect();
+        assert_eq!(
+            expanded,
+            vec![
+                "echo",
+                "<?>",
+                "status <?>",
+                "$?",
+                "$?",
+                "$",
+                "<HOME>/bin",
+                "<USER>s",
+                "a.b"
+            ]
+        );
+        assert_eq!(words[1].text(), "$?");
+        assert_eq!(words[7].text(), "$USERs");
+    }
+
+    #[test]
+    fn test_tilde() {
+        let parsed = parse(r#"echo ~ ~/bin a~ '~' \~ "~/x" ~name ~$X x=~/y"#).unwrap();
+        let words = &simple(&parsed.items[0].and_or.first.commands[0]).words;
+        let lookup = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+
+        let expanded: Vec<String> = words.iter().map(|w| w.expand(&lookup)).collect();
+        assert_eq!(
+            expanded,
+            vec![
+                "echo",
+                "/home/me",
+                "/home/me/bin",
+                "a~",
+                "~",
+                "~",
+                "~/x",
+                "~name",
+                "~",
+                "x=~/y"
+            ]
+        );
+        assert_eq!(words[2].text(), "~/bin");
+        assert_eq!(words[1].expand(&|_: &str| None), "~");
+
+        let (name, value) = words[9].assignment().unwrap();
+        assert_eq!((name, value.expand(&lookup).as_str()), ("x", "/home/me/y"));
+    }
+
+    #[test]
+    fn test_parameter_defaults() {
+        let parsed = parse(r#"echo ${A:-one} "${B:-$A two}" ${C:-'}'}"#).unwrap();
+        let words = &simple(&parsed.items[0].and_or.first.commands[0]).words;
+        let lookup = |name: &str| (name == "A").then(|| "a".to_string());
+
+        let expanded: Vec<String> = words.iter().map(|w| w.expand(&lookup)).collect();
+        assert_eq!(expanded, vec!["echo", "a", "a two", "}"]);
+        assert_eq!(words[2].text(), "${B:-$A two}");
+    }
+
+    #[test]
+    fn test_bad_substitution() {
+        for line in ["echo ${A", "echo ${}", "echo ${A:x}", "echo ${A:-b"] {
+            let err = parse(line).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::BadSubstitution, "{}", line);
+            assert_eq!(err.pos, 5);
+        }
+    }
+
+    #[test]
+    fn test_empty_line() {
+        assert!(parse("   ").unwrap().items.is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_quote() {
+        let line = r#"echo "hello"#;
+        let err = parse(line).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnterminatedQuote('"'));
+        assert_eq!(err.column(line), 6);
+        assert!(err.render(line).ends_with("\n  echo \"hello\n       ^"));
+    }
+
+    #[test]
+    fn test_dangling_operator() {
+        let err = parse("ls |").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd("|".to_string()));
+        assert_eq!(err.pos, 4);
+
+        let script = "echo a\nls &&\n\n";
+        let err = parse(script).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd("&&".to_string()));
+        assert!(
+            err.render(script)
+                .ends_with("unexpected end of input after '&&'\n  ls &&\n       ^")
+        );
+
+        let err = parse("&& ls").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedToken("&&".to_string()));
+        assert_eq!(err.pos, 0);
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let words = match only_command("echo $1 ${10} $# \"$@\" $* $!") {
+            Command::Simple(command) => command.words,
+            other => panic!("{:?}", other),
+        };
+        let names: Vec<String> = words[1..].iter().map(Word::text).collect();
+        assert_eq!(names, vec!["$1", "$10", "$#", "$@", "$*", "$!"]);
+        // Without braces only one digit is read
+        assert_eq!(
+            words[2].expand(&|name: &str| Some(format!("<{}>", name))),
+            "<10>"
+        );
+        assert_eq!(argvs("echo $12")[0][1], "$12");
+    }
+
+    #[test]
+    fn test_if_and_loops() {
+        match only_command("if test -f a; then echo a; elif false; then echo b; else echo c; fi") {
+            Command::If {
+                branches,
+                otherwise,
+            } => {
+                assert_eq!(branches.len(), 2);
+                assert_eq!(otherwise.unwrap().items.len(), 1);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        match only_command("for f in *.log 'a b'; do echo $f; done") {
+            Command::For { name, words, body } => {
+                assert_eq!(name, "f");
+                let words: Vec<String> = words.unwrap().iter().map(Word::text).collect();
+                assert_eq!(words, vec!["*.log", "a b"]);
+                assert_eq!(body.items.len(), 1);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            only_command("for arg; do echo $arg; done"),
+            Command::For { words: None, .. }
+        ));
+        assert!(matches!(
+            only_command("until false\ndo\n  break\ndone"),
+            Command::While { until: true, .. }
+        ));
+
+        // Reserved words are only special where a command starts
+        assert_eq!(
+            argvs("echo if then fi"),
+            vec![vec!["echo", "if", "then", "fi"]]
+        );
+        assert_eq!(argvs("'if' x"), vec![vec!["if", "x"]]);
+    }
+
+    #[test]
+    fn test_case_and_functions() {
+        match only_command("case $x in a|b) echo ab;; (*.txt) ;; *) echo other\nesac") {
+            Command::Case { word, arms } => {
+                assert_eq!(word.text(), "$x");
+                let patterns: Vec<Vec<String>> = arms
+                    .iter()
+                    .map(|arm| arm.patterns.iter().map(Word::text).collect())
+                    .collect();
+                assert_eq!(patterns, vec![vec!["a", "b"], vec!["*.txt"], vec!["*"]]);
+                assert!(arms[1].body.items.is_empty());
+            }
+            other => panic!("{:?}", other),
+        }
+
+        for line in ["greet() { echo hi $1; }", "function greet {\n echo hi\n}"] {
+            match only_command(line) {
+                Command::Function { name, body, text } => {
+                    assert_eq!(name, "greet");
+                    assert!(matches!(*body, Command::Group(_)));
+                    assert_eq!(text, line);
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_comments_and_newlines() {
+        assert_eq!(
+            argvs("echo a # note\necho b |\n  grep b &&\n\necho c"),
+            vec![
+                vec!["echo", "a"],
+                vec!["echo", "b"],
+                vec!["grep", "b"],
+                vec!["echo", "c"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_compound_commands() {
+        for line in [
+            "if true; then echo",
+            "for f in a; do",
+            "case x in",
+            "f() {",
+            "echo 'a",
+        ] {
+            let err = parse(line).unwrap_err();
+            assert!(err.is_incomplete(), "{}: {:?}", line, err);
+        }
+
+        let err = parse("if true; then fi").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedToken("fi".to_string()));
+        let err = parse("echo a; done").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnexpectedToken("done".to_string())
+        );
+        assert!(!err.is_incomplete());
+
+        // Errors on later lines point into their own line
+        let input = "echo a\nls ) b";
+        let err = parse(input).unwrap_err();
+        assert_eq!(err.column(input), 4);
+        assert!(err.render(input).ends_with("\n  ls ) b\n     ^"));
+    }
+
+    #[test]
+    fn test_here_documents() {
+        let input = "cat <<EOF >out; cat <<-'X'\n$HOME \\$x\nEOF\n\t\tkeep $HOME\n\tX\necho";
+        let CommandLine { items } = parse(input).unwrap();
+        assert_eq!(items.len(), 3);
+        let redirects: Vec<&Redirect> = items[..2]
+            .iter()
+            .flat_map(|item| match &item.and_or.first.commands[0] {
+                Command::Simple(command) => command.redirects.first(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(redirects[0].op, RedirectOp::HereDoc);
+        assert_eq!(
+            redirects[0].target.parts,
+            vec![WordPart::DoubleQuoted(vec![
+                WordPart::Parameter(Parameter {
+                    name: "HOME".to_string(),
+                    default: None
+                }),
+                WordPart::Literal(" $x\n".to_string()),
+            ])]
+        );
+        assert_eq!(
+            redirects[1].target.parts,
+            vec![WordPart::SingleQuoted("keep $HOME\n".to_string())]
+        );
+
+        let err = parse("cat <<EOF\nno end").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnterminatedHereDoc("EOF".to_string())
+        );
+        assert!(err.is_incomplete());
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let words = match only_command(r#"diff <(sort a) "x $(echo ")" $(b))""#) {
+            Command::Simple(command) => command.words,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            words[1].parts,
+            vec![WordPart::ProcessSubstitution("sort a".to_string())]
+        );
+        assert_eq!(
+            words[2].parts,
+            vec![WordPart::DoubleQuoted(vec![
+                WordPart::Literal("x ".to_string()),
+                WordPart::CommandSubstitution(r#"echo ")" $(b)"#.to_string()),
+            ])]
+        );
+        assert_eq!(words[2].text(), r#"x $(echo ")" $(b))"#);
+
+        let err = parse("echo $(ls").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnterminatedSubstitution);
+        assert!(err.is_incomplete());
+        assert_eq!(err.pos, 5);
+
+        let err = parse("echo $(ls |) x").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedToken(")".to_string()));
+        assert_eq!(err.pos, 11);
+        let err = parse("echo $(ls; fi)").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedToken("fi".to_string()));
+        assert_eq!(err.pos, 11);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases = |name: &str| match name {
+            "ll" => Some("ls -l".to_string()),
+            "ls" => Some("ls --color".to_string()),
+            "gs" => Some("git status | head".to_string()),
+            "loop" => Some("loop2".to_string()),
+            "loop2" => Some("loop".to_string()),
+            _ => None,
+        };
+        let argvs = |line: &str| -> Vec<Vec<String>> {
+            let parsed = parse_with_aliases(line, &aliases).unwrap();
+            parsed
+                .items
+                .iter()
+                .flat_map(|item| &item.and_or.first.commands)
+                .map(|command| simple(command).argv())
+                .collect()
+        };
+
+        assert_eq!(
+            argvs("ll -a; echo ll; 'll'"),
+            vec![
+                vec!["ls", "--color", "-l", "-a"],
+                vec!["echo", "ll"],
+                vec!["ll"]
+            ]
+        );
+        assert_eq!(
+            argvs("gs -n 1"),
+            vec![vec!["git", "status"], vec!["head", "-n", "1"]]
+        );
+        assert_eq!(argvs("loop"), vec![vec!["loop"]]);
+        let parsed = parse_with_aliases("if ll; then gs; fi", &aliases).unwrap();
+        match &parsed.items[0].and_or.first.commands[0] {
+            Command::If { branches, .. } => {
+                let condition = &branches[0].0.items[0].and_or.first.commands[0];
+                assert_eq!(simple(condition).argv(), vec!["ls", "--color", "-l"]);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -1,134 +1,534 @@
+use crate::commands::{self, Command};
+use crate::external::ExternalStage;
+use crate::redirect::{Input, RedirectedStage, Redirections, Target};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, Fuse, StreamExt};
 use std::future::Future;
-use std::pin::Pin;
-use std::io;
+use std::io::{self, Read, Write};
+use std::sync::OnceLock;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+/// The byte stream flowing between two pipeline stages.
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// Number of chunks buffered between two stages before the writer has to wait.
+pub const CHANNEL_CAPACITY: usize = 16;
+
+/// Resolves to a stage's exit status once it has finished.
+pub type StatusFuture = BoxFuture<'static, io::Result<i32>>;
+
+/// What a stage produces: its output stream and, for stages backed by a
+/// process, a future for the exit status.
+pub struct StageOutput {
+    pub stream: ByteStream,
+    pub status: Option<StatusFuture>,
+}
 
+impl From<ByteStream> for StageOutput {
+    fn from(stream: ByteStream) -> Self {
+        Self {
+            stream,
+            status: None,
+        }
+    }
+}
 
-// Pipeline command trait
-pub trait AsyncCommand {
-    type Input;
-    type Output;
+/// How a stage finished: its exit status and the first error it reported.
+#[derive(Debug)]
+pub struct StageStatus {
+    pub code: i32,
+    pub error: Option<io::Error>,
+}
 
-    fn execute(
-        &self,
-        input: Self::Input,
-    ) -> Pin<Box<dyn Future<Output = io::Result<Self::Output>> + Send + '_>>;
+impl StageStatus {
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
 }
 
+/// One stage of a streaming pipeline such as `grep ERROR` in `cat a.log | grep ERROR`.
+pub trait Stage: Send {
+    /// Turns the previous stage's output into this stage's output.
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput;
+
+    /// Sends the stage's own diagnostics to `target`. Built-in stages report
+    /// errors through their stream instead, so by default this does nothing.
+    fn redirect_stderr(&mut self, _target: Target) {}
 
-// Pipeline that chains multiple commands
-pub struct Pipeline<C1, C2> {
-    first: C1,
-    second: C2,
+    /// Lets the stage read the shell's own stdin directly, returning whether
+    /// it will. Stages that cannot are given it as their input stream instead.
+    fn inherit_stdin(&mut self) -> bool {
+        false
+    }
 }
 
-impl<C1, C2> Pipeline<C1, C2>
-where
-    C1: AsyncCommand,
-    C2: AsyncCommand<Input = C1::Output>,
-{
-    pub fn new(first: C1, second: C2) -> Self {
-        Self { first, second }
+/// `cat [FILE]...`, passing its input through when no files are given
+pub struct CatStage {
+    files: Vec<String>,
+}
+
+impl CatStage {
+    pub fn new(files: Vec<String>) -> Self {
+        Self { files }
     }
-    
-    pub async fn execute(&self, input: C1::Input) -> io::Result<C2::Output> {
-        let intermediate = self.first.execute(input).await?;
-        self.second.execute(intermediate).await
+}
+
+impl Stage for CatStage {
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput {
+        use crate::cat::cat_async;
+
+        if self.files.is_empty() {
+            return input.into();
+        }
+        stream::once(cat_async(self.files)).flatten().boxed().into()
     }
 }
 
-// Example: Cat -> Grep pipeline
-pub struct CatGrepPipeline {
+/// `head -n N [FILE]...`, taking the first lines of its input when no files are given
+pub struct HeadStage {
+    lines: usize,
     files: Vec<String>,
-    pattern: String,
 }
 
-impl CatGrepPipeline {
-    pub fn new(files: Vec<String>, pattern: String) -> Self {
-        Self { files, pattern }
+impl HeadStage {
+    pub fn new(lines: usize, files: Vec<String>) -> Self {
+        Self { lines, files }
     }
 }
 
-impl AsyncCommand for CatGrepPipeline {
-    type Input = ();
-    type Output = String;
+impl Stage for HeadStage {
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput {
+        use crate::head::{head_async, head_stream};
+
+        if self.files.is_empty() {
+            return head_stream(input, self.lines).into();
+        }
+        stream::once(head_async(self.files, self.lines))
+            .flatten()
+            .boxed()
+            .into()
+    }
+}
 
-    fn execute(&self, _input: ()) -> Pin<Box<dyn Future<Output = io::Result<String>> + Send + '_>> {
-        use crate::cat::cat_async_to_string;
-        use crate::grep::grep_async_to_string;
+/// `tail -n N [FILE]...`, keeping the last lines of its input when no files are given
+pub struct TailStage {
+    lines: usize,
+    files: Vec<String>,
+}
 
-        let files: Vec<String> = self.files.clone();
-        let pattern = self.pattern.clone();
+impl TailStage {
+    pub fn new(lines: usize, files: Vec<String>) -> Self {
+        Self { lines, files }
+    }
+}
 
-        Box::pin(async move {
-            // Step 1: Run cat
-            let cat_output = cat_async_to_string(files).await?;
+impl Stage for TailStage {
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput {
+        use crate::tail::{tail_async, tail_stream};
+
+        if self.files.is_empty() {
+            return tail_stream(input, self.lines).into();
+        }
+        stream::once(tail_async(self.files, self.lines))
+            .flatten()
+            .boxed()
+            .into()
+    }
+}
 
-            // Step 2: Write to temp file
-            let temp_file = "temp_pipeline.txt";
-            tokio::fs::write(temp_file, &cat_output).await?;
+/// Runs a built-in [`Command`] as a pipeline stage on a blocking thread,
+/// with its stdin reading from the previous stage and its stdout feeding the next.
+pub struct BuiltinStage {
+    command: &'static dyn Command,
+    args: Vec<String>,
+    stderr: Target,
+}
 
-            // Step 3: Run grep
-            let result = grep_async_to_string(&pattern, vec![temp_file.to_string()]).await;
+impl BuiltinStage {
+    pub fn new(command: &'static dyn Command, args: Vec<String>) -> Self {
+        Self {
+            command,
+            args,
+            stderr: Target::Stderr,
+        }
+    }
+}
 
-            // Step 4: Cleanup
-            let _ = tokio::fs::remove_file(temp_file).await;
+impl Stage for BuiltinStage {
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let handle = tokio::runtime::Handle::current();
+        let BuiltinStage {
+            command,
+            args,
+            stderr,
+        } = *self;
+
+        let task = tokio::task::spawn_blocking(move || {
+            let mut stdin = StreamReader {
+                stream: input.fuse(),
+                handle,
+                chunk: Bytes::new(),
+            };
+            let mut stdout = io::BufWriter::new(ChannelWriter(tx.clone()));
+            let mut stderr: Box<dyn Write> = match stderr {
+                Target::Stdout => Box::new(ChannelWriter(tx)),
+                target => target.writer().unwrap_or_else(|_| Box::new(io::stderr())),
+            };
+
+            let code = command.run(&args, &mut stdin, &mut stdout, &mut stderr);
+            // The next stage may have stopped reading, which is not an error here
+            let _ = stdout.flush();
+            code
+        });
+
+        StageOutput {
+            stream: receiver_stream(rx),
+            status: Some(Box::pin(
+                async move { task.await.map_err(io::Error::other) },
+            )),
+        }
+    }
 
-            result
-        })
+    fn redirect_stderr(&mut self, target: Target) {
+        self.stderr = target;
     }
 }
 
+/// Reads a byte stream from a blocking thread. The stream is fused, since
+/// readers may keep reading after the end and get `Ok(0)` each time.
+struct StreamReader {
+    stream: Fuse<ByteStream>,
+    handle: tokio::runtime::Handle,
+    chunk: Bytes,
+}
 
-// Example: Cat -> Head pipeline
-pub struct CatHeadPipeline {
-    files: Vec<String>,
-    lines: usize,
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // An empty chunk is skipped, as returning 0 for it would mean the end
+        while self.chunk.is_empty() {
+            match self.handle.block_on(self.stream.next()) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Err(e),
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk.split_to(n));
+        Ok(n)
+    }
+}
+
+/// Writes into the channel to the next stage from a blocking thread.
+struct ChannelWriter(mpsc::Sender<Bytes>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Bytes::copy_from_slice(buf))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds a pipeline stage from a command name and its arguments. Built-ins
+/// without a native stage run on a blocking thread, and names that are not
+/// built-ins run as external programs.
+pub fn parse_stage(argv: &[String]) -> Result<Box<dyn Stage>, String> {
+    let name = argv[0].as_str();
+    let args = &argv[1..];
+
+    match commands::registry().get(name) {
+        Some(command) => command
+            .stage(args)
+            .unwrap_or_else(|| Ok(Box::new(BuiltinStage::new(command, args.to_vec())))),
+        None => Ok(Box::new(ExternalStage::new(name, args.to_vec()))),
+    }
+}
+
+/// Runs a stage to completion from synchronous code, for the `run` of
+/// commands that are implemented as stages.
+pub fn run_stage(
+    name: &str,
+    stage: Result<Box<dyn Stage>, String>,
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> i32 {
+    let stage = match stage {
+        Ok(stage) => stage,
+        Err(e) => {
+            let _ = writeln!(stderr, "{}", e);
+            return 2;
+        }
+    };
+    let mut input = Vec::new();
+    if let Err(e) = stdin.read_to_end(&mut input) {
+        return commands::report_error(stderr, name, e);
+    }
+
+    let stage = RedirectedStage::new(
+        stage,
+        Redirections {
+            stdin: Some(Input::Bytes(input)),
+            ..Redirections::default()
+        },
+    );
+    let mut output = Vec::new();
+    let status = runtime()
+        .block_on(run_stages(vec![Box::new(stage)], &mut output))
+        .remove(0);
+
+    if let Err(e) = stdout.write_all(&output) {
+        return commands::report_error(stderr, name, e);
+    }
+    if let Some(e) = status.error {
+        commands::report_error(stderr, name, e);
+    }
+    status.code
+}
+
+/// Parses `-n N`, `-nN`, `-N` and `--lines=N` for head and tail.
+pub fn parse_line_count(name: &str, args: &[String]) -> Result<(usize, Vec<String>), String> {
+    let mut lines = 10;
+    let mut files = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let count = if arg == "-n" {
+            match iter.next() {
+                Some(value) => value.as_str(),
+                None => return Err(format!("{}: option requires an argument -- 'n'", name)),
+            }
+        } else if let Some(value) = arg.strip_prefix("--lines=") {
+            value
+        } else if let Some(value) = arg.strip_prefix("-n") {
+            value
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            &arg[1..]
+        } else {
+            files.push(arg.clone());
+            continue;
+        };
+
+        lines = count
+            .parse()
+            .map_err(|_| format!("{}: invalid number of lines: '{}'", name, count))?;
+    }
+
+    Ok((lines, files))
 }
 
-impl CatHeadPipeline {
-    pub fn new(files: Vec<String>, lines: usize) -> Self {
-        Self { files, lines }
+/// Splits a byte stream into lines, stripping `\n` and `\r\n` terminators.
+pub fn lines(input: ByteStream) -> BoxStream<'static, io::Result<String>> {
+    stream::unfold(
+        (input, Vec::new(), false),
+        |(mut input, mut buffer, mut done)| async move {
+            loop {
+                if let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                    let rest = buffer.split_off(newline + 1);
+                    let line = std::mem::replace(&mut buffer, rest);
+                    return Some((Ok(line_to_string(line)), (input, buffer, done)));
+                }
+                if done {
+                    if buffer.is_empty() {
+                        return None;
+                    }
+                    let line = std::mem::take(&mut buffer);
+                    return Some((Ok(line_to_string(line)), (input, buffer, done)));
+                }
+                match input.next().await {
+                    Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                    Some(Err(e)) => return Some((Err(e), (input, buffer, done))),
+                    None => done = true,
+                }
+            }
+        },
+    )
+    .boxed()
+}
+
+fn line_to_string(mut line: Vec<u8>) -> String {
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
     }
+    String::from_utf8_lossy(&line).into_owned()
 }
 
-impl AsyncCommand for CatHeadPipeline {
-    type Input = ();
-    type Output = String;
+/// Size of the reads from files and child processes.
+const READ_CHUNK: usize = 8 * 1024;
 
-    fn execute(&self, _input: ()) -> Pin<Box<dyn Future<Output = io::Result<String>> + Send + '_>> {
+/// Reads `reader` to the end as a stream of chunks.
+pub fn reader_stream<R>(reader: R) -> ByteStream
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut buffer = vec![0; READ_CHUNK];
+        match reader.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Ok(Bytes::from(buffer)), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+    .boxed()
+}
 
-        use crate::cat::cat_async_to_string;
-        use crate::head::head_async_to_string;
+/// The shell's own stdin as a stream, read only as far as the stage asks.
+pub fn stdin_stream() -> ByteStream {
+    reader_stream(tokio::io::stdin())
+}
 
-        let files = self.files.clone();
-        let lines = self.lines;
+/// Wraps a bounded channel receiver as the input stream of the next stage.
+fn receiver_stream(rx: mpsc::Receiver<Bytes>) -> ByteStream {
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (Ok(chunk), rx))
+    })
+    .boxed()
+}
 
-        Box::pin(async move {
-            let cat_output = cat_async_to_string(files.clone()).await?;
+/// Drains a stage's output into `sink`, then waits for its exit status.
+///
+/// Stages without a process fail with status 1 if their stream reported an error.
+async fn finish_stage<F, Fut>(output: StageOutput, mut sink: F) -> StageStatus
+where
+    F: FnMut(Bytes) -> Fut,
+    Fut: Future<Output = io::Result<bool>>,
+{
+    let StageOutput { mut stream, status } = output;
+    let mut error = None;
+
+    while let Some(chunk) = stream.next().await {
+        let result = match chunk {
+            Ok(chunk) => sink(chunk).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(true) => {}
+            // The consumer has gone away, so stop producing
+            Ok(false) => break,
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    // Close our end first so a child blocked on writing sees a broken pipe
+    drop(stream);
+
+    let code = match status {
+        Some(status) => match status.await {
+            Ok(code) => code,
+            Err(e) => {
+                error.get_or_insert(e);
+                1
+            }
+        },
+        None if error.is_some() => 1,
+        None => 0,
+    };
+    StageStatus { code, error }
+}
 
-            let temp_file = "temp_head_pipeline.txt";
-            tokio::fs::write(temp_file, cat_output).await?;
+/// Runs every stage concurrently, each stage in its own task connected to the
+/// next by a bounded channel, and writes the last stage's output to `output`.
+///
+/// When a stage stops reading early (like `head`), the stages before it see
+/// their channel close and stop as well. Returns one status per stage.
+pub async fn run_stages(
+    stages: Vec<Box<dyn Stage>>,
+    output: &mut (dyn Write + Send),
+) -> Vec<StageStatus> {
+    run_stages_from(stream::empty().boxed(), stages, output).await
+}
 
-            let result = head_async_to_string(vec![temp_file], lines).await;
+/// Like [`run_stages`], but with `input` as the first stage's input.
+pub async fn run_stages_from(
+    mut input: ByteStream,
+    stages: Vec<Box<dyn Stage>>,
+    output: &mut (dyn Write + Send),
+) -> Vec<StageStatus> {
+    let mut handles = Vec::new();
+    let count = stages.len();
+    let mut last = None;
+
+    for (index, stage) in stages.into_iter().enumerate() {
+        let stage_output = stage.run(input);
+
+        if index + 1 == count {
+            last = Some(stage_output);
+            break;
+        }
+
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        handles.push(tokio::spawn(finish_stage(stage_output, move |chunk| {
+            let tx = tx.clone();
+            async move { Ok(tx.send(chunk).await.is_ok()) }
+        })));
+        input = receiver_stream(rx);
+    }
 
-            let _ = tokio::fs::remove_file(temp_file).await;
-            result
-        })
+    let mut statuses = Vec::with_capacity(count);
+    let last_status = match last {
+        Some(last) => Some(
+            finish_stage(last, |chunk| {
+                let written = output.write_all(&chunk).map(|_| true);
+                async move { written }
+            })
+            .await,
+        ),
+        None => None,
+    };
+    let _ = output.flush();
+
+    for handle in handles {
+        statuses.push(handle.await.unwrap_or_else(|e| StageStatus {
+            code: 1,
+            error: Some(io::Error::other(e)),
+        }));
     }
+    statuses.extend(last_status);
+    statuses
 }
 
+/// Runs the stages and collects the final output, failing on the first stage error.
+pub async fn run_stages_to_string(stages: Vec<Box<dyn Stage>>) -> io::Result<String> {
+    let mut output = Vec::new();
+    for status in run_stages(stages, &mut output).await {
+        if let Some(e) = status.error {
+            return Err(e);
+        }
+    }
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
 
-// Generic pipeline executor
-pub async fn execute_pipeline<C: AsyncCommand<Input = ()>>(
-    command: C,
-) -> io::Result<C::Output> {
-    command.execute(()).await
+/// Shared runtime used to drive pipelines from the synchronous REPL and TUI.
+pub fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| Runtime::new().expect("Failed to start async runtime"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn argv(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
     #[tokio::test]
     async fn test_cat_grep_pipeline() {
         let file_path = "test_pipeline.txt";
@@ -136,12 +536,12 @@ mod tests {
 
         tokio::fs::write(file_path, content).await.unwrap();
 
-        let pipeline = CatGrepPipeline::new(
-            vec![file_path.to_string()],
-            "hello".to_string(),
-        );
+        let stages = vec![
+            parse_stage(&argv(&format!("cat {}", file_path))).unwrap(),
+            parse_stage(&argv("grep hello")).unwrap(),
+        ];
 
-        let result = execute_pipeline(pipeline).await.unwrap();
+        let result = run_stages_to_string(stages).await.unwrap();
         assert!(result.contains("hello world"));
         assert!(result.contains("hello again"));
 
@@ -155,9 +555,12 @@ mod tests {
 
         tokio::fs::write(file_path, content).await.unwrap();
 
-        let pipeline = CatHeadPipeline::new(vec![file_path.to_string()], 3);
+        let stages = vec![
+            parse_stage(&argv(&format!("cat {}", file_path))).unwrap(),
+            parse_stage(&argv("head -n 3")).unwrap(),
+        ];
 
-        let result = execute_pipeline(pipeline).await.unwrap();
+        let result = run_stages_to_string(stages).await.unwrap();
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 3);
         assert_eq!(lines[0], "line 1");
@@ -166,4 +569,91 @@ mod tests {
 
         tokio::fs::remove_file(file_path).await.unwrap();
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_three_stage_pipeline() {
+        let file_path = "test_three_stage_pipeline.txt";
+        let content: String = (1..=100)
+            .map(|i| format!("{} line {}\n", if i % 2 == 0 { "ERROR" } else { "INFO" }, i))
+            .collect();
+
+        tokio::fs::write(file_path, content).await.unwrap();
+
+        let stages = [
+            argv(&format!("cat {}", file_path)),
+            argv("grep ERROR"),
+            argv("head -n 3"),
+        ]
+        .iter()
+        .map(|argv| parse_stage(argv).unwrap())
+        .collect();
+
+        let result = run_stages_to_string(stages).await.unwrap();
+        assert_eq!(result, "ERROR line 2\nERROR line 4\nERROR line 6\n");
+
+        tokio::fs::remove_file(file_path).await.unwrap();
+    }
+
+    struct Yes;
+
+    impl Stage for Yes {
+        fn run(self: Box<Self>, _input: ByteStream) -> StageOutput {
+            stream::repeat_with(|| Ok(Bytes::from_static(b"y\n")))
+                .boxed()
+                .into()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_head_stops_endless_producer() {
+        let stages: Vec<Box<dyn Stage>> =
+            vec![Box::new(Yes), parse_stage(&argv("head -2")).unwrap()];
+
+        let result = run_stages_to_string(stages).await.unwrap();
+        assert_eq!(result, "y\ny\n");
+    }
+
+    struct Text(&'static [u8]);
+
+    impl Stage for Text {
+        fn run(self: Box<Self>, _input: ByteStream) -> StageOutput {
+            stream::iter([Ok(Bytes::from_static(self.0))])
+                .boxed()
+                .into()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_builtin_reads_past_the_end() {
+        // Built-ins may read again after the end, as grep does when the last
+        // line has no newline or there is no input at all
+        for (input, expected) in [(&b"a\nb"[..], "b\n"), (b"", "")] {
+            let stages: Vec<Box<dyn Stage>> =
+                vec![Box::new(Text(input)), parse_stage(&argv("grep b")).unwrap()];
+            let result = run_stages_to_string(stages).await.unwrap();
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lines_across_chunks() {
+        let chunks = vec![
+            Ok(Bytes::from_static(b"he")),
+            Ok(Bytes::from_static(b"llo\r\nwor")),
+            Ok(Bytes::from_static(b"ld")),
+        ];
+        let collected: Vec<String> = lines(stream::iter(chunks).boxed())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(collected, vec!["hello", "world"]);
+    }
+
+    #[test]
+    fn test_parse_stage_errors() {
+        assert!(parse_stage(&argv("grep")).is_err());
+        assert!(parse_stage(&argv("head -n x")).is_err());
+        assert!(parse_stage(&argv("ps")).is_ok());
+        assert!(parse_stage(&argv("sort")).is_ok());
+    }
+}
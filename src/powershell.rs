@@ -1,26 +1,95 @@
+use crate::commands;
+use crate::external::{self, ExternalStage};
+use crate::pipeline::{self, Stage};
+use crate::vars;
 use colored::Colorize;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::process::Command;
 
-/// Execute PowerShell commands by shelling out to the system PowerShell
-pub fn execute(args: &[&str]) {
+pub struct PowerShell;
+
+impl commands::Command for PowerShell {
+    fn name(&self) -> &'static str {
+        "psh"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["powershell"]
+    }
+
+    fn usage(&self) -> &'static str {
+        "psh <command> [options]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run PowerShell commands (--interactive for a session)"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        if !is_powershell_available() {
+            return commands::report_error(
+                stderr,
+                self.name(),
+                "PowerShell is not available on this system",
+            );
+        }
+        if args.is_empty() {
+            return commands::exit_code(stderr, self.name(), show_powershell_help(stdout));
+        }
+        pipeline::run_stage(
+            self.name(),
+            Ok(Box::new(stage(args))),
+            stdin,
+            stdout,
+            stderr,
+        )
+    }
+
+    fn stage(&self, args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        if args.is_empty() || !is_powershell_available() {
+            return None;
+        }
+        Some(Ok(Box::new(stage(args))))
+    }
+
+    fn run_in_terminal(&self, args: &[String]) -> Option<i32> {
+        if args.first().map(String::as_str) == Some("--interactive") {
+            interactive_mode();
+            Some(0)
+        } else {
+            Some(execute(
+                &args.iter().map(String::as_str).collect::<Vec<_>>(),
+            ))
+        }
+    }
+}
+
+/// Execute PowerShell commands by shelling out to the system PowerShell,
+/// returning PowerShell's exit code
+pub fn execute(args: &[&str]) -> i32 {
     // Check if PowerShell is available
     if !is_powershell_available() {
         println!(
             "{}",
             "Error: PowerShell is not available on this system".red()
         );
-        return;
+        return external::NOT_FOUND_STATUS;
     }
 
     // If no arguments provided, show PowerShell help
     if args.is_empty() {
-        show_powershell_help();
-        return;
+        let _ = show_powershell_help(&mut io::stdout());
+        return 0;
     }
 
     // Execute the PowerShell command
-    execute_powershell_command(args);
+    execute_powershell_command(args)
 }
 
 /// Check if PowerShell is available on the system
@@ -53,46 +122,35 @@ fn get_powershell_executable() -> &'static str {
     }
 }
 
-/// Execute a PowerShell command with the provided arguments
-fn execute_powershell_command(args: &[&str]) {
+/// Execute a PowerShell command with the provided arguments, streaming its output to the terminal
+fn execute_powershell_command(args: &[&str]) -> i32 {
     let ps_exe = get_powershell_executable();
     let command_string = args.join(" ");
 
     let mut cmd = Command::new(ps_exe);
-    cmd.args(&["-Command", &command_string]);
+    vars::apply(&mut cmd);
+    cmd.args(["-Command", &command_string]);
 
-    // Execute the command and handle output
-    match cmd.output() {
-        Ok(output) => {
-            // Print stdout
-            if !output.stdout.is_empty() {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
-            }
-
-            // Print stderr
-            if !output.stderr.is_empty() {
-                eprint!("{}", String::from_utf8_lossy(&output.stderr));
-            }
-
-            // If the command failed, show the exit code
-            if !output.status.success() {
-                if let Some(code) = output.status.code() {
-                    eprintln!(
-                        "{}",
-                        format!("PowerShell command failed with exit code: {}", code).red()
-                    );
-                }
-            }
-        }
+    match cmd.status() {
+        Ok(status) => external::exit_code(status),
         Err(e) => {
             eprintln!(
                 "{}",
                 format!("Failed to execute PowerShell command: {}", e).red()
             );
+            1
         }
     }
 }
 
+/// Pipeline stage running a PowerShell command, so its output can stream into other commands
+pub fn stage(args: &[String]) -> ExternalStage {
+    ExternalStage::new(
+        get_powershell_executable(),
+        vec!["-Command".to_string(), args.join(" ")],
+    )
+}
+
 /// Show interactive PowerShell mode for complex operations
 pub fn interactive_mode() {
     println!("{}", "PowerShell Interactive Mode".bold().blue());
@@ -123,7 +181,13 @@ pub fn interactive_mode() {
                 }
 
                 // Execute the PowerShell command
-                execute_powershell_command(&[input]);
+                let code = execute_powershell_command(&[input]);
+                if code != 0 {
+                    eprintln!(
+                        "{}",
+                        format!("PowerShell command failed with exit code: {}", code).red()
+                    );
+                }
             }
             Err(e) => {
                 eprintln!("{}", format!("Error reading input: {}", e).red());
@@ -134,118 +198,126 @@ pub fn interactive_mode() {
 }
 
 /// Show PowerShell help and common commands
-fn show_powershell_help() {
-    println!("{}", "PowerShell Commands Available".bold().blue());
-    println!("{}", "Usage: ps <command> [options]".dimmed());
-    println!();
+fn show_powershell_help(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{}", "PowerShell Commands Available".bold().blue())?;
+    writeln!(out, "{}", "Usage: ps <command> [options]".dimmed())?;
+    writeln!(out)?;
 
-    println!("{}", "Most Common PowerShell Commands:".bold().white());
-    println!(
-        "  {:<20} {}",
-        "Get-Process".yellow(),
-        "List running processes"
-    );
-    println!(
-        "  {:<20} {}",
-        "Get-ChildItem".yellow(),
-        "List files and directories (like ls)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Set-Location".yellow(),
-        "Change directory (like cd)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Get-Location".yellow(),
-        "Get current directory (like pwd)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Get-Content".yellow(),
-        "Read file contents (like cat)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Set-Content".yellow(),
-        "Write content to file"
-    );
-    println!(
-        "  {:<20} {}",
-        "Copy-Item".yellow(),
-        "Copy files or directories (like cp)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Move-Item".yellow(),
-        "Move files or directories (like mv)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Remove-Item".yellow(),
-        "Delete files or directories (like rm)"
-    );
-    println!(
-        "  {:<20} {}",
-        "New-Item".yellow(),
-        "Create new files or directories"
-    );
-    println!(
-        "  {:<20} {}",
-        "Get-Service".yellow(),
-        "List system services"
-    );
-    println!("  {:<20} {}", "Get-EventLog".yellow(), "Read event logs");
-    println!("  {:<20} {}", "Get-WmiObject".yellow(), "Query WMI objects");
-    println!(
-        "  {:<20} {}",
-        "Invoke-WebRequest".yellow(),
-        "Make HTTP requests (like curl)"
-    );
-    println!(
-        "  {:<20} {}",
-        "Test-Connection".yellow(),
-        "Ping hosts (like ping)"
-    );
-    println!();
+    writeln!(out, "{}", "Most Common PowerShell Commands:".bold().white())?;
+    writeln!(
+        out,
+        "  {:<20} List running processes",
+        "Get-Process".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} List files and directories (like ls)",
+        "Get-ChildItem".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Change directory (like cd)",
+        "Set-Location".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Get current directory (like pwd)",
+        "Get-Location".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Read file contents (like cat)",
+        "Get-Content".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Write content to file",
+        "Set-Content".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Copy files or directories (like cp)",
+        "Copy-Item".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Move files or directories (like mv)",
+        "Move-Item".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Delete files or directories (like rm)",
+        "Remove-Item".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Create new files or directories",
+        "New-Item".yellow()
+    )?;
+    writeln!(out, "  {:<20} List system services", "Get-Service".yellow())?;
+    writeln!(out, "  {:<20} Read event logs", "Get-EventLog".yellow())?;
+    writeln!(out, "  {:<20} Query WMI objects", "Get-WmiObject".yellow())?;
+    writeln!(
+        out,
+        "  {:<20} Make HTTP requests (like curl)",
+        "Invoke-WebRequest".yellow()
+    )?;
+    writeln!(
+        out,
+        "  {:<20} Ping hosts (like ping)",
+        "Test-Connection".yellow()
+    )?;
+    writeln!(out)?;
 
-    println!("{}", "Examples:".bold().cyan());
-    println!(
+    writeln!(out, "{}", "Examples:".bold().cyan())?;
+    writeln!(
+        out,
         "  {}",
         "ps Get-Process | Where-Object {$_.CPU -gt 100}".dimmed()
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "  {}",
         "ps Get-ChildItem C:\\ -Recurse -Include *.txt".dimmed()
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "  {}",
         "ps Get-Service | Where-Object {$_.Status -eq 'Running'}".dimmed()
-    );
-    println!("  {}", "ps Test-Connection google.com -Count 4".dimmed());
-    println!(
+    )?;
+    writeln!(
+        out,
+        "  {}",
+        "ps Test-Connection google.com -Count 4".dimmed()
+    )?;
+    writeln!(
+        out,
         "  {}",
         "ps Get-EventLog -LogName System -Newest 10".dimmed()
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "  {}",
         "ps Get-WmiObject -Class Win32_ComputerSystem".dimmed()
-    );
-    println!();
+    )?;
+    writeln!(out)?;
 
-    println!("{}", "Interactive Mode:".bold().magenta());
-    println!("  {}", "ps --interactive".dimmed());
-    println!(
+    writeln!(out, "{}", "Interactive Mode:".bold().magenta())?;
+    writeln!(out, "  {}", "ps --interactive".dimmed())?;
+    writeln!(
+        out,
         "  {}",
         "  Enter interactive PowerShell mode for easier command execution".dimmed()
-    );
-    println!();
+    )?;
+    writeln!(out)?;
 
-    println!("{}", "Aliases:".bold().green());
-    println!(
+    writeln!(out, "{}", "Aliases:".bold().green())?;
+    writeln!(
+        out,
         "  {}",
         "psh = ps (shorter alias for PowerShell commands)".dimmed()
-    );
+    )?;
+    Ok(())
 }
 
 /// Get PowerShell version information
@@ -253,7 +325,7 @@ fn show_powershell_help() {
 pub fn get_version_info() -> Option<String> {
     let ps_exe = get_powershell_executable();
     match Command::new(ps_exe)
-        .args(&["-Command", "$PSVersionTable.PSVersion.ToString()"])
+        .args(["-Command", "$PSVersionTable.PSVersion.ToString()"])
         .output()
     {
         Ok(output) => {
@@ -277,7 +349,7 @@ pub fn get_version_info() -> Option<String> {
 pub fn test_current_directory() -> bool {
     let ps_exe = get_powershell_executable();
     match Command::new(ps_exe)
-        .args(&["-Command", "Get-Location"])
+        .args(["-Command", "Get-Location"])
         .output()
     {
         Ok(output) => output.status.success(),
@@ -0,0 +1,463 @@
+//! `printf`, which formats its arguments as C's `printf` does.
+//!
+//! The format is reused for as long as arguments remain, so
+//! `printf '%s=%s\n' a 1 b 2` prints two lines. Missing arguments count as
+//! empty strings or zero.
+
+use crate::commands::{self, Command};
+use crate::echo::{self, Escapes};
+use std::io::{Read, Write};
+
+/// A conversion such as `%-8.3s`.
+#[derive(Debug, Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    zero: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+/// The arguments, handed out in turn.
+struct Arguments<'a> {
+    args: &'a [String],
+    next: usize,
+    /// Why a numeric argument was not one, for the error message
+    errors: Vec<String>,
+}
+
+impl Arguments<'_> {
+    fn string(&mut self) -> &str {
+        let arg = self.args.get(self.next).map_or("", String::as_str);
+        self.next += 1;
+        arg
+    }
+
+    /// The next argument as an integer. Like C, `0x` starts hexadecimal, a
+    /// leading `0` octal, and a leading quote the code of the character
+    /// after it.
+    fn integer(&mut self) -> i128 {
+        let arg = self.string().to_string();
+        let text = arg.trim_start();
+        if let Some(quoted) = text.strip_prefix(['\'', '"']) {
+            return quoted.chars().next().map_or(0, |c| c as i128);
+        }
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (radix, digits) =
+            if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+                (16, hex)
+            } else if digits.len() > 1 && digits.starts_with('0') {
+                (8, &digits[1..])
+            } else {
+                (10, digits)
+            };
+        match i128::from_str_radix(digits, radix) {
+            Ok(value) if negative => -value,
+            Ok(value) => value,
+            Err(_) if text.is_empty() && arg.is_empty() => 0,
+            Err(_) => {
+                self.errors
+                    .push(format!("'{}': expected a numeric value", arg));
+                0
+            }
+        }
+    }
+
+    fn float(&mut self) -> f64 {
+        let arg = self.string().to_string();
+        let text = arg.trim();
+        if text.is_empty() {
+            return 0.0;
+        }
+        if text.starts_with(['\'', '"']) {
+            return text[1..].chars().next().map_or(0.0, |c| c as u32 as f64);
+        }
+        text.parse().unwrap_or_else(|_| {
+            self.errors
+                .push(format!("'{}': expected a numeric value", arg));
+            0.0
+        })
+    }
+
+    /// The next argument as a width or precision given by `*`.
+    fn count(&mut self) -> usize {
+        self.integer().max(0) as usize
+    }
+}
+
+/// Pads `body` to the spec's width. Zeros go after any sign or `0x`, whose
+/// length is `prefix`.
+fn pad(body: String, spec: &Spec, prefix: usize, zeros: bool) -> String {
+    let length = body.chars().count();
+    let Some(width) = spec.width.filter(|width| *width > length) else {
+        return body;
+    };
+    let fill = width - length;
+    if spec.left {
+        format!("{}{}", body, " ".repeat(fill))
+    } else if zeros && spec.zero {
+        format!("{}{}{}", &body[..prefix], "0".repeat(fill), &body[prefix..])
+    } else {
+        format!("{}{}", " ".repeat(fill), body)
+    }
+}
+
+fn sign(negative: bool, spec: &Spec) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+fn format_integer(value: i128, conversion: char, spec: &Spec) -> String {
+    let (negative, magnitude) = match conversion {
+        'd' | 'i' => (value < 0, value.unsigned_abs()),
+        // Negative numbers wrap around, as the unsigned conversions of C
+        // print them
+        _ => (false, value as u64 as u128),
+    };
+    let mut digits = match conversion {
+        'o' => format!("{:o}", magnitude),
+        'x' => format!("{:x}", magnitude),
+        'X' => format!("{:X}", magnitude),
+        _ => magnitude.to_string(),
+    };
+    if let Some(precision) = spec.precision {
+        if precision == 0 && magnitude == 0 {
+            digits.clear();
+        } else if digits.len() < precision {
+            digits = format!("{}{}", "0".repeat(precision - digits.len()), digits);
+        }
+    }
+    let prefix = match conversion {
+        'o' if spec.alternate && !digits.starts_with('0') => "0",
+        'x' if spec.alternate && magnitude != 0 => "0x",
+        'X' if spec.alternate && magnitude != 0 => "0X",
+        'd' | 'i' => sign(negative, spec),
+        _ => "",
+    };
+    let body = format!("{}{}", prefix, digits);
+    // A precision turns off padding with zeros
+    pad(body, spec, prefix.len(), spec.precision.is_none())
+}
+
+/// `1.500000e+00`, as C writes exponents, from Rust's `1.500000e0`.
+fn c_exponent(value: f64, precision: usize, upper: bool) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, sign, exponent.abs())
+}
+
+fn format_float(value: f64, conversion: char, spec: &Spec) -> String {
+    let precision = spec.precision.unwrap_or(6);
+    let upper = conversion.is_ascii_uppercase();
+    let magnitude = value.abs();
+    let mut digits = if !magnitude.is_finite() {
+        let text = if magnitude.is_nan() { "nan" } else { "inf" };
+        if upper {
+            text.to_uppercase()
+        } else {
+            text.to_string()
+        }
+    } else {
+        match conversion {
+            'e' | 'E' => c_exponent(magnitude, precision, upper),
+            'g' | 'G' => {
+                // The shorter of %e and %f, as C chooses it
+                let significant = precision.max(1);
+                let exponent = if magnitude == 0.0 {
+                    0
+                } else {
+                    let rounded = format!("{:.*e}", significant - 1, magnitude);
+                    rounded
+                        .split_once('e')
+                        .map_or(0, |(_, e)| e.parse().unwrap_or(0))
+                };
+                let mut text = if exponent < -4 || exponent >= significant as i32 {
+                    c_exponent(magnitude, significant - 1, upper)
+                } else {
+                    format!(
+                        "{:.*}",
+                        (significant as i32 - 1 - exponent) as usize,
+                        magnitude
+                    )
+                };
+                if !spec.alternate && text.contains('.') {
+                    let (number, exponent) = match text.find(['e', 'E']) {
+                        Some(at) => text.split_at(at),
+                        None => (text.as_str(), ""),
+                    };
+                    let number = number.trim_end_matches('0').trim_end_matches('.');
+                    text = format!("{}{}", number, exponent);
+                }
+                text
+            }
+            _ => format!("{:.*}", precision, magnitude),
+        }
+    };
+    if spec.alternate && precision == 0 && !digits.contains('.') && magnitude.is_finite() {
+        digits.push('.');
+    }
+    let prefix = sign(value.is_sign_negative() && !value.is_nan(), spec);
+    pad(
+        format!("{}{}", prefix, digits),
+        spec,
+        prefix.len(),
+        magnitude.is_finite(),
+    )
+}
+
+/// A string quoted so that the shell reads it back as it is, as `%q` does.
+pub fn shell_quote(text: &str) -> String {
+    if text.is_empty() {
+        return "''".to_string();
+    }
+    if text.chars().any(char::is_control) {
+        let mut quoted = String::from("$'");
+        for c in text.chars() {
+            match c {
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                '\'' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c if c.is_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        return quoted;
+    }
+    let mut quoted = String::new();
+    for c in text.chars() {
+        if !(c.is_alphanumeric() || "_@%+=:,./-".contains(c)) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+/// Formats `args` with `format`, reusing it while arguments remain.
+/// Returns the output and the messages for arguments that were not the
+/// numbers their conversions needed.
+pub fn format(format: &str, args: &[String]) -> Result<(Vec<u8>, Vec<String>), String> {
+    let mut output = Vec::new();
+    let mut args = Arguments {
+        args,
+        next: 0,
+        errors: Vec::new(),
+    };
+    loop {
+        let start = args.next;
+        if format_once(format, &mut args, &mut output)? {
+            break;
+        }
+        // Once more while arguments remain, unless none were used
+        if args.next >= args.args.len() || args.next == start {
+            break;
+        }
+    }
+    Ok((output, args.errors))
+}
+
+/// Goes through the format once. Returns whether a `\c` in a `%b` argument
+/// ended the output.
+fn format_once(format: &str, args: &mut Arguments, output: &mut Vec<u8>) -> Result<bool, String> {
+    let mut rest = format;
+    while !rest.is_empty() {
+        let Some(percent) = rest.find('%') else {
+            output.extend(echo::unescape(rest, Escapes::Format).0);
+            break;
+        };
+        output.extend(echo::unescape(&rest[..percent], Escapes::Format).0);
+        rest = &rest[percent + 1..];
+
+        let mut spec = Spec::default();
+        let mut chars = rest.char_indices().peekable();
+        while let Some((_, flag)) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        let number = |chars: &mut std::iter::Peekable<std::str::CharIndices>,
+                      args: &mut Arguments| {
+            if chars.next_if(|(_, c)| *c == '*').is_some() {
+                return Some(args.count());
+            }
+            let mut value = None;
+            while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                value = Some(value.unwrap_or(0) * 10 + digit.to_digit(10).unwrap_or(0) as usize);
+            }
+            value
+        };
+        spec.width = number(&mut chars, args);
+        if chars.next_if(|(_, c)| *c == '.').is_some() {
+            spec.precision = Some(number(&mut chars, args).unwrap_or(0));
+        }
+        let Some((at, conversion)) = chars.next() else {
+            return Err("missing format character".to_string());
+        };
+        rest = &rest[at + conversion.len_utf8()..];
+
+        let text = match conversion {
+            '%' => "%".to_string(),
+            's' | 'q' | 'c' => {
+                let arg = args.string();
+                let mut text = match conversion {
+                    'q' => shell_quote(arg),
+                    'c' => arg.chars().take(1).collect(),
+                    _ => arg.to_string(),
+                };
+                if let Some(precision) = spec.precision.filter(|_| conversion == 's') {
+                    text = text.chars().take(precision).collect();
+                }
+                pad(text, &spec, 0, false)
+            }
+            'b' => {
+                let (bytes, stopped) = echo::unescape(args.string(), Escapes::Echo);
+                let mut text = String::from_utf8_lossy(&bytes).into_owned();
+                if let Some(precision) = spec.precision {
+                    text = text.chars().take(precision).collect();
+                }
+                output.extend(pad(text, &spec, 0, false).into_bytes());
+                if stopped {
+                    return Ok(true);
+                }
+                continue;
+            }
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => format_integer(args.integer(), conversion, &spec),
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => format_float(args.float(), conversion, &spec),
+            other => return Err(format!("%{}: invalid conversion specification", other)),
+        };
+        output.extend(text.into_bytes());
+    }
+    Ok(false)
+}
+
+/// `printf FORMAT [ARGUMENT]...`
+pub struct Printf;
+
+impl Command for Printf {
+    fn name(&self) -> &'static str {
+        "printf"
+    }
+
+    fn usage(&self) -> &'static str {
+        "printf FORMAT [ARGUMENT]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Print arguments formatted with %s, %d, %x, %f, %b and %q, reusing the format for the rest"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let args = match args.first().map(String::as_str) {
+            Some("--") => &args[1..],
+            _ => args,
+        };
+        let Some((format_string, rest)) = args.split_first() else {
+            return commands::usage_error(stderr, self.usage());
+        };
+        match format(format_string, rest) {
+            Ok((output, errors)) => {
+                let written = stdout.write_all(&output).and_then(|()| stdout.flush());
+                let mut status = commands::exit_code(stderr, self.name(), written);
+                for error in errors {
+                    status = commands::report_error(stderr, self.name(), error);
+                }
+                status
+            }
+            Err(message) => commands::report_error(stderr, self.name(), message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printf(format_string: &str, args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (output, _) = format(format_string, &args).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(printf(r"%s-%s\n", &["a", "b", "c"]), "a-b\nc-\n");
+        assert_eq!(
+            printf("[%5s|%-5s|%.2s]", &["ab", "cd", "xyz"]),
+            "[   ab|cd   |xy]"
+        );
+        assert_eq!(printf("[%*s]", &["4", "x"]), "[   x]");
+        assert_eq!(printf("%b|%s", &[r"a\tb", r"a\tb"]), "a\tb|a\\tb");
+        assert_eq!(printf("%b%s", &[r"x\cy", "z"]), "x");
+        assert_eq!(
+            printf("%q %q %q", &["a b", "", "it's\n"]),
+            r"a\ b '' $'it\'s\n'"
+        );
+        assert_eq!(printf("%c%%", &["hello"]), "h%");
+        assert_eq!(printf("no args\\n", &["ignored"]), "no args\n");
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(
+            printf("%d %i %5d|%-5d|%05d", &["42", "-7", "3", "3", "-3"]),
+            "42 -7     3|3    |-0003"
+        );
+        assert_eq!(
+            printf("%x %X %#x %o %#o", &["255", "255", "255", "8", "8"]),
+            "ff FF 0xff 10 010"
+        );
+        assert_eq!(printf("%d %d %d", &["0x10", "010", "'A"]), "16 8 65");
+        assert_eq!(printf("%+d % d %.3d", &["5", "5", "5"]), "+5  5 005");
+        assert_eq!(printf("%u", &["-1"]), "18446744073709551615");
+        assert_eq!(
+            printf("%f %.2f %8.3f", &["1.5", "2.345", "-3.14159"]),
+            "1.500000 2.35   -3.142"
+        );
+        assert_eq!(
+            printf("%e %.2E", &["12345.678", "0.000123"]),
+            "1.234568e+04 1.23E-04"
+        );
+        assert_eq!(
+            printf("%g %g %g %G", &["100000", "1000000", "0.0001", "1e-5"]),
+            "100000 1e+06 0.0001 1E-05"
+        );
+        assert_eq!(printf("%d", &[]), "0");
+
+        let (_, errors) = format("%d", &["abc".to_string()]).unwrap();
+        assert_eq!(errors, vec!["'abc': expected a numeric value".to_string()]);
+        assert!(format("%z", &[]).is_err());
+        assert!(format("%5", &[]).is_err());
+    }
+}
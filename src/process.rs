@@ -52,6 +52,19 @@ mod windows_process {
         Ok(wide)
     }
 
+    /// Builds the `NAME=value\0...\0` block CreateProcessW takes as an environment.
+    fn environment_block(variables: &[(String, String)]) -> Result<Vec<u16>, ProcessError> {
+        let mut block = Vec::new();
+        for (name, value) in variables {
+            block.extend(to_wide_null(&format!("{}={}", name, value))?);
+        }
+        if block.is_empty() {
+            block.push(0);
+        }
+        block.push(0);
+        Ok(block)
+    }
+
     pub fn spawn(
         exe_path: &str,
         args: &[&str],
@@ -70,6 +83,7 @@ mod windows_process {
         } else {
             None
         };
+        let mut environment = environment_block(&crate::vars::exported())?;
 
         unsafe {
             let mut si: STARTUPINFOW = zeroed();
@@ -83,7 +97,7 @@ mod windows_process {
                 ptr::null_mut(),
                 0,
                 CREATE_UNICODE_ENVIRONMENT,
-                ptr::null_mut(),
+                environment.as_mut_ptr() as *mut _,
                 current_dir_wide
                     .as_ref()
                     .map(|v| v.as_ptr())
@@ -121,6 +135,12 @@ mod windows_process {
             assert!(matches!(result, Err(ProcessError::NullTermination)));
         }
 
+        #[test]
+        fn test_environment_block() {
+            let block = environment_block(&[("A".to_string(), "1".to_string())]).unwrap();
+            assert_eq!(block, vec![65, 61, 49, 0, 0]);
+        }
+
         #[test]
         fn test_spawn_invalid_exe_path() {
             let result = spawn("C:/not_a_real_exe.exe", &[], None);
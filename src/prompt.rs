@@ -0,0 +1,260 @@
+//! The prompt the CLI shows before each command, built from the `PS1`
+//! variable like bash's.
+//!
+//! Besides plain text, `PS1` may hold these escapes:
+//!
+//! | Escape | Expands to |
+//! |--------|------------|
+//! | `\w`, `\W` | the working directory with `~` for home, or just its last part |
+//! | `\u`, `\h`, `\H` | the user name, and the host name up to the first `.` or in full |
+//! | `\t`, `\A` | the time as `HH:MM:SS` or `HH:MM` |
+//! | `\?` | the exit status of the last command |
+//! | `\j` | the number of background jobs |
+//! | `\g` | the git branch, with `*` if there are changes, as ` (main*)`; nothing outside a repository |
+//! | `\m` | the editing mode, `vi` or `emacs`, as chosen with `set -o` |
+//! | `\$` | `#` for root and `$` for everyone else |
+//! | `\e`, `\n`, `\\` | an escape character for colours, a newline, a backslash |
+//!
+//! `\[` and `\]` are accepted and ignored, so prompts written for bash work
+//! unchanged.
+//!
+//! After `set -o vi` the prompt starts with `[vi] `, unless `PS1` shows the
+//! mode itself with `\m`.
+
+use crate::git;
+use crate::localtime;
+use crate::options::{self, ShellOption};
+use crate::vars;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// The prompt when `PS1` is not set.
+pub const DEFAULT_PS1: &str = r"\w\g >> ";
+
+/// The prompt for the further lines of a command, when `PS2` is not set.
+pub const DEFAULT_PS2: &str = "> ";
+
+/// How long the git segment is trusted while neither `HEAD` nor the index
+/// changes. Edits to files do not touch either, so the dirty marker would
+/// otherwise never appear.
+const GIT_CACHE_TIME: Duration = Duration::from_secs(10);
+
+/// What the prompt can show about the shell.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct State {
+    pub status: i32,
+    pub jobs: usize,
+}
+
+/// The prompt for a new command.
+pub fn primary(state: &State) -> String {
+    let template = vars::get("PS1").unwrap_or_else(|| DEFAULT_PS1.to_string());
+    let prompt = render(&template, state);
+    mark_vi_mode(&template, prompt, options::is_set(ShellOption::Vi))
+}
+
+/// Puts `[vi] ` in front of the prompt in vi mode, so that it is clear
+/// which keys are in effect even with a `PS1` that does not use `\m`.
+fn mark_vi_mode(template: &str, prompt: String, vi: bool) -> String {
+    if vi && !template.contains(r"\m") {
+        format!("[vi] {}", prompt)
+    } else {
+        prompt
+    }
+}
+
+/// The prompt for the next line of an incomplete command.
+pub fn continuation(state: &State) -> String {
+    let template = vars::get("PS2").unwrap_or_else(|| DEFAULT_PS2.to_string());
+    render(&template, state)
+}
+
+/// Expands the escapes in a prompt template.
+pub fn render(template: &str, state: &State) -> String {
+    let mut prompt = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('w') => prompt.push_str(&working_directory(false)),
+            Some('W') => prompt.push_str(&working_directory(true)),
+            Some('u') => prompt.push_str(&user()),
+            Some('h') => prompt.push_str(host().split('.').next().unwrap_or_default()),
+            Some('H') => prompt.push_str(host()),
+            Some('t') => prompt.push_str(&time(true)),
+            Some('A') => prompt.push_str(&time(false)),
+            Some('?') => prompt.push_str(&state.status.to_string()),
+            Some('j') => prompt.push_str(&state.jobs.to_string()),
+            Some('g') => prompt.push_str(&git_segment()),
+            Some('m') if options::is_set(ShellOption::Vi) => prompt.push_str("vi"),
+            Some('m') => prompt.push_str("emacs"),
+            Some('$') => prompt.push(if is_root() { '#' } else { '$' }),
+            Some('e') => prompt.push('\x1b'),
+            Some('n') => prompt.push('\n'),
+            Some('\\') => prompt.push('\\'),
+            Some('[' | ']') => {}
+            // Anything else is shown as it was written
+            Some(other) => {
+                prompt.push('\\');
+                prompt.push(other);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
+
+fn working_directory(last_part: bool) -> String {
+    let Ok(cwd) = std::env::current_dir() else {
+        return "?".to_string();
+    };
+    if last_part && cwd.parent().is_some() {
+        return cwd.file_name().map_or_else(
+            || cwd.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+    }
+    match dirs::home_dir() {
+        Some(home) => tilde(&cwd, &home),
+        None => cwd.display().to_string(),
+    }
+}
+
+/// A path with the home directory at its start written as `~`.
+fn tilde(path: &Path, home: &Path) -> String {
+    match path.strip_prefix(home) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) => format!("~{}{}", std::path::MAIN_SEPARATOR, rest.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+fn user() -> String {
+    vars::get("USER")
+        .or_else(|| vars::get("USERNAME"))
+        .unwrap_or_default()
+}
+
+fn host() -> &'static str {
+    static HOST: OnceLock<String> = OnceLock::new();
+    HOST.get_or_init(|| sysinfo::System::host_name().unwrap_or_default())
+}
+
+fn is_root() -> bool {
+    if cfg!(windows) {
+        false
+    } else {
+        vars::get("EUID").or_else(|| vars::get("UID")).as_deref() == Some("0") || user() == "root"
+    }
+}
+
+fn time(seconds: bool) -> String {
+    let now = localtime::now();
+    if seconds {
+        format!("{:02}:{:02}:{:02}", now.hour(), now.minute(), now.second())
+    } else {
+        format!("{:02}:{:02}", now.hour(), now.minute())
+    }
+}
+
+/// The `.git` directory of the repository holding `dir`, if there is one.
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|git_dir| git_dir.exists())
+}
+
+/// The last git segment shown, with what it was worked out from.
+struct GitSegment {
+    cwd: PathBuf,
+    /// When `HEAD` and the index were last changed
+    changed: [Option<SystemTime>; 2],
+    checked: Instant,
+    text: String,
+}
+
+/// ` (BRANCH)`, or ` (BRANCH*)` when there are changes. Running git takes a
+/// while in large repositories, so it is only asked again when the
+/// directory, the branch or the index has changed, or the last answer is
+/// getting old. Outside a repository git is not run at all.
+fn git_segment() -> String {
+    static LAST: Mutex<Option<GitSegment>> = Mutex::new(None);
+
+    let Ok(cwd) = std::env::current_dir() else {
+        return String::new();
+    };
+    let Some(git_dir) = find_git_dir(&cwd) else {
+        return String::new();
+    };
+    let modified = |name: &str| {
+        fs::metadata(git_dir.join(name))
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    let changed = [modified("HEAD"), modified("index")];
+
+    let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(segment) = last.as_ref()
+        && segment.cwd == cwd
+        && segment.changed == changed
+        && segment.checked.elapsed() < GIT_CACHE_TIME
+    {
+        return segment.text.clone();
+    }
+
+    let text = match git::get_current_branch() {
+        Some(branch) => {
+            let dirty = git::get_repo_status().as_deref() == Some("dirty");
+            format!(" ({}{})", branch, if dirty { "*" } else { "" })
+        }
+        None => String::new(),
+    };
+    *last = Some(GitSegment {
+        cwd,
+        changed,
+        checked: Instant::now(),
+        text: text.clone(),
+    });
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let state = State { status: 3, jobs: 2 };
+        assert_eq!(
+            render(r"\[\e[1m\]\?:\j \\ \x\$ ", &state),
+            format!("\x1b[1m3:2 \\ \\x{} ", if is_root() { '#' } else { '$' })
+        );
+        assert_eq!(render(r"\t", &state).len(), 8);
+        assert_eq!(render(r"\A\n", &state).len(), 6);
+        assert_eq!(render("trailing \\", &state), "trailing \\");
+    }
+
+    #[test]
+    fn test_vi_mode_marker() {
+        let prompt = || "~ >> ".to_string();
+        assert_eq!(mark_vi_mode(DEFAULT_PS1, prompt(), true), "[vi] ~ >> ");
+        assert_eq!(mark_vi_mode(DEFAULT_PS1, prompt(), false), "~ >> ");
+        assert_eq!(mark_vi_mode(r"\m \w >> ", prompt(), true), "~ >> ");
+    }
+
+    #[test]
+    fn test_tilde() {
+        let home = Path::new("/home/me");
+        assert_eq!(tilde(Path::new("/home/me"), home), "~");
+        assert_eq!(
+            tilde(Path::new("/home/me/src"), home),
+            format!("~{}src", std::path::MAIN_SEPARATOR)
+        );
+        assert_eq!(tilde(Path::new("/home/meow"), home), "/home/meow");
+    }
+}
@@ -1,20 +1,49 @@
+use crate::commands::{self, Command};
+use std::io::{self, Read, Write};
 use sysinfo::System;
 
-pub fn execute() {
+pub struct Ps;
+
+impl Command for Ps {
+    fn name(&self) -> &'static str {
+        "ps"
+    }
+
+    fn usage(&self) -> &'static str {
+        "ps"
+    }
+
+    fn description(&self) -> &'static str {
+        "List running processes"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
     let mut sys = System::new_all();
     sys.refresh_all();
 
     // Print header
-    println!("{}", "=".repeat(90));
-    println!("{:^90}", "PROCESS LIST");
-    println!("{}", "=".repeat(90));
+    writeln!(out, "{}", "=".repeat(90))?;
+    writeln!(out, "{:^90}", "PROCESS LIST")?;
+    writeln!(out, "{}", "=".repeat(90))?;
 
     // Column headers
-    println!(
+    writeln!(
+        out,
         "{:<8} {:<25} {:<8} {:<10} {:<12} {:<15}",
         "PID", "NAME", "CPU%", "MEMORY", "DISK R/W", "STATUS"
-    );
-    println!("{}", "-".repeat(90));
+    )?;
+    writeln!(out, "{}", "-".repeat(90))?;
 
     // Get processes and sort by CPU usage
     let mut processes: Vec<_> = sys.processes().iter().collect();
@@ -40,24 +69,26 @@ pub fn execute() {
 
         let status = format!("{:?}", process.status());
 
-        println!(
+        writeln!(
+            out,
             "{:<8} {:<25} {:<8} {:<10} {:<12} {:<15}",
             pid, name, cpu, memory, disk_info, status
-        );
+        )?;
     }
 
-    println!("{}", "-".repeat(90));
+    writeln!(out, "{}", "-".repeat(90))?;
 
     // System summary
-    println!("\n{:^40}", "SYSTEM SUMMARY");
-    println!("{}", "-".repeat(40));
-    println!("Total processes: {}", sys.processes().len());
-    println!("CPU cores: {}", sys.cpus().len());
-    println!("Global CPU usage: {:.1}%", sys.global_cpu_usage());
-    println!("Total memory: {}", format_bytes(sys.total_memory()));
-    println!("Used memory: {}", format_bytes(sys.used_memory()));
-    println!("Total swap: {}", format_bytes(sys.total_swap()));
-    println!("Used swap: {}", format_bytes(sys.used_swap()));
+    writeln!(out, "\n{:^40}", "SYSTEM SUMMARY")?;
+    writeln!(out, "{}", "-".repeat(40))?;
+    writeln!(out, "Total processes: {}", sys.processes().len())?;
+    writeln!(out, "CPU cores: {}", sys.cpus().len())?;
+    writeln!(out, "Global CPU usage: {:.1}%", sys.global_cpu_usage())?;
+    writeln!(out, "Total memory: {}", format_bytes(sys.total_memory()))?;
+    writeln!(out, "Used memory: {}", format_bytes(sys.used_memory()))?;
+    writeln!(out, "Total swap: {}", format_bytes(sys.total_swap()))?;
+    writeln!(out, "Used swap: {}", format_bytes(sys.used_swap()))?;
+    Ok(())
 }
 
 // Helper function to format bytes
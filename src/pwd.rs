@@ -0,0 +1,35 @@
+use crate::commands::{self, Command};
+use colored::Colorize;
+use std::env;
+use std::io::{self, Read, Write};
+
+pub struct Pwd;
+
+impl Command for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn usage(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the working directory"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
+    let cwd = env::current_dir()?;
+    writeln!(out, "{}", cwd.display().to_string().bold().cyan())
+}
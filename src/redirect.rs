@@ -0,0 +1,268 @@
+use crate::commands;
+use crate::parser::{Redirect, RedirectOp};
+use crate::pipeline::{self, ByteStream, Stage, StageOutput};
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::process::Stdio;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Where a command's stdin comes from after `<` or `<<<`.
+#[derive(Debug)]
+pub enum Input {
+    File(File),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Input {
+    /// Turns the input into a stream that replaces the stage's piped input.
+    pub fn into_stream(self) -> ByteStream {
+        match self {
+            Input::File(file) => pipeline::reader_stream(tokio::fs::File::from_std(file)),
+            Input::Text(text) => stream::once(async move { Ok(Bytes::from(text)) }).boxed(),
+            Input::Bytes(bytes) => stream::once(async move { Ok(Bytes::from(bytes)) }).boxed(),
+        }
+    }
+}
+
+/// Where a command's stdout or stderr goes. `Stdout` and `Stderr` are the
+/// shell's own streams; inside a pipeline `Stdout` is the pipe to the next stage.
+#[derive(Debug)]
+pub enum Target {
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+impl Target {
+    pub fn try_clone(&self) -> io::Result<Target> {
+        Ok(match self {
+            Target::Stdout => Target::Stdout,
+            Target::Stderr => Target::Stderr,
+            Target::File(file) => Target::File(file.try_clone()?),
+        })
+    }
+
+    /// A writer for the target, with `Stdout` and `Stderr` meaning the process's own streams.
+    pub fn writer(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(match self {
+            Target::Stdout => Box::new(io::stdout()),
+            Target::Stderr => Box::new(io::stderr()),
+            Target::File(file) => Box::new(file.try_clone()?),
+        })
+    }
+
+    /// The target as a child process's stdout or stderr.
+    pub fn stdio(&self) -> io::Result<Stdio> {
+        Ok(match self {
+            Target::Stdout => Stdio::from(io::stdout()),
+            Target::Stderr => Stdio::from(io::stderr()),
+            Target::File(file) => Stdio::from(file.try_clone()?),
+        })
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self, Target::File(_))
+    }
+}
+
+/// The opened redirections of one command.
+#[derive(Debug)]
+pub struct Redirections {
+    pub stdin: Option<Input>,
+    pub stdout: Target,
+    pub stderr: Target,
+}
+
+impl Default for Redirections {
+    fn default() -> Self {
+        Self {
+            stdin: None,
+            stdout: Target::Stdout,
+            stderr: Target::Stderr,
+        }
+    }
+}
+
+impl Redirections {
+    /// Opens the files named by a command's redirections, applying them left
+    /// to right so that `> out.txt 2>&1` sends both streams to the file while
+    /// `2>&1 > out.txt` leaves stderr on the terminal.
+    pub fn open(redirects: &[Redirect]) -> Result<Self, String> {
+        let mut redirections = Self::default();
+
+        for redirect in redirects {
+            let target = redirect.target.text();
+            let open_error = |e: io::Error| format!("{}: {}", target, commands::io_message(&e));
+
+            match (redirect.op, redirect.fd) {
+                (RedirectOp::Input, 0) => {
+                    let file = File::open(&target).map_err(open_error)?;
+                    redirections.stdin = Some(Input::File(file));
+                }
+                (RedirectOp::HereString, 0) => {
+                    redirections.stdin = Some(Input::Text(format!("{}\n", target)));
+                }
+                (RedirectOp::HereDoc, 0) => {
+                    redirections.stdin = Some(Input::Text(target));
+                }
+                (RedirectOp::Output | RedirectOp::Append, fd @ (1 | 2)) => {
+                    let file = OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .append(redirect.op == RedirectOp::Append)
+                        .truncate(redirect.op == RedirectOp::Output)
+                        .open(&target)
+                        .map_err(open_error)?;
+                    *redirections.output_mut(fd) = Target::File(file);
+                }
+                (RedirectOp::DupOutput, fd @ (1 | 2)) => {
+                    let duplicate = match target.as_str() {
+                        "1" => redirections.stdout.try_clone(),
+                        "2" => redirections.stderr.try_clone(),
+                        _ => return Err(format!("{}: bad file descriptor", target)),
+                    }
+                    .map_err(open_error)?;
+                    *redirections.output_mut(fd) = duplicate;
+                }
+                (_, fd) => return Err(format!("{}: bad file descriptor", fd)),
+            }
+        }
+
+        Ok(redirections)
+    }
+
+    fn output_mut(&mut self, fd: u32) -> &mut Target {
+        if fd == 2 {
+            &mut self.stderr
+        } else {
+            &mut self.stdout
+        }
+    }
+}
+
+/// Runs a stage with its stdin, stdout and stderr redirected.
+pub struct RedirectedStage {
+    stage: Box<dyn Stage>,
+    redirections: Redirections,
+}
+
+impl RedirectedStage {
+    pub fn new(stage: Box<dyn Stage>, redirections: Redirections) -> Self {
+        Self {
+            stage,
+            redirections,
+        }
+    }
+}
+
+impl Stage for RedirectedStage {
+    fn run(self: Box<Self>, input: ByteStream) -> StageOutput {
+        let Redirections {
+            stdin,
+            stdout,
+            stderr,
+        } = self.redirections;
+        let mut stage = self.stage;
+
+        stage.redirect_stderr(stderr);
+        let input = stdin.map_or(input, Input::into_stream);
+        let output = stage.run(input);
+
+        let mut sink: Box<dyn AsyncWrite + Unpin + Send> = match stdout {
+            Target::Stdout => return output,
+            Target::Stderr => Box::new(tokio::io::stderr()),
+            Target::File(file) => Box::new(tokio::fs::File::from_std(file)),
+        };
+
+        // The output goes to the sink, so the next stage reads nothing
+        let StageOutput {
+            stream: mut source,
+            status,
+        } = output;
+        let stream = stream::once(async move {
+            let mut error = None;
+            while let Some(chunk) = source.next().await {
+                let written = match chunk {
+                    Ok(chunk) => sink.write_all(&chunk).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = written {
+                    error.get_or_insert(e);
+                }
+            }
+            if let Err(e) = sink.flush().await {
+                error.get_or_insert(e);
+            }
+            error
+        })
+        .filter_map(|error| async move { error.map(Err) })
+        .boxed();
+
+        StageOutput { stream, status }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use crate::pipeline::{parse_stage, run_stages};
+
+    fn redirects(line: &str) -> Vec<Redirect> {
+        let parsed = parser::parse(line).unwrap();
+        match &parsed.items[0].and_or.first.commands[0] {
+            parser::Command::Simple(command) => command.redirects.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_redirections_apply_in_order() {
+        let path = "test_redirect_order.txt";
+
+        let redirections = Redirections::open(&redirects(&format!("ps > {} 2>&1", path))).unwrap();
+        assert!(redirections.stdout.is_file());
+        assert!(redirections.stderr.is_file());
+
+        let redirections = Redirections::open(&redirects(&format!("ps 2>&1 > {}", path))).unwrap();
+        assert!(redirections.stdout.is_file());
+        assert!(matches!(redirections.stderr, Target::Stdout));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_redirection_errors() {
+        let err = Redirections::open(&redirects("cat < test_redirect_missing.txt")).unwrap_err();
+        assert_eq!(err, "test_redirect_missing.txt: No such file or directory");
+
+        let err = Redirections::open(&redirects("ls 3> out.txt")).unwrap_err();
+        assert_eq!(err, "3: bad file descriptor");
+    }
+
+    #[tokio::test]
+    async fn test_redirected_stage() {
+        let path = "test_redirect_stage.txt";
+        std::fs::write(path, "old contents\n").unwrap();
+
+        let argv = vec!["grep".to_string(), "b".to_string()];
+        let redirections =
+            Redirections::open(&redirects(&format!("grep b <<< 'a b c' >> {}", path))).unwrap();
+        let stage = RedirectedStage::new(parse_stage(&argv).unwrap(), redirections);
+
+        let mut output = Vec::new();
+        let statuses = run_stages(vec![Box::new(stage)], &mut output).await;
+
+        assert!(output.is_empty());
+        assert!(statuses[0].success());
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "old contents\na b c\n"
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
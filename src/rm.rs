@@ -1,22 +1,499 @@
+//! `rm`, which removes files and, with `-r`, directories, or moves them to
+//! the trash with `--trash`.
+//!
+//! Like GNU `rm`, it refuses to remove `/`, and beyond that the home
+//! directory and any directory holding the working directory;
+//! `--no-preserve-root` lifts these guards.
+
+use crate::commands::{self, Command};
+use crate::trash;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path};
 
-pub fn rm<S: AsRef<Path>>(files: Vec<S>) -> io::Result<()> {
-    for file_path in files {
-        let path = file_path.as_ref();
+/// When to ask before removing.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Prompt {
+    #[default]
+    Never,
+    /// `-I`: once, before removing more than three files or recursively
+    Once,
+    /// `-i`: before every removal
+    Always,
+}
 
-        if path.exists() {
-            if path.is_file() {
-                fs::remove_file(path)?;
-                println!("Removed file: {}", path.display());
-            } else {
-                eprintln!("Warning: '{}' is not a file", path.display());
+/// How `rm` goes about removing.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// `-r`, `-R`
+    pub recursive: bool,
+    /// `-f`: no complaint about missing files
+    pub force: bool,
+    pub prompt: Prompt,
+    /// `-v`
+    pub verbose: bool,
+    /// `--trash`: move to the trash rather than delete
+    pub trash: bool,
+    /// Cleared by `--no-preserve-root`
+    pub preserve_root: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            recursive: false,
+            force: false,
+            prompt: Prompt::Never,
+            verbose: false,
+            trash: false,
+            preserve_root: true,
+        }
+    }
+}
+
+/// Why a directory must not be removed, if it is one of those guarded.
+fn guarded(path: &Path) -> Option<String> {
+    if matches!(
+        path.components().next_back(),
+        Some(Component::CurDir | Component::ParentDir)
+    ) {
+        return Some(format!(
+            "refusing to remove '.' or '..' directory: skipping '{}'",
+            path.display()
+        ));
+    }
+    let path = fs::canonicalize(path).ok()?;
+    if path.parent().is_none() {
+        return Some(format!(
+            "it is dangerous to operate recursively on '{}' (use --no-preserve-root to override)",
+            path.display()
+        ));
+    }
+    if dirs::home_dir().and_then(|home| fs::canonicalize(home).ok()) == Some(path.clone()) {
+        return Some(format!(
+            "refusing to remove the home directory '{}' (use --no-preserve-root to override)",
+            path.display()
+        ));
+    }
+    if std::env::current_dir().is_ok_and(|cwd| cwd.starts_with(&path)) {
+        return Some(format!(
+            "refusing to remove '{}': it holds the working directory",
+            path.display()
+        ));
+    }
+    None
+}
+
+/// What a prompt calls a file: `regular file`, `directory` and so on.
+fn describe(metadata: &fs::Metadata) -> &'static str {
+    if metadata.is_dir() {
+        "directory"
+    } else if metadata.file_type().is_symlink() {
+        "symbolic link"
+    } else if metadata.len() == 0 {
+        "regular empty file"
+    } else {
+        "regular file"
+    }
+}
+
+/// One run of `rm`, with the streams it prompts and reports on.
+struct Remover<'a> {
+    options: &'a Options,
+    stdin: &'a mut dyn Read,
+    stdout: &'a mut dyn Write,
+    stderr: &'a mut dyn Write,
+    status: i32,
+}
+
+impl Remover<'_> {
+    fn fail(&mut self, message: String) -> bool {
+        self.status = commands::report_error(self.stderr, "rm", message);
+        false
+    }
+
+    fn cannot_remove(&mut self, path: &Path, error: &io::Error) -> bool {
+        let message = format!(
+            "cannot remove '{}': {}",
+            path.display(),
+            commands::io_message(error)
+        );
+        self.fail(message)
+    }
+
+    /// Asks a question and reads the answer a line from the input. Only an
+    /// answer starting with `y` is a yes.
+    fn ask(&mut self, question: String) -> bool {
+        let _ = write!(self.stderr, "rm: {} ", question);
+        let _ = self.stderr.flush();
+        // A byte at a time, so that no later answers are read ahead
+        let mut answer = Vec::new();
+        let mut byte = [0];
+        while let Ok(1) = self.stdin.read(&mut byte) {
+            if byte[0] == b'\n' {
+                break;
+            }
+            answer.push(byte[0]);
+        }
+        matches!(answer.trim_ascii_start().first(), Some(b'y' | b'Y'))
+    }
+
+    fn report(&mut self, message: String) {
+        if self.options.verbose {
+            let _ = writeln!(self.stdout, "{}", message);
+        }
+    }
+
+    fn remove_operand(&mut self, operand: &str) {
+        let path = Path::new(operand);
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && self.options.force => return,
+            Err(e) => {
+                self.cannot_remove(path, &e);
+                return;
+            }
+        };
+        if metadata.is_dir() {
+            if !self.options.recursive {
+                self.fail(format!("cannot remove '{}': Is a directory", operand));
+                return;
             }
+            if self.options.preserve_root
+                && let Some(reason) = guarded(path)
+            {
+                self.fail(reason);
+                return;
+            }
+        }
+
+        if self.options.trash {
+            let question = format!("move {} '{}' to the trash?", describe(&metadata), operand);
+            if self.options.prompt == Prompt::Always && !self.ask(question) {
+                return;
+            }
+            match trash::put(path) {
+                Ok(_) => self.report(format!("trashed '{}'", operand)),
+                Err(e) => {
+                    let message = format!(
+                        "cannot move '{}' to the trash: {}",
+                        operand,
+                        commands::io_message(&e)
+                    );
+                    self.fail(message);
+                }
+            }
+        } else if metadata.is_dir() {
+            self.remove_tree(path);
         } else {
-            eprintln!("Warning: File '{}' not found", path.display());
+            self.remove_file(path, &metadata);
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path, metadata: &fs::Metadata) -> bool {
+        let question = format!("remove {} '{}'?", describe(metadata), path.display());
+        if self.options.prompt == Prompt::Always && !self.ask(question) {
+            return false;
+        }
+        match fs::remove_file(path) {
+            Ok(()) => {
+                self.report(format!("removed '{}'", path.display()));
+                true
+            }
+            Err(e) => self.cannot_remove(path, &e),
+        }
+    }
+
+    /// Removes a directory and everything in it, returning whether it is
+    /// gone. It stays when anything in it does.
+    fn remove_tree(&mut self, path: &Path) -> bool {
+        let always = self.options.prompt == Prompt::Always;
+        if always && !self.ask(format!("descend into directory '{}'?", path.display())) {
+            return false;
+        }
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => return self.cannot_remove(path, &e),
+        };
+        let mut emptied = true;
+        for entry in entries {
+            let child = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => return self.cannot_remove(path, &e),
+            };
+            emptied &= match fs::symlink_metadata(&child) {
+                Ok(metadata) if metadata.is_dir() => self.remove_tree(&child),
+                Ok(metadata) => self.remove_file(&child, &metadata),
+                Err(e) => self.cannot_remove(&child, &e),
+            };
         }
+        if !emptied || (always && !self.ask(format!("remove directory '{}'?", path.display()))) {
+            return false;
+        }
+        match fs::remove_dir(path) {
+            Ok(()) => {
+                self.report(format!("removed directory '{}'", path.display()));
+                true
+            }
+            Err(e) => self.cannot_remove(path, &e),
+        }
+    }
+}
+
+/// Removes each of `operands` as `options` says, reading answers to prompts
+/// from `stdin`. Returns the exit status.
+pub fn remove(
+    operands: &[String],
+    options: &Options,
+    stdin: &mut dyn Read,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> i32 {
+    let mut remover = Remover {
+        options,
+        stdin,
+        stdout,
+        stderr,
+        status: 0,
+    };
+    if options.prompt == Prompt::Once && (operands.len() > 3 || options.recursive) {
+        let question = format!(
+            "remove {} argument{}{}?",
+            operands.len(),
+            if operands.len() == 1 { "" } else { "s" },
+            if options.recursive {
+                " recursively"
+            } else {
+                ""
+            }
+        );
+        if !remover.ask(question) {
+            return 0;
+        }
+    }
+    for operand in operands {
+        remover.remove_operand(operand);
+    }
+    remover.status
+}
+
+/// `rm [-fiIrRv] [--trash] [--no-preserve-root] FILE...`
+pub struct Rm;
+
+impl Rm {
+    /// Sorts the arguments into options and the paths to remove, which go in
+    /// `operands`. Of `-f`, `-i` and `-I`, whichever comes last decides when
+    /// to ask; an unknown option gives `None`.
+    fn parse_args(args: &[String], operands: &mut Vec<String>) -> Option<Options> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(args.cloned());
+                    break;
+                }
+                "--recursive" => options.recursive = true,
+                "--force" => {
+                    options.force = true;
+                    options.prompt = Prompt::Never;
+                }
+                "--interactive" | "--interactive=always" => options.prompt = Prompt::Always,
+                "--interactive=once" => options.prompt = Prompt::Once,
+                "--interactive=never" => options.prompt = Prompt::Never,
+                "--verbose" => options.verbose = true,
+                "--trash" => options.trash = true,
+                "--preserve-root" => options.preserve_root = true,
+                "--no-preserve-root" => options.preserve_root = false,
+                flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                    for flag in flags[1..].chars() {
+                        // Whichever of -f, -i and -I comes last wins
+                        match flag {
+                            'r' | 'R' => options.recursive = true,
+                            'f' => {
+                                options.force = true;
+                                options.prompt = Prompt::Never;
+                            }
+                            'i' => {
+                                options.force = false;
+                                options.prompt = Prompt::Always;
+                            }
+                            'I' => {
+                                options.force = false;
+                                options.prompt = Prompt::Once;
+                            }
+                            'v' => options.verbose = true,
+                            _ => return None,
+                        }
+                    }
+                }
+                _ if arg.starts_with("--") => return None,
+                _ => operands.push(arg.clone()),
+            }
+        }
+        Some(options)
     }
-    Ok(())
 }
 
+impl Command for Rm {
+    fn name(&self) -> &'static str {
+        "rm"
+    }
+
+    fn usage(&self) -> &'static str {
+        "rm [-fiIrRv] [--trash] [--no-preserve-root] FILE..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove files, or directories with -r; --trash moves them to the trash instead"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let mut operands = Vec::new();
+        let Some(options) = Self::parse_args(args, &mut operands) else {
+            return commands::usage_error(stderr, self.usage());
+        };
+        if operands.is_empty() {
+            // Nothing to do is fine when told not to complain
+            return if options.force {
+                0
+            } else {
+                commands::usage_error(stderr, self.usage())
+            };
+        }
+        remove(&operands, &options, stdin, stdout, stderr)
+    }
+}
+
+/// Removes files, but not directories, without asking.
+pub fn rm<S: AsRef<Path>>(files: Vec<S>) -> io::Result<()> {
+    let operands: Vec<String> = files
+        .iter()
+        .map(|file| file.as_ref().to_string_lossy().into_owned())
+        .collect();
+    let mut errors = Vec::new();
+    let status = remove(
+        &operands,
+        &Options::default(),
+        &mut io::empty(),
+        &mut io::sink(),
+        &mut errors,
+    );
+    if status == 0 {
+        Ok(())
+    } else {
+        let errors = String::from_utf8_lossy(&errors);
+        let last = errors.lines().last().unwrap_or_default();
+        Err(io::Error::other(
+            last.trim_start_matches("rm: ").to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str], answers: &str) -> (i32, String, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        let code = Rm.run(&args, &mut answers.as_bytes(), &mut output, &mut errors);
+        (
+            code,
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_recursive() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        fs::write(tree.join("sub/file"), "x").unwrap();
+        let tree_arg = tree.to_string_lossy();
+
+        let (code, _, errors) = run(&[&tree_arg], "");
+        assert_eq!(code, 1);
+        assert!(errors.contains("Is a directory"));
+
+        let (code, output, _) = run(&["-rv", &tree_arg], "");
+        assert_eq!(code, 0);
+        assert!(!tree.exists());
+        assert_eq!(
+            output,
+            format!(
+                "removed '{0}/sub/file'\nremoved directory '{0}/sub'\nremoved directory '{0}'\n",
+                tree_arg
+            )
+        );
+    }
+
+    #[test]
+    fn test_force_and_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing").to_string_lossy().into_owned();
+        let (code, _, errors) = run(&[&missing], "");
+        assert_eq!(code, 1);
+        assert!(errors.contains("No such file or directory"), "{}", errors);
+        assert_eq!(
+            run(&["-f", &missing], ""),
+            (0, String::new(), String::new())
+        );
+        assert_eq!(run(&["-f"], "").0, 0);
+        assert_eq!(run(&[], "").0, 2);
+        assert_eq!(run(&["-x", &missing], "").0, 2);
+    }
+
+    #[test]
+    fn test_prompts() {
+        let dir = tempfile::tempdir().unwrap();
+        let keep = dir.path().join("keep");
+        let gone = dir.path().join("gone");
+        fs::write(&keep, "").unwrap();
+        fs::write(&gone, "").unwrap();
+
+        let (code, _, errors) = run(
+            &["-i", &keep.to_string_lossy(), &gone.to_string_lossy()],
+            "n\n yes\n",
+        );
+        assert_eq!(code, 0);
+        assert!(keep.exists() && !gone.exists());
+        assert!(errors.starts_with("rm: remove regular empty file '"));
+
+        // -I asks once, and a no leaves everything
+        let operands = ["a", "b", "c", "d"].map(|name| dir.path().join(name));
+        for path in &operands {
+            fs::write(path, "").unwrap();
+        }
+        let mut args: Vec<String> = vec!["-I".to_string()];
+        args.extend(
+            operands
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned()),
+        );
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let (_, _, errors) = run(&args, "n\n");
+        assert_eq!(errors, "rm: remove 4 arguments? ");
+        assert!(operands.iter().all(|path| path.exists()));
+        run(&args, "y\n");
+        assert!(operands.iter().all(|path| !path.exists()));
+    }
+
+    #[test]
+    fn test_guards() {
+        let (code, _, errors) = run(&["-rf", "."], "");
+        assert_eq!(code, 1);
+        assert!(errors.contains("refusing to remove '.' or '..'"));
+        assert!(guarded(Path::new("/")).unwrap().contains("dangerous"));
+        let cwd = std::env::current_dir().unwrap();
+        assert!(guarded(&cwd).unwrap().contains("working directory"));
+        assert_eq!(guarded(Path::new("/no/such/dir")), None);
+    }
+}
@@ -1,27 +1,61 @@
+use crate::commands::{self, Command};
 use colored::Colorize;
+use std::io::{self, Read, Write};
 use sysinfo::Components;
 
-pub fn execute() {
-    println!("{}", "System Component Temperatures:".bold().blue());
-    println!("{}", "=".repeat(50));
+pub struct Sensors;
+
+impl Command for Sensors {
+    fn name(&self) -> &'static str {
+        "sensors"
+    }
+
+    fn usage(&self) -> &'static str {
+        "sensors"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show temperature sensors"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{}", "System Component Temperatures:".bold().blue())?;
+    writeln!(out, "{}", "=".repeat(50))?;
 
     let mut components = Components::new_with_refreshed_list();
 
     components.refresh(false);
 
     if components.is_empty() {
-        println!("{}", "No temperature sensors found or accessible.".yellow());
-        println!(
+        writeln!(
+            out,
+            "{}",
+            "No temperature sensors found or accessible.".yellow()
+        )?;
+        writeln!(
+            out,
             "{}",
             "Note: On Windows, temperature sensors may require:".dimmed()
-        );
-        println!("{}", "  - Administrator privileges".dimmed());
-        println!(
+        )?;
+        writeln!(out, "{}", "  - Administrator privileges".dimmed())?;
+        writeln!(
+            out,
             "{}",
             "  - Hardware that supports temperature monitoring".dimmed()
-        );
-        println!("{}", "  - Proper drivers installed".dimmed());
-        return;
+        )?;
+        writeln!(out, "{}", "  - Proper drivers installed".dimmed())?;
+        return Ok(());
     }
 
     let mut sensor_count = 0;
@@ -31,52 +65,59 @@ pub fn execute() {
         let max_temp = component.max();
         let critical_temp = component.critical();
 
-        if let Some(temp) = temperature {
-            if temp > 0.0 {
-                sensor_count += 1;
-                print!("{}: ", label.bold());
-
-                let temp_str = format!("{:.1}°C", temp);
-                if let Some(crit) = critical_temp {
-                    if temp >= crit {
-                        print!("{}", temp_str.red().bold());
-                    } else if temp >= crit * 0.8 {
-                        print!("{}", temp_str.yellow());
-                    } else {
-                        print!("{}", temp_str.green());
-                    }
-                } else {
-                    print!("{}", temp_str.cyan());
-                }
+        if let Some(temp) = temperature
+            && temp > 0.0
+        {
+            sensor_count += 1;
+            write!(out, "{}: ", label.bold())?;
 
-                if let Some(max) = max_temp {
-                    if max > 0.0 {
-                        print!(" {}", format!("(Max: {:.1}°C)", max).dimmed());
-                    }
+            let temp_str = format!("{:.1}°C", temp);
+            if let Some(crit) = critical_temp {
+                if temp >= crit {
+                    write!(out, "{}", temp_str.red().bold())?;
+                } else if temp >= crit * 0.8 {
+                    write!(out, "{}", temp_str.yellow())?;
+                } else {
+                    write!(out, "{}", temp_str.green())?;
                 }
+            } else {
+                write!(out, "{}", temp_str.cyan())?;
+            }
 
-                if let Some(crit) = critical_temp {
-                    if crit > 0.0 {
-                        print!(" {}", format!("[Critical: {:.1}°C]", crit).red().dimmed());
-                    }
-                }
+            if let Some(max) = max_temp
+                && max > 0.0
+            {
+                write!(out, " {}", format!("(Max: {:.1}°C)", max).dimmed())?;
+            }
 
-                println!();
+            if let Some(crit) = critical_temp
+                && crit > 0.0
+            {
+                write!(
+                    out,
+                    " {}",
+                    format!("[Critical: {:.1}°C]", crit).red().dimmed()
+                )?;
             }
+
+            writeln!(out)?;
         }
     }
 
     if sensor_count == 0 {
-        println!("{}", "No valid temperature data available.".yellow());
-        println!(
+        writeln!(out, "{}", "No valid temperature data available.".yellow())?;
+        writeln!(
+            out,
             "{}",
             "This may be normal on Windows systems without accessible sensors.".dimmed()
-        );
+        )?;
     } else {
-        println!("{}", "=".repeat(50));
-        println!(
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(
+            out,
             "{}",
             format!("Found {} temperature sensor(s)", sensor_count).green()
-        );
+        )?;
     }
+    Ok(())
 }
@@ -0,0 +1,1444 @@
+//! Runs parsed command lines for both the CLI and the TUI, dispatching
+//! every command through the registry in [`crate::commands`].
+
+use crate::alias;
+use crate::commands::{self, Command};
+use crate::external;
+use crate::glob;
+use crate::jobs::{self, JobTable, State};
+use crate::options::{self, ShellOption};
+use crate::parser::{
+    self, AndOrList, CaseArm, CommandLine, Connector, Expand, Parameter, Redirect, SimpleCommand,
+    Word, WordPart,
+};
+use crate::pipeline::{self, Stage};
+use crate::powershell;
+use crate::redirect::{Input, RedirectedStage, Redirections, Target};
+use crate::vars;
+use colored::Colorize;
+use futures::stream::{self, StreamExt};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+use tempfile::TempPath;
+
+/// Where a front end shows the output of commands.
+#[derive(Debug, Clone)]
+pub enum Console {
+    /// The process's own stdout and stderr, as in the CLI.
+    Terminal,
+    /// A buffer collecting both streams, as in the TUI's output pane.
+    Capture(SharedBuffer),
+    /// A buffer collecting stdout, with stderr left to the enclosing console,
+    /// as for the commands of a `$(...)`.
+    Substitution(SharedBuffer, Box<Console>),
+}
+
+impl Console {
+    /// A writer for `target`, resolving the standard streams to this console.
+    fn writer(&self, target: &Target) -> io::Result<Box<dyn Write + Send>> {
+        match (self, target) {
+            (Console::Capture(buffer), Target::Stdout | Target::Stderr)
+            | (Console::Substitution(buffer, _), Target::Stdout) => Ok(Box::new(buffer.clone())),
+            (Console::Substitution(_, outer), Target::Stderr) => outer.writer(target),
+            _ => target.writer(),
+        }
+    }
+
+    /// The buffer that output goes to, unless it goes to the terminal.
+    fn buffer(&self) -> Option<&SharedBuffer> {
+        match self {
+            Console::Terminal => None,
+            Console::Capture(buffer) | Console::Substitution(buffer, _) => Some(buffer),
+        }
+    }
+}
+
+/// An in-memory output buffer that can be written to through several handles.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Removes and returns everything written so far.
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Exit status of a command line that fails to parse, as in POSIX shells.
+pub const SYNTAX_ERROR_STATUS: i32 = 2;
+
+/// Built-ins the shell runs itself because they need its state, rather than
+/// through the command registry.
+pub const SPECIAL_BUILTINS: [&str; 12] = [
+    "exit", "quit", "return", "break", "continue", "source", ".", "jobs", "fg", "bg", "wait",
+    "disown",
+];
+
+/// A pending `break`, `continue` or `return`, which stops the lists being
+/// run until the loop or function it belongs to is reached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    /// Leave this many enclosing loops
+    Break(usize),
+    /// Leave this many enclosing loops, less one which goes on to its next round
+    Continue(usize),
+    Return,
+}
+
+/// A function defined with `NAME() COMMAND`.
+#[derive(Debug, Clone)]
+struct Function {
+    body: parser::Command,
+    /// The definition as it was written, which background jobs run to
+    /// define the function in their own process
+    text: String,
+}
+
+/// The command interpreter shared by the CLI and the TUI.
+#[derive(Debug)]
+pub struct Shell {
+    console: Console,
+    /// The exit status of the last pipeline, which `$?` expands to.
+    last_status: i32,
+    exit_status: Option<i32>,
+    /// `$1`, `$2` and so on, from the script's or the current function's arguments
+    positional: Vec<String>,
+    functions: HashMap<String, Function>,
+    flow: Option<Flow>,
+    /// How many loops enclose the running command in the current function
+    loop_depth: usize,
+    function_depth: usize,
+    /// How many `if` or `while` conditions are running, where `set -e` is ignored
+    condition_depth: usize,
+    /// The status of the last `$(...)` run while expanding the current command
+    substitution_status: Cell<Option<i32>>,
+    /// Files holding the output of `<(...)`, removed once their command is done
+    output_files: RefCell<Vec<TempPath>>,
+    jobs: JobTable,
+    /// The process ID of the last background job, which `$!` expands to
+    last_job: Option<u32>,
+}
+
+impl Shell {
+    pub fn new(console: Console) -> Self {
+        Self {
+            console,
+            last_status: 0,
+            exit_status: None,
+            positional: Vec::new(),
+            functions: HashMap::new(),
+            flow: None,
+            loop_depth: 0,
+            function_depth: 0,
+            condition_depth: 0,
+            substitution_status: Cell::new(None),
+            output_files: RefCell::new(Vec::new()),
+            jobs: JobTable::default(),
+            last_job: None,
+        }
+    }
+
+    /// Sets the positional parameters, as for the arguments of a script.
+    pub fn set_positional(&mut self, args: Vec<String>) {
+        self.positional = args;
+    }
+
+    /// The exit status of the last pipeline that ran.
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    /// Parses and runs a command line, returning the exit status of the last
+    /// pipeline that ran.
+    pub fn run_line(&mut self, line: &str) -> i32 {
+        let command_line = match parser::parse_with_aliases(line, &alias::get) {
+            Ok(command_line) => command_line,
+            Err(e) => {
+                self.error(&e.render(line));
+                self.last_status = SYNTAX_ERROR_STATUS;
+                return SYNTAX_ERROR_STATUS;
+            }
+        };
+
+        self.run_list(&command_line)
+    }
+
+    /// Runs each and-or list in turn, returning the status of the last one.
+    fn run_list(&mut self, list: &CommandLine) -> i32 {
+        for item in &list.items {
+            if self.exit_status.is_some() || self.flow.is_some() {
+                break;
+            }
+            if item.background {
+                self.last_status = self.start_job(&item.and_or);
+                continue;
+            }
+
+            self.last_status = self.run_pipeline(&item.and_or.first);
+            // `set -e` ignores failures followed by `&&` or `||`
+            let mut checked = item.and_or.rest.is_empty();
+            for (index, (connector, pipeline)) in item.and_or.rest.iter().enumerate() {
+                let should_run = match connector {
+                    Connector::And => self.last_status == 0,
+                    Connector::Or => self.last_status != 0,
+                };
+                if should_run && self.exit_status.is_none() && self.flow.is_none() {
+                    self.last_status = self.run_pipeline(pipeline);
+                    checked = index + 1 == item.and_or.rest.len();
+                }
+            }
+
+            if checked
+                && self.last_status != 0
+                && self.exit_status.is_none()
+                && self.condition_depth == 0
+                && options::is_set(ShellOption::ErrExit)
+            {
+                self.exit_status = Some(self.last_status);
+            }
+        }
+        self.last_status
+    }
+
+    /// Runs a script line by line until it ends or exits, returning its exit
+    /// status. Lines ending in a backslash continue on the next line, and a
+    /// compound command or quote left open runs on until it is complete.
+    pub fn run_script(&mut self, script: &str) -> i32 {
+        let mut pending = String::new();
+        for line in script.lines() {
+            // `\\` at the end is an escaped backslash, not a continuation
+            let trailing_backslashes = line.len() - line.trim_end_matches('\\').len();
+            if trailing_backslashes % 2 == 1 {
+                pending.push_str(&line[..line.len() - 1]);
+                continue;
+            }
+            pending.push_str(line);
+            if is_incomplete(&pending) {
+                pending.push('\n');
+                continue;
+            }
+            self.run_line(&std::mem::take(&mut pending));
+            if self.exit_status.is_some() {
+                break;
+            }
+        }
+        if !pending.trim().is_empty() && self.exit_status.is_none() {
+            self.run_line(&pending);
+        }
+        self.exit_status.unwrap_or(self.last_status)
+    }
+
+    /// The number of background jobs, including finished ones not yet reported.
+    pub fn job_count(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// The names of the functions defined so far.
+    pub fn function_names(&self) -> impl Iterator<Item = &String> {
+        self.functions.keys()
+    }
+
+    /// Runs `~/.winixrc` if there is one, as the CLI and the TUI do when they
+    /// start so that everyone can keep their aliases and variables there.
+    pub fn source_rc(&mut self) {
+        if let Some(path) = rc_path()
+            && path.is_file()
+        {
+            self.source(&[path.display().to_string()]);
+        }
+    }
+
+    /// `source FILE [ARG]...`, running a script in this shell so that its
+    /// variables, aliases and functions stay defined. Any arguments become
+    /// the positional parameters while it runs.
+    fn source(&mut self, args: &[String]) -> i32 {
+        let Some(path) = args.first() else {
+            self.error("Usage: source FILE [ARG]...");
+            return SYNTAX_ERROR_STATUS;
+        };
+        let script = match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                self.error(&format!("source: {}: {}", path, commands::io_message(&e)));
+                return 1;
+            }
+        };
+
+        let positional =
+            (args.len() > 1).then(|| std::mem::replace(&mut self.positional, args[1..].to_vec()));
+        let status = self.run_script(&script);
+        if let Some(positional) = positional {
+            self.positional = positional;
+        }
+        status
+    }
+
+    /// The status the shell was asked to exit with by `exit` or `set -e`.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    fn run_pipeline(&mut self, pipeline: &parser::Pipeline) -> i32 {
+        let output_files = self.output_files.borrow().len();
+        let status = self.run_pipeline_commands(pipeline);
+        self.output_files.borrow_mut().truncate(output_files);
+        status
+    }
+
+    fn run_pipeline_commands(&mut self, pipeline: &parser::Pipeline) -> i32 {
+        if let [command] = &pipeline.commands[..] {
+            return self.run_command(command);
+        }
+
+        let mut commands = Vec::new();
+        for command in &pipeline.commands {
+            match command {
+                parser::Command::Simple(command) => {
+                    let command = self.expand(command);
+                    // Functions run in the shell itself, which the stages cannot share
+                    if let Some(name) = command.argv().first()
+                        && self.functions.contains_key(name)
+                    {
+                        self.error(&format!(
+                            "winix: {}: functions cannot be used in pipelines yet",
+                            name
+                        ));
+                        return 2;
+                    }
+                    commands.push(command);
+                }
+                _ => {
+                    self.error("winix: compound commands cannot be used in pipelines yet");
+                    return 2;
+                }
+            }
+        }
+        if commands.iter().any(|c| c.words.is_empty()) {
+            self.error("winix: empty command in pipeline");
+            return 1;
+        }
+        self.run_streaming(&commands)
+    }
+
+    fn run_command(&mut self, command: &parser::Command) -> i32 {
+        match command {
+            parser::Command::Simple(command) => {
+                self.substitution_status.set(None);
+                if !command.words.is_empty()
+                    && let Some(assignments) = command
+                        .words
+                        .iter()
+                        .map(Word::assignment)
+                        .collect::<Option<Vec<_>>>()
+                {
+                    return self.assign(&assignments);
+                }
+                let command = self.expand(command);
+                self.run_simple(&command)
+            }
+            parser::Command::If {
+                branches,
+                otherwise,
+            } => {
+                for (condition, body) in branches {
+                    let status = self.run_condition(condition);
+                    if self.exit_status.is_some() || self.flow.is_some() {
+                        return status;
+                    }
+                    if status == 0 {
+                        return self.run_list(body);
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.run_list(body),
+                    None => 0,
+                }
+            }
+            parser::Command::For { name, words, body } => {
+                let values: Vec<String> = match words {
+                    Some(words) => self.expand_words(words).iter().map(Word::text).collect(),
+                    None => self.positional.clone(),
+                };
+                self.loop_depth += 1;
+                let mut status = 0;
+                for value in values {
+                    vars::set(name, &value);
+                    status = self.run_list(body);
+                    if self.end_of_round() {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            parser::Command::While {
+                condition,
+                body,
+                until,
+            } => {
+                self.loop_depth += 1;
+                let mut status = 0;
+                loop {
+                    let tested = self.run_condition(condition);
+                    if self.flow.is_some() || self.exit_status.is_some() {
+                        if self.end_of_round() {
+                            break;
+                        }
+                        continue;
+                    }
+                    if (tested == 0) == *until {
+                        break;
+                    }
+                    status = self.run_list(body);
+                    if self.end_of_round() {
+                        break;
+                    }
+                }
+                self.loop_depth -= 1;
+                status
+            }
+            parser::Command::Case { word, arms } => self.run_case(word, arms),
+            parser::Command::Group(body) => self.run_list(body),
+            parser::Command::Function { name, body, text } => {
+                let function = Function {
+                    body: (**body).clone(),
+                    text: text.clone(),
+                };
+                self.functions.insert(name.clone(), function);
+                0
+            }
+        }
+    }
+
+    /// Runs the condition of an `if` or a loop, where `set -e` does not apply.
+    fn run_condition(&mut self, condition: &CommandLine) -> i32 {
+        self.condition_depth += 1;
+        let status = self.run_list(condition);
+        self.condition_depth -= 1;
+        status
+    }
+
+    /// Called after each round of a loop to say whether the loop is over,
+    /// taking up any `break` or `continue` aimed at it.
+    fn end_of_round(&mut self) -> bool {
+        match self.flow {
+            Some(Flow::Break(count)) => {
+                self.flow = (count > 1).then(|| Flow::Break(count - 1));
+                true
+            }
+            Some(Flow::Continue(count)) if count > 1 => {
+                self.flow = Some(Flow::Continue(count - 1));
+                true
+            }
+            Some(Flow::Continue(_)) => {
+                self.flow = None;
+                false
+            }
+            Some(Flow::Return) => true,
+            None => self.exit_status.is_some(),
+        }
+    }
+
+    /// Runs the first arm with a pattern matching the word, or nothing.
+    fn run_case(&mut self, word: &Word, arms: &[CaseArm]) -> i32 {
+        let text = word.expand(self);
+        let arm = arms.iter().find(|arm| {
+            arm.patterns
+                .iter()
+                .any(|pattern| glob::matches(&pattern.expand_chars(self), &text, false))
+        });
+        match arm {
+            Some(arm) if !arm.body.items.is_empty() => self.run_list(&arm.body),
+            _ => 0,
+        }
+    }
+
+    /// Runs a function with its arguments as the positional parameters.
+    fn call_function(&mut self, body: &parser::Command, args: &[String]) -> i32 {
+        let positional = std::mem::replace(&mut self.positional, args.to_vec());
+        // Loops around the call cannot be left from inside the function
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.function_depth += 1;
+
+        let status = self.run_command(body);
+
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        self.positional = positional;
+        if self.flow == Some(Flow::Return) {
+            self.flow = None;
+        }
+        status
+    }
+
+    fn run_simple(&mut self, command: &SimpleCommand) -> i32 {
+        // With no command to run, the status is that of any `$(...)`
+        let substituted = self.substitution_status.take().unwrap_or(0);
+        if command.words.is_empty() {
+            return substituted;
+        }
+        let argv = command.argv();
+        match argv[0].as_str() {
+            "exit" | "quit" => return self.exit(&argv[1..]),
+            "return" => return self.return_from_function(&argv[1..]),
+            "break" | "continue" => return self.leave_loop(&argv[0], &argv[1..]),
+            "source" | "." => return self.source(&argv[1..]),
+            "jobs" | "fg" | "bg" | "wait" | "disown" => {
+                return self.job_control(&argv[0], &argv[1..]);
+            }
+            _ => {}
+        }
+        if let Some(Function { body, .. }) = self.functions.get(&argv[0]).cloned() {
+            if !command.redirects.is_empty() {
+                self.error("winix: redirecting the output of functions is not supported yet");
+                return 1;
+            }
+            return self.call_function(&body, &argv[1..]);
+        }
+        let (builtin, args) = match commands::registry().get(&argv[0]) {
+            Some(builtin) => (builtin, &argv[1..]),
+            None => match powershell_fallback(&argv[0]) {
+                // Cmdlets such as Get-Process run through `psh`
+                Some(powershell) => (powershell, &argv[..]),
+                None => return self.run_program(command, &argv),
+            },
+        };
+
+        if command.redirects.is_empty()
+            && matches!(self.console, Console::Terminal)
+            && let Some(code) = builtin.run_in_terminal(args)
+        {
+            return code;
+        }
+        if builtin.stage(args).is_some() {
+            return self.run_streaming(std::slice::from_ref(command));
+        }
+        match Redirections::open(&command.redirects) {
+            Ok(redirections) => self.run_builtin(builtin, args, redirections),
+            Err(e) => self.redirect_error(e),
+        }
+    }
+
+    /// Sets each variable of a command made up only of `NAME=value` words.
+    /// Only words written that way count, so a quoted `"A=b"` or a `$X`
+    /// holding `A=b` is run as a command instead.
+    fn assign(&mut self, assignments: &[(&str, Word)]) -> i32 {
+        for (name, value) in assignments {
+            vars::set(name, &value.expand(self));
+        }
+        // The status is that of any `$(...)` among the values
+        self.substitution_status.take().unwrap_or(0)
+    }
+
+    /// The value of a parameter: a special one such as `$?` or `$1`, or else
+    /// a variable.
+    fn lookup(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status.to_string()),
+            "!" => self.last_job.map(|pid| pid.to_string()),
+            "#" => Some(self.positional.len().to_string()),
+            "@" | "*" => Some(self.positional.join(" ")),
+            _ => match name.parse::<usize>() {
+                Ok(0) => Some("winix".to_string()),
+                Ok(index) => self.positional.get(index - 1).cloned(),
+                Err(_) => vars::get(name),
+            },
+        }
+    }
+
+    /// Expands the parameters in a command's words and redirection targets,
+    /// then braces and globs in its words.
+    fn expand(&self, command: &SimpleCommand) -> SimpleCommand {
+        let expand_word = |word: &Word| Word::quoted(word.expand(self));
+
+        SimpleCommand {
+            words: self.expand_words(&command.words),
+            redirects: command
+                .redirects
+                .iter()
+                .map(|redirect| Redirect {
+                    target: expand_word(&redirect.target),
+                    ..redirect.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// Expands the parameters, substitutions, braces and globs in a list of
+    /// words. Unquoted words that expand to nothing are dropped, `"$@"`
+    /// becomes one word per positional parameter, and an unquoted `$(...)`
+    /// standing alone is split into words at whitespace, as in other shells.
+    fn expand_words(&self, words: &[Word]) -> Vec<Word> {
+        let is_quoted = |word: &Word| {
+            word.parts.iter().any(|part| {
+                !matches!(
+                    part,
+                    WordPart::Literal(_)
+                        | WordPart::Parameter(_)
+                        | WordPart::CommandSubstitution(_)
+                )
+            })
+        };
+        let is_all_arguments = |word: &Word| {
+            let all = |part: &WordPart| matches!(part, WordPart::Parameter(Parameter { name, default: None }) if name == "@");
+            match &word.parts[..] {
+                [part] if all(part) => true,
+                [WordPart::DoubleQuoted(parts)] => matches!(&parts[..], [part] if all(part)),
+                _ => false,
+            }
+        };
+
+        let mut expanded = Vec::new();
+        for word in words {
+            if is_all_arguments(word) {
+                expanded.extend(self.positional.iter().cloned().map(Word::quoted));
+                continue;
+            }
+            if let [WordPart::CommandSubstitution(source)] = &word.parts[..] {
+                for field in self.command_output(source).split_whitespace() {
+                    let chars: Vec<(char, bool)> = field.chars().map(|c| (c, false)).collect();
+                    expanded.extend(glob::expand(&chars).into_iter().map(Word::quoted));
+                }
+                continue;
+            }
+            let chars = word.expand_chars(self);
+            if chars.is_empty() && !is_quoted(word) {
+                continue;
+            }
+            expanded.extend(glob::expand(&chars).into_iter().map(Word::quoted));
+        }
+        expanded
+    }
+
+    /// A copy of the shell with its parameters and functions but no jobs, for
+    /// running commands apart from it.
+    fn subshell(&self, console: Console) -> Shell {
+        let mut subshell = Shell::new(console);
+        subshell.last_status = self.last_status;
+        subshell.positional = self.positional.clone();
+        subshell.functions = self.functions.clone();
+        subshell
+    }
+
+    /// Starts an and-or list running in the background as a new job, in a
+    /// winix process of its own that is given this shell's variables,
+    /// aliases, options, functions and arguments first.
+    fn start_job(&mut self, and_or: &AndOrList) -> i32 {
+        let program = match env::current_exe() {
+            Ok(program) => program,
+            Err(e) => {
+                self.error(&format!("winix: cannot start a job: {}", e));
+                return 1;
+            }
+        };
+        let mut script = String::new();
+        // Exported variables reach it through its environment
+        for (name, value) in vars::unexported() {
+            script.push_str(&format!("{}={}\n", name, vars::quote(&value)));
+        }
+        for (name, value) in alias::all() {
+            script.push_str(&alias::definition(&name, &value));
+            script.push('\n');
+        }
+        for function in self.functions.values() {
+            script.push_str(&function.text);
+            script.push('\n');
+        }
+        for option in ShellOption::ALL {
+            let flag = if options::is_set(option) { "-s" } else { "-u" };
+            script.push_str(&format!("shopt {} {}\n", flag, option.name()));
+        }
+        script.push_str(&and_or.text);
+
+        let mut command = process::Command::new(program);
+        command.arg("-c").arg(script).args(&self.positional);
+        vars::apply(&mut command);
+        let output = match self.console {
+            Console::Terminal => None,
+            _ => self
+                .console
+                .writer(&Target::Stdout)
+                .and_then(|stdout| Ok((stdout, self.console.writer(&Target::Stderr)?)))
+                .ok(),
+        };
+        match self.jobs.start(and_or.text.clone(), command, output) {
+            Ok(id) => {
+                let index = self.jobs.len() - 1;
+                self.last_job = Some(self.jobs.get_mut(index).pid());
+                self.notice(&format!("[{}] {}", id, and_or.text));
+                0
+            }
+            Err(e) => {
+                self.error(&format!("winix: cannot start a job: {}", e));
+                1
+            }
+        }
+    }
+
+    /// Announces the background jobs that have finished since the last call,
+    /// as the CLI does before each prompt.
+    pub fn report_finished_jobs(&mut self) {
+        for line in self.jobs.take_finished() {
+            self.notice(&line);
+        }
+    }
+
+    /// The `jobs`, `fg`, `bg`, `wait` and `disown` built-ins, which take job
+    /// specs such as `%1`.
+    fn job_control(&mut self, name: &str, args: &[String]) -> i32 {
+        let specs: Vec<Option<&str>> = if args.is_empty() {
+            vec![None]
+        } else {
+            args.iter().map(|arg| Some(arg.as_str())).collect()
+        };
+        let mut status = 0;
+
+        match name {
+            "jobs" if args.is_empty() => {
+                for line in self.jobs.describe_all() {
+                    self.print(&line);
+                }
+            }
+            "jobs" => {
+                let mut indices = Vec::new();
+                for spec in specs {
+                    match self.jobs.find(spec) {
+                        Ok(index) => indices.push(index),
+                        Err(e) => {
+                            self.error(&format!("jobs: {}", e));
+                            status = 1;
+                        }
+                    }
+                }
+                for line in self.jobs.describe_jobs(&indices) {
+                    self.print(&line);
+                }
+            }
+            "wait" if args.is_empty() => {
+                // Stopped jobs would never finish, so they are left alone
+                let mut index = 0;
+                while index < self.jobs.len() {
+                    let job = self.jobs.get_mut(index);
+                    if job.state() == State::Stopped || job.wait() == State::Stopped {
+                        index += 1;
+                    } else {
+                        self.jobs.remove(index);
+                    }
+                }
+            }
+            "disown" if args.first().is_some_and(|arg| arg == "-a") => self.jobs.clear(),
+            _ => {
+                for spec in specs {
+                    let index = match self.jobs.find(spec) {
+                        Ok(index) => index,
+                        Err(e) => {
+                            self.error(&format!("{}: {}", name, e));
+                            status = if name == "wait" {
+                                external::NOT_FOUND_STATUS
+                            } else {
+                                1
+                            };
+                            continue;
+                        }
+                    };
+                    status = match name {
+                        "fg" => {
+                            let text = self.jobs.get_mut(index).text.clone();
+                            self.print(&text);
+                            let job = self.jobs.get_mut(index);
+                            job.resume();
+                            let state = match self.console {
+                                Console::Terminal => job.wait_in_foreground(),
+                                _ => job.wait(),
+                            };
+                            self.finish_waiting(index, state)
+                        }
+                        "wait" => {
+                            let job = self.jobs.get_mut(index);
+                            let state = match job.state() {
+                                State::Stopped => State::Stopped,
+                                _ => job.wait(),
+                            };
+                            self.finish_waiting(index, state)
+                        }
+                        "bg" => {
+                            let marker = self.jobs.marker(index);
+                            let job = self.jobs.get_mut(index);
+                            let message = if job.state() == State::Stopped {
+                                job.resume();
+                                format!("[{}]{} {} &", job.id, marker, job.text)
+                            } else {
+                                format!("bg: job {} already in background", job.id)
+                            };
+                            self.notice(&message);
+                            0
+                        }
+                        // The job keeps running, but the shell forgets it
+                        _ => {
+                            self.jobs.remove(index).resume();
+                            0
+                        }
+                    };
+                }
+            }
+        }
+        status
+    }
+
+    /// The status of a job that `fg` or `wait` waited for, which leaves the
+    /// table if it finished and is announced if it was stopped instead.
+    fn finish_waiting(&mut self, index: usize, state: State) -> i32 {
+        match state {
+            State::Done(code) => {
+                self.jobs.remove(index);
+                code
+            }
+            _ => {
+                let marker = self.jobs.marker(index);
+                let line = self.jobs.get_mut(index).describe(marker);
+                self.notice(&line);
+                jobs::STOPPED_STATUS
+            }
+        }
+    }
+
+    /// Runs the commands of a `$(...)` or `<(...)` in a copy of the shell,
+    /// returning what they wrote to stdout. Like a subshell in other shells,
+    /// a `cd`, `exit`, assignment, alias or option among them does not
+    /// affect this shell.
+    fn capture_output(&self, source: &str) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let console = Console::Substitution(buffer.clone(), Box::new(self.console.clone()));
+        let mut subshell = self.subshell(console);
+
+        let cwd = env::current_dir();
+        let (variables, aliases, shell_options) = (vars::save(), alias::save(), options::save());
+        let status = subshell.run_script(source);
+        vars::restore(variables);
+        alias::restore(aliases);
+        options::restore(shell_options);
+        if let Ok(cwd) = cwd {
+            let _ = env::set_current_dir(cwd);
+        }
+        self.substitution_status.set(Some(status));
+        buffer.take()
+    }
+
+    /// `exit [N]`, leaving the shell with status N or else the last status.
+    fn exit(&mut self, args: &[String]) -> i32 {
+        let status = match args.first().map(|arg| arg.parse::<i32>()) {
+            None => self.last_status,
+            Some(Ok(status)) => status,
+            Some(Err(_)) => {
+                self.error(&format!("exit: {}: numeric argument required", args[0]));
+                SYNTAX_ERROR_STATUS
+            }
+        };
+        self.exit_status = Some(status);
+        status
+    }
+
+    /// `return [N]`, leaving the current function with status N or else the
+    /// last status.
+    fn return_from_function(&mut self, args: &[String]) -> i32 {
+        if self.function_depth == 0 {
+            self.error("return: can only be used in a function");
+            return 1;
+        }
+        let status = match args.first().map(|arg| arg.parse::<i32>()) {
+            None => self.last_status,
+            Some(Ok(status)) => status,
+            Some(Err(_)) => {
+                self.error(&format!("return: {}: numeric argument required", args[0]));
+                SYNTAX_ERROR_STATUS
+            }
+        };
+        self.flow = Some(Flow::Return);
+        status
+    }
+
+    /// `break [N]` and `continue [N]`, for the Nth enclosing loop.
+    fn leave_loop(&mut self, name: &str, args: &[String]) -> i32 {
+        let count = match args.first().map(|arg| arg.parse::<usize>()) {
+            None => 1,
+            Some(Ok(count)) if count > 0 => count,
+            Some(_) => {
+                self.error(&format!("{}: {}: loop count out of range", name, args[0]));
+                return 1;
+            }
+        };
+        if self.loop_depth == 0 {
+            self.error(&format!(
+                "{}: only meaningful in a for, while or until loop",
+                name
+            ));
+            return 0;
+        }
+        let count = count.min(self.loop_depth);
+        self.flow = Some(if name == "break" {
+            Flow::Break(count)
+        } else {
+            Flow::Continue(count)
+        });
+        0
+    }
+
+    /// Runs a built-in in the shell itself, with its streams redirected.
+    fn run_builtin(
+        &mut self,
+        builtin: &dyn Command,
+        args: &[String],
+        redirections: Redirections,
+    ) -> i32 {
+        let (mut stdout, mut stderr) = match (
+            self.console.writer(&redirections.stdout),
+            self.console.writer(&redirections.stderr),
+        ) {
+            (Ok(stdout), Ok(stderr)) => (stdout, stderr),
+            (Err(e), _) | (_, Err(e)) => {
+                self.error(&format!("winix: {}", e));
+                return 1;
+            }
+        };
+        let mut stdin: Box<dyn Read> = match (redirections.stdin, &self.console) {
+            (Some(Input::File(file)), _) => Box::new(file),
+            (Some(Input::Text(text)), _) => Box::new(Cursor::new(text.into_bytes())),
+            (Some(Input::Bytes(bytes)), _) => Box::new(Cursor::new(bytes)),
+            (None, Console::Terminal) => Box::new(io::stdin()),
+            (None, Console::Capture(_) | Console::Substitution(..)) => Box::new(io::empty()),
+        };
+
+        // Escape codes would end up in the file
+        let plain = matches!(self.console, Console::Terminal)
+            && (redirections.stdout.is_file() || redirections.stderr.is_file());
+        if plain {
+            colored::control::set_override(false);
+        }
+        let code = builtin.run(args, &mut stdin, &mut stdout, &mut stderr);
+        if plain {
+            colored::control::unset_override();
+        }
+        let _ = stdout.flush();
+        code
+    }
+
+    /// Runs a program from the PATH in the foreground.
+    fn run_program(&mut self, command: &SimpleCommand, argv: &[String]) -> i32 {
+        if external::find_program(&argv[0]).is_none() {
+            self.error(&format!("Unknown command: '{}'", argv[0]));
+            self.error("Type 'help' for available commands");
+            return external::NOT_FOUND_STATUS;
+        }
+        // The TUI has no terminal to hand over, so it captures the output instead
+        if self.console.buffer().is_some() {
+            return self.run_streaming(std::slice::from_ref(command));
+        }
+
+        let redirections = match Redirections::open(&command.redirects) {
+            Ok(redirections) => redirections,
+            Err(e) => return self.redirect_error(e),
+        };
+        match external::run_foreground(&argv[0], &argv[1..], redirections) {
+            Ok(code) => code,
+            Err(e) => {
+                self.error(&format!("{}: {}", argv[0], e));
+                1
+            }
+        }
+    }
+
+    /// Runs every command of a pipeline concurrently, streaming bytes between them.
+    fn run_streaming(&mut self, commands: &[SimpleCommand]) -> i32 {
+        let mut stages: Vec<Box<dyn Stage>> = Vec::new();
+        let mut error_targets = Vec::new();
+        let mut input = stream::empty().boxed();
+        let argvs: Vec<Vec<String>> = commands.iter().map(|c| c.argv()).collect();
+
+        for (index, (command, argv)) in commands.iter().zip(&argvs).enumerate() {
+            let last = index + 1 == commands.len();
+            let mut stage = match parse_stage(argv) {
+                Ok(stage) => stage,
+                Err(e) => {
+                    // Bad arguments are a usage error, as when the command runs alone
+                    self.error(&e);
+                    return 2;
+                }
+            };
+            let opened = self
+                .stage_redirections(command, last)
+                .and_then(|redirections| {
+                    let error_target =
+                        redirections.stderr.try_clone().map_err(|e| e.to_string())?;
+                    Ok((redirections, error_target))
+                });
+            match opened {
+                Ok((redirections, error_target)) => {
+                    // The first stage reads the shell's stdin, as it would alone
+                    if index == 0
+                        && redirections.stdin.is_none()
+                        && matches!(self.console, Console::Terminal)
+                        && !stage.inherit_stdin()
+                    {
+                        input = pipeline::stdin_stream();
+                    }
+                    stages.push(Box::new(RedirectedStage::new(stage, redirections)));
+                    error_targets.push(error_target);
+                }
+                Err(e) => {
+                    self.error(&e);
+                    return 1;
+                }
+            }
+        }
+
+        let results = match self.console.buffer() {
+            None => pipeline::runtime().block_on(pipeline::run_stages_from(
+                input,
+                stages,
+                &mut io::stdout(),
+            )),
+            Some(buffer) => {
+                let mut buffer = buffer.clone();
+                pipeline::runtime().block_on(pipeline::run_stages_from(input, stages, &mut buffer))
+            }
+        };
+
+        // Like other shells, the pipeline's status is its last stage's
+        let mut status = 0;
+        for ((argv, result), target) in argvs.iter().zip(results).zip(&error_targets) {
+            if let Some(e) = &result.error {
+                let message = format!("{}: {}", argv[0], e);
+                if let Ok(mut writer) = self.console.writer(target) {
+                    let _ = match target {
+                        Target::File(_) => writeln!(writer, "{}", message),
+                        _ => writeln!(writer, "{}", message.red()),
+                    };
+                }
+            }
+            status = result.code;
+        }
+        status
+    }
+
+    /// Opens the redirections of one pipeline stage. In the TUI, output sent
+    /// to stderr shows in the output pane, which the last stage writes to.
+    fn stage_redirections(
+        &self,
+        command: &SimpleCommand,
+        last: bool,
+    ) -> Result<Redirections, String> {
+        let mut redirections =
+            Redirections::open(&command.redirects).map_err(|e| format!("winix: {}", e))?;
+        if let Console::Capture(_) = self.console {
+            if let Target::Stderr = redirections.stdout {
+                redirections.stdout = Target::Stdout;
+            }
+            if last
+                && matches!(redirections.stdout, Target::Stdout)
+                && matches!(redirections.stderr, Target::Stderr)
+            {
+                redirections.stderr = Target::Stdout;
+            }
+        }
+        Ok(redirections)
+    }
+
+    fn redirect_error(&self, error: String) -> i32 {
+        self.error(&format!("winix: {}", error));
+        1
+    }
+
+    /// Writes a line to the console's stdout.
+    fn print(&self, message: &str) {
+        if let Ok(mut stdout) = self.console.writer(&Target::Stdout) {
+            let _ = writeln!(stdout, "{}", message);
+        }
+    }
+
+    /// Writes an uncoloured message about jobs to the console's stderr.
+    fn notice(&self, message: &str) {
+        if let Ok(mut stderr) = self.console.writer(&Target::Stderr) {
+            let _ = writeln!(stderr, "{}", message);
+        }
+    }
+
+    /// Reports a shell error on the console's stderr.
+    fn error(&self, message: &str) {
+        if let Ok(mut stderr) = self.console.writer(&Target::Stderr) {
+            let _ = writeln!(stderr, "{}", message.red());
+        }
+    }
+}
+
+/// Whether a command line stops partway through a command, as inside quotes
+/// or a here-document, after `|` or `&&`, or before a compound command is
+/// closed, so that it goes on on the next line.
+pub fn is_incomplete(line: &str) -> bool {
+    parser::parse_with_aliases(line, &alias::get).is_err_and(|e| e.is_incomplete())
+}
+
+/// The startup file, `~/.winixrc`.
+pub fn rc_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".winixrc"))
+}
+
+impl Expand for Shell {
+    fn parameter(&self, name: &str) -> Option<String> {
+        self.lookup(name)
+    }
+
+    fn command_output(&self, source: &str) -> String {
+        let output = self.capture_output(source);
+        String::from_utf8_lossy(&output)
+            .trim_end_matches(['\n', '\r'])
+            .to_string()
+    }
+
+    fn output_file(&self, source: &str) -> String {
+        let output = self.capture_output(source);
+        let written = tempfile::NamedTempFile::new().and_then(|mut file| {
+            file.write_all(&output)?;
+            Ok(file.into_temp_path())
+        });
+        match written {
+            Ok(path) => {
+                let name = path.display().to_string();
+                self.output_files.borrow_mut().push(path);
+                name
+            }
+            Err(e) => {
+                self.error(&format!(
+                    "winix: cannot save the output of <({}): {}",
+                    source, e
+                ));
+                String::new()
+            }
+        }
+    }
+}
+
+/// The `psh` command, for names that are neither built-ins nor programs on
+/// the PATH when PowerShell is installed.
+fn powershell_fallback(name: &str) -> Option<&'static dyn Command> {
+    if external::find_program(name).is_some() || !powershell::is_powershell_available() {
+        return None;
+    }
+    commands::registry().get("psh")
+}
+
+/// Like [`pipeline::parse_stage`], but sends PowerShell cmdlets to `psh`.
+fn parse_stage(argv: &[String]) -> Result<Box<dyn Stage>, String> {
+    if commands::registry().get(&argv[0]).is_none() && powershell_fallback(&argv[0]).is_some() {
+        return Ok(Box::new(powershell::stage(argv)));
+    }
+    pipeline::parse_stage(argv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> (i32, String) {
+        let output = SharedBuffer::default();
+        let mut shell = Shell::new(Console::Capture(output.clone()));
+        let status = shell.run_line(line);
+        (status, String::from_utf8(output.take()).unwrap())
+    }
+
+    #[test]
+    fn test_builtin_in_pipeline() {
+        let (status, output) = run("echo one two | grep two");
+        assert_eq!(status, 0);
+        assert_eq!(output, "one two\n");
+    }
+
+    #[test]
+    fn test_builtin_output_redirected() {
+        let path = "test_shell_redirect.txt";
+        let (status, output) = run(&format!("echo saved > {}", path));
+
+        assert_eq!(status, 0);
+        assert!(output.is_empty());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "saved\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let output = SharedBuffer::default();
+        let mut shell = Shell::new(Console::Capture(output.clone()));
+
+        assert_eq!(shell.run_line("cd test_shell_missing_dir || echo $?"), 0);
+        assert!(String::from_utf8(output.take()).unwrap().ends_with("1\n"));
+
+        assert_eq!(shell.run_line("echo | grep x && echo matched"), 1);
+        assert!(output.take().is_empty());
+        assert_eq!(shell.last_status(), 1);
+
+        assert_eq!(shell.run_line("echo a | grep '['"), 2);
+        assert!(
+            String::from_utf8(output.take())
+                .unwrap()
+                .starts_with("grep: ")
+        );
+
+        shell.run_line("winix-no-such-program; echo \"status $?\"");
+        assert!(
+            String::from_utf8(output.take())
+                .unwrap()
+                .ends_with("status 127\n")
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let output = SharedBuffer::default();
+        let mut shell = Shell::new(Console::Capture(output.clone()));
+
+        shell.run_line("WINIX_SHELL_GREETING='hi there'");
+        shell.run_line("echo $WINIX_SHELL_GREETING ${WINIX_SHELL_UNSET:-friend}");
+        assert_eq!(
+            String::from_utf8(output.take()).unwrap(),
+            "hi there friend\n"
+        );
+
+        // Only exported variables reach child processes
+        shell.run_line("env | grep WINIX_SHELL_GREETING");
+        assert_eq!(String::from_utf8(output.take()).unwrap(), "");
+        shell.run_line("export WINIX_SHELL_GREETING && env | grep WINIX_SHELL_GREETING");
+        assert_eq!(
+            String::from_utf8(output.take()).unwrap(),
+            "WINIX_SHELL_GREETING=hi there\n"
+        );
+
+        shell.run_line("unset WINIX_SHELL_GREETING; echo $WINIX_SHELL_GREETING done");
+        assert_eq!(String::from_utf8(output.take()).unwrap(), "done\n");
+    }
+
+    #[test]
+    fn test_assignments_are_written_unquoted() {
+        let (status, output) =
+            run("WINIX_SHELL_A=1 WINIX_SHELL_B=\"$WINIX_SHELL_A 2\"; echo $WINIX_SHELL_B");
+        assert_eq!((status, output.as_str()), (0, "1 2\n"));
+
+        // A quoted or expanded `NAME=value` is a command name
+        let (status, _) = run("\"WINIX_SHELL_C=1\"");
+        assert_eq!(status, 127);
+        let (_, output) = run("echo ${WINIX_SHELL_C:-unset}");
+        assert_eq!(output, "unset\n");
+        let (status, _) = run("WINIX_SHELL_D=WINIX_SHELL_E=1; $WINIX_SHELL_D");
+        assert_eq!(status, 127);
+        let (_, output) = run("echo ${WINIX_SHELL_E:-unset}");
+        assert_eq!(output, "unset\n");
+    }
+
+    #[test]
+    fn test_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.tmp", "b.tmp", "c.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let dir = dir.path().display().to_string().replace('\\', "/");
+
+        let (_, output) = run(&format!("echo {0}/*.tmp '{0}/*.tmp'", dir));
+        assert_eq!(output, format!("{0}/a.tmp {0}/b.tmp {0}/*.tmp\n", dir));
+
+        let (_, output) = run(&format!("echo {}/{{c,d}}.t?t x{{1,2}}", dir));
+        assert_eq!(output, format!("{0}/c.txt {0}/d.t?t x1 x2\n", dir));
+
+        let (_, output) = run(&format!(
+            "shopt -s nullglob; echo {}/*.none end; shopt -u nullglob",
+            dir
+        ));
+        assert_eq!(output, "end\n");
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let (status, output) =
+            run("if false; then echo a; elif true; then echo b; else echo c; fi");
+        assert_eq!((status, output.as_str()), (0, "b\n"));
+        let (status, _) = run("test 1 -eq 2 || [ -n a -a ! -z a ]");
+        assert_eq!(status, 0);
+        let (status, _) = run("[ a = b");
+        assert_eq!(status, 2);
+
+        let (_, output) = run(
+            "for x in 1 2 3 4; do if [ $x = 2 ]; then continue; fi; echo $x; if [ $x = 3 ]; then break; fi; done",
+        );
+        assert_eq!(output, "1\n3\n");
+
+        let (_, output) = run(
+            "WINIX_SHELL_N=; while [ \"$WINIX_SHELL_N\" != xxx ]; do WINIX_SHELL_N=x$WINIX_SHELL_N; echo -; done",
+        );
+        assert_eq!(output, "-\n-\n-\n");
+
+        let (_, output) = run(
+            "for f in notes.txt run.sh other; do case $f in *.txt|*.md) echo doc;; *.sh) echo script;; *) echo $f;; esac; done",
+        );
+        assert_eq!(output, "doc\nscript\nother\n");
+
+        let (status, output) = run("break; echo after");
+        assert_eq!(status, 0);
+        assert!(output.contains("only meaningful in a for, while or until loop"));
+        assert!(output.ends_with("after\n"));
+    }
+
+    #[test]
+    fn test_functions() {
+        let output = SharedBuffer::default();
+        let mut shell = Shell::new(Console::Capture(output.clone()));
+        shell.set_positional(vec!["outer".to_string()]);
+
+        shell.run_line(
+            "show() { echo \"$# [$1] [$2]\"; for arg in \"$@\"; do echo \"<$arg>\"; done; }",
+        );
+        shell.run_line("show 'a b' c; echo \" $1\"");
+        assert_eq!(
+            String::from_utf8(output.take()).unwrap(),
+            "2 [a b] [c]\n<a b>\n<c>\n outer\n"
+        );
+
+        shell.run_line(
+            "check() { if [ \"$1\" = ok ]; then return 0; fi; return 4; echo unreachable; }",
+        );
+        assert_eq!(shell.run_line("check ok"), 0);
+        assert_eq!(shell.run_line("check bad"), 4);
+        assert!(output.take().is_empty());
+
+        assert_eq!(shell.run_line("check ok | grep x"), 2);
+        assert!(
+            String::from_utf8(output.take())
+                .unwrap()
+                .contains("check: functions cannot be used in pipelines")
+        );
+        assert_eq!(shell.run_line("{ echo x; } | grep x"), 2);
+        assert!(
+            String::from_utf8(output.take())
+                .unwrap()
+                .contains("compound commands cannot be used in pipelines")
+        );
+
+        assert_eq!(shell.run_line("return 1"), 1);
+        assert!(
+            String::from_utf8(output.take())
+                .unwrap()
+                .contains("only be used in a function")
+        );
+    }
+
+    #[test]
+    fn test_substitutions() {
+        let (status, output) = run("echo \"[$(echo a b | grep a)]\" $(echo c   d) x$(true)y");
+        assert_eq!(status, 0);
+        assert_eq!(output, "[a b] c d xy\n");
+
+        let (status, _) = run("WINIX_SHELL_SUBST=$(false)");
+        assert_eq!(status, 1);
+        // Variables see the output, and stderr is not captured
+        let (_, output) =
+            run("WINIX_SHELL_SUBST=$(cat test_shell_missing.txt); echo \"[$WINIX_SHELL_SUBST]\"");
+        assert!(output.ends_with("[]\n"));
+        assert!(output.contains("test_shell_missing.txt"));
+
+        // Nothing the commands change outlives them
+        let (_, output) = run(
+            "echo \"$(WINIX_SHELL_Y=5; alias winix_zz=ls; set -e; cd ..)\"; echo ${WINIX_SHELL_Y:-unset}",
+        );
+        assert_eq!(output, "\nunset\n");
+        assert_eq!(alias::get("winix_zz"), None);
+        assert!(!options::is_set(ShellOption::ErrExit));
+
+        let (status, output) = run("cat <(echo from a file); echo <(echo)");
+        assert_eq!(status, 0);
+        let (text, path) = output.split_at("from a file\n".len());
+        assert_eq!(text, "from a file\n");
+        assert!(!std::path::Path::new(path).exists());
+    }
+
+    #[test]
+    fn test_here_documents() {
+        vars::set("WINIX_SHELL_HEREDOC", "there");
+        let (status, output) =
+            run("cat <<EOF | grep e\nhi $WINIX_SHELL_HEREDOC\nskip\n$(echo bye)\nEOF\necho done");
+        assert_eq!(status, 0);
+        assert_eq!(output, "hi there\nbye\ndone\n");
+
+        let (_, output) = run("cat <<-'END'\n\t$WINIX_SHELL_HEREDOC\n\tEND\n");
+        assert_eq!(output, "$WINIX_SHELL_HEREDOC\n");
+
+        assert!(is_incomplete("cat <<EOF\nhi"));
+        assert!(is_incomplete("greet() {\necho hi"));
+        assert!(is_incomplete("echo a |"));
+        assert!(!is_incomplete("greet() {\necho hi\n}"));
+    }
+
+    #[test]
+    fn test_script_continuations() {
+        let output = SharedBuffer::default();
+        let mut shell = Shell::new(Console::Capture(output.clone()));
+        shell.run_script("echo a \\\n  b\necho c\\\\\necho d\\\\\\\ne\n");
+        assert_eq!(
+            String::from_utf8(output.take()).unwrap(),
+            "a b\nc\\\nd\\e\n"
+        );
+    }
+
+    #[test]
+    fn test_aliases_and_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("rc");
+        std::fs::write(
+            &script,
+            "alias winix_greet='echo hello |'\nwinix_twice() {\n  echo \"$1$1\"\n}\n",
+        )
+        .unwrap();
+
+        let output = SharedBuffer::default();
+        let mut shell = Shell::new(Console::Capture(output.clone()));
+        assert_eq!(shell.run_line(&format!("source {}", script.display())), 0);
+        assert_eq!(shell.run_line("winix_greet grep hell; winix_twice ab"), 0);
+        assert_eq!(String::from_utf8(output.take()).unwrap(), "hello\nabab\n");
+
+        // Like in other shells, aliases take effect from the next line
+        shell.run_line("unalias winix_greet");
+        shell.run_line("winix_greet");
+        assert!(
+            String::from_utf8(output.take())
+                .unwrap()
+                .contains("Unknown command: 'winix_greet'")
+        );
+        assert_eq!(shell.run_line(". test_shell_missing.rc"), 1);
+    }
+
+    #[test]
+    fn test_errors_shown_in_capture() {
+        let (status, output) = run("cd");
+        assert_eq!(status, 2);
+        assert!(output.contains("Usage: cd <directory>"));
+
+        let (status, output) = run("echo 'unterminated");
+        assert_eq!(status, SYNTAX_ERROR_STATUS);
+        assert!(output.contains("syntax error"));
+    }
+}
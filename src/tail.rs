@@ -1,9 +1,12 @@
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Read, Write};
 use std::path::Path;
 use tokio::fs::File as TokioFile;
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use futures::stream::{self, Stream, StreamExt};
 use bytes::Bytes;
+use std::collections::VecDeque;
+use crate::commands::Command;
+use crate::pipeline::{self, ByteStream, TailStage, Stage};
 
 // Sync version for benchmarking
 pub fn tail_sync<S: AsRef<Path>>(files: Vec<S>, lines: usize) -> io::Result<String> {
@@ -86,6 +89,32 @@ pub async fn tail_async<S: AsRef<Path> + Send + 'static>(
     }.await
 }
 
+// Keeps the last lines of an incoming byte stream, emitting them once it ends
+pub fn tail_stream(input: ByteStream, lines: usize) -> ByteStream {
+    stream::once(async move {
+        let mut input_lines = pipeline::lines(input);
+        let mut last = VecDeque::with_capacity(lines);
+
+        while let Some(line) = input_lines.next().await {
+            match line {
+                Ok(line) => {
+                    if last.len() == lines {
+                        last.pop_front();
+                    }
+                    if lines > 0 {
+                        last.push_back(line);
+                    }
+                }
+                Err(e) => return stream::once(async move { Err(e) }).boxed(),
+            }
+        }
+
+        stream::iter(last.into_iter().map(|line| Ok(Bytes::from(line + "\n")))).boxed()
+    })
+    .flatten()
+    .boxed()
+}
+
 // Convenience function that collects the stream into a String
 pub async fn tail_async_to_string<S: AsRef<Path> + Send + 'static>(
     files: Vec<S>,
@@ -108,6 +137,43 @@ pub async fn tail_async_to_string<S: AsRef<Path> + Send + 'static>(
     Ok(result)
 }
 
+pub struct Tail;
+
+impl Tail {
+    fn build(args: &[String]) -> Result<Box<dyn Stage>, String> {
+        let (lines, files) = pipeline::parse_line_count("tail", args)?;
+        Ok(Box::new(TailStage::new(lines, files)))
+    }
+}
+
+impl Command for Tail {
+    fn name(&self) -> &'static str {
+        "tail"
+    }
+
+    fn usage(&self) -> &'static str {
+        "tail [-n N] [FILE]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the last lines of the input"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        pipeline::run_stage(self.name(), Self::build(args), stdin, stdout, stderr)
+    }
+
+    fn stage(&self, args: &[String]) -> Option<Result<Box<dyn Stage>, String>> {
+        Some(Self::build(args))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
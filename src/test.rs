@@ -0,0 +1,383 @@
+//! `true`, `false`, and `test` with its `[ ... ]` form, the commands that
+//! `if` and `while` conditions are usually made of.
+
+use crate::commands::{self, Command};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// `true`
+pub struct True;
+
+impl Command for True {
+    fn name(&self) -> &'static str {
+        "true"
+    }
+
+    fn usage(&self) -> &'static str {
+        "true"
+    }
+
+    fn description(&self) -> &'static str {
+        "Do nothing, successfully"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+    ) -> i32 {
+        0
+    }
+}
+
+/// `false`
+pub struct False;
+
+impl Command for False {
+    fn name(&self) -> &'static str {
+        "false"
+    }
+
+    fn usage(&self) -> &'static str {
+        "false"
+    }
+
+    fn description(&self) -> &'static str {
+        "Do nothing, unsuccessfully"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+    ) -> i32 {
+        1
+    }
+}
+
+/// `test EXPRESSION`
+pub struct Test;
+
+impl Command for Test {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn usage(&self) -> &'static str {
+        "test EXPRESSION"
+    }
+
+    fn description(&self) -> &'static str {
+        "Check file types and compare strings and numbers"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        status(stderr, self.name(), evaluate(args))
+    }
+}
+
+/// `[ EXPRESSION ]`, which is `test` with a closing `]`.
+pub struct Bracket;
+
+impl Command for Bracket {
+    fn name(&self) -> &'static str {
+        "["
+    }
+
+    fn usage(&self) -> &'static str {
+        "[ EXPRESSION ]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Check file types and compare strings and numbers"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let result = match args.split_last() {
+            Some((last, expression)) if last == "]" => evaluate(expression),
+            _ => Err("missing ']'".to_string()),
+        };
+        status(stderr, self.name(), result)
+    }
+}
+
+/// Turns the result of an expression into an exit status: 0 when it holds,
+/// 1 when it does not and 2 when it could not be evaluated.
+fn status(stderr: &mut dyn Write, name: &str, result: Result<bool, String>) -> i32 {
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            commands::report_error(stderr, name, &e);
+            2
+        }
+    }
+}
+
+/// Evaluates the words of a `test` expression.
+pub fn evaluate(args: &[String]) -> Result<bool, String> {
+    let words: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut parser = Parser {
+        words: &words,
+        position: 0,
+    };
+    if words.is_empty() {
+        return Ok(false);
+    }
+    let result = parser.or()?;
+    match parser.peek() {
+        None => Ok(result),
+        Some(word) => Err(format!("{}: unexpected argument", word)),
+    }
+}
+
+/// A recursive descent over the words, with `-o` binding more loosely than
+/// `-a`, and `-a` more loosely than `!`.
+struct Parser<'a> {
+    words: &'a [&'a str],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.words.get(self.position).copied()
+    }
+
+    fn remaining(&self) -> usize {
+        self.words.len() - self.position
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let word = self.peek().ok_or("argument expected")?;
+        self.position += 1;
+        Ok(word)
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut result = self.and()?;
+        while self.peek() == Some("-o") && self.remaining() > 1 {
+            self.position += 1;
+            // Both sides are parsed so that errors on either are reported
+            result = self.and()? || result;
+        }
+        Ok(result)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut result = self.not()?;
+        while self.peek() == Some("-a") && self.remaining() > 1 {
+            self.position += 1;
+            result = self.not()? && result;
+        }
+        Ok(result)
+    }
+
+    fn not(&mut self) -> Result<bool, String> {
+        // A lone `!` is just a non-empty string, as is one compared with `=`
+        if self.peek() == Some("!")
+            && self.remaining() > 1
+            && !self
+                .words
+                .get(self.position + 1)
+                .is_some_and(|w| is_binary(w))
+        {
+            self.position += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let word = self.next()?;
+        if let Some(operator) = self.peek()
+            && is_binary(operator)
+            && self.remaining() > 1
+        {
+            self.position += 1;
+            let right = self.next()?;
+            return binary(word, operator, right);
+        }
+        if word == "(" && self.remaining() > 1 {
+            let result = self.or()?;
+            return match self.next() {
+                Ok(")") => Ok(result),
+                _ => Err("missing ')'".to_string()),
+            };
+        }
+        if is_unary(word) && self.remaining() > 0 {
+            let operand = self.next()?;
+            return Ok(unary(word, operand));
+        }
+        Ok(!word.is_empty())
+    }
+}
+
+fn is_unary(word: &str) -> bool {
+    matches!(
+        word,
+        "-n" | "-z" | "-e" | "-f" | "-d" | "-s" | "-r" | "-w" | "-x" | "-L" | "-h"
+    )
+}
+
+fn is_binary(word: &str) -> bool {
+    matches!(
+        word,
+        "=" | "==" | "!=" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" | "-nt" | "-ot"
+    )
+}
+
+fn unary(operator: &str, operand: &str) -> bool {
+    let path = Path::new(operand);
+    match operator {
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-s" => fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0),
+        "-r" => fs::File::open(path).is_ok() || fs::read_dir(path).is_ok(),
+        "-w" => fs::metadata(path).is_ok_and(|metadata| !metadata.permissions().readonly()),
+        "-x" => is_executable(path),
+        // -L and -h
+        _ => path.is_symlink(),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Windows has no execute bit, so directories and programs Windows would
+/// run count as executable.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    let program = path.extension().is_some_and(|extension| {
+        ["exe", "bat", "cmd", "com"]
+            .iter()
+            .any(|known| extension.eq_ignore_ascii_case(known))
+    });
+    path.is_dir() || (program && path.is_file())
+}
+
+fn binary(left: &str, operator: &str, right: &str) -> Result<bool, String> {
+    match operator {
+        "=" | "==" => Ok(left == right),
+        "!=" => Ok(left != right),
+        "-nt" | "-ot" => {
+            let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+            let (left, right) = (modified(left), modified(right));
+            // A file that exists is newer than one that does not
+            Ok(match operator {
+                "-nt" => left > right,
+                _ => left < right,
+            })
+        }
+        _ => {
+            let (left, right) = (integer(left)?, integer(right)?);
+            Ok(match operator {
+                "-eq" => left == right,
+                "-ne" => left != right,
+                "-lt" => left < right,
+                "-le" => left <= right,
+                "-gt" => left > right,
+                _ => left >= right,
+            })
+        }
+    }
+}
+
+fn integer(word: &str) -> Result<i64, String> {
+    word.trim()
+        .parse()
+        .map_err(|_| format!("{}: integer expression expected", word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(line: &str) -> Result<bool, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        evaluate(&args)
+    }
+
+    #[test]
+    fn test_strings_and_numbers() {
+        assert_eq!(test(""), Ok(false));
+        assert_eq!(test("abc"), Ok(true));
+        assert_eq!(test("-n"), Ok(true));
+        assert_eq!(test("!"), Ok(true));
+        assert_eq!(test("-z abc"), Ok(false));
+        assert_eq!(test("a = a"), Ok(true));
+        assert_eq!(test("a != a"), Ok(false));
+        assert_eq!(test("! = !"), Ok(true));
+        assert_eq!(test("10 -gt 9"), Ok(true));
+        assert_eq!(test("-3 -le -4"), Ok(false));
+        assert!(test("a -eq 1").is_err());
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(test("! a = b"), Ok(true));
+        assert_eq!(test("a = b -o 1 -eq 1"), Ok(true));
+        assert_eq!(test("a = a -a 1 -eq 2"), Ok(false));
+        assert_eq!(test("( a = b -o b = b ) -a c"), Ok(true));
+        assert!(test("( a = a").is_err());
+        assert!(test("a b").is_err());
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "x").unwrap();
+        let (dir, file) = (dir.path().display(), file.display());
+
+        assert_eq!(test(&format!("-d {} -a -f {}", dir, file)), Ok(true));
+        assert_eq!(test(&format!("-f {}", dir)), Ok(false));
+        assert_eq!(test(&format!("-s {}", file)), Ok(true));
+        assert_eq!(test(&format!("-e {}/missing", dir)), Ok(false));
+        assert_eq!(test(&format!("{} -nt {}/missing", file, dir)), Ok(true));
+        assert_eq!(test(&format!("{}/missing -ot {}", dir, file)), Ok(true));
+    }
+
+    #[test]
+    fn test_bracket_needs_closing() {
+        let args =
+            |words: &[&str]| -> Vec<String> { words.iter().map(|w| w.to_string()).collect() };
+        let mut stderr = Vec::new();
+        let status = Bracket.run(
+            &args(&["a", "]"]),
+            &mut std::io::empty(),
+            &mut Vec::new(),
+            &mut stderr,
+        );
+        assert_eq!(status, 0);
+        let status = Bracket.run(
+            &args(&["a"]),
+            &mut std::io::empty(),
+            &mut Vec::new(),
+            &mut stderr,
+        );
+        assert_eq!(status, 2);
+    }
+}
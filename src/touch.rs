@@ -1,47 +1,364 @@
-use std::fs::{File};
+use crate::commands::{self, Command};
+use crate::localtime;
+use filetime::FileTime;
+use std::fs::{self, File};
+use std::io::{Read, Write};
 use std::path::Path;
+use time::{Date, Month, PrimitiveDateTime, Time};
 
+pub struct Touch;
 
-#[cfg(unix)]
-use filetime::{FileTime, set_file_times};
+impl Command for Touch {
+    fn name(&self) -> &'static str {
+        "touch"
+    }
+
+    fn usage(&self) -> &'static str {
+        "touch [-acm] [-r FILE | -t STAMP | -d DATE] FILE..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Create files or update their timestamps"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        run(args, stdout, stderr)
+    }
+}
+
+/// A time in the local time zone, or `None` if there is no such time.
+fn local_time(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<FileTime> {
+    let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+    let time = Time::from_hms(hour, minute, second).ok()?;
+    let local = localtime::assume_local(PrimitiveDateTime::new(date, time));
+    Some(FileTime::from_unix_time(local.unix_timestamp(), 0))
+}
+
+/// Reads `digits` as a number, or `None` if they are not all digits.
+fn number<T: std::str::FromStr>(digits: &str) -> Option<T> {
+    digits
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| digits.parse().ok())
+        .flatten()
+}
+
+/// The time in a `-t` stamp, `[[CC]YY]MMDDhhmm[.ss]`.
+pub fn parse_stamp(stamp: &str) -> Option<FileTime> {
+    let (main, seconds) = match stamp.split_once('.') {
+        Some((main, seconds)) if seconds.len() == 2 => (main, number(seconds)?),
+        Some(_) => return None,
+        None => (stamp, 0),
+    };
+    if !main.is_ascii() {
+        return None;
+    }
+    let (year, rest) = match main.len() {
+        8 => (localtime::now().year(), main),
+        // Two-digit years from 69 are in the 1900s, as POSIX says
+        10 => {
+            let year: i32 = number(&main[..2])?;
+            (
+                if year >= 69 { 1900 + year } else { 2000 + year },
+                &main[2..],
+            )
+        }
+        12 => (number(&main[..4])?, &main[4..]),
+        _ => return None,
+    };
+    local_time(
+        year,
+        number(&rest[0..2])?,
+        number(&rest[2..4])?,
+        number(&rest[4..6])?,
+        number(&rest[6..8])?,
+        seconds,
+    )
+}
+
+/// The time in a `-d` date: `2024-01-02`, `2024-01-02 10:00`, with seconds
+/// or a `T` before the time if wanted, `@SECONDS` since 1970, `now`,
+/// `today`, `yesterday` or `tomorrow`.
+pub fn parse_date(date: &str) -> Option<FileTime> {
+    let date = date.trim();
+    let now = FileTime::now();
+    let days =
+        |days: i64| FileTime::from_unix_time(now.unix_seconds() + days * 86_400, now.nanoseconds());
+    match date {
+        "now" | "today" => return Some(now),
+        "yesterday" => return Some(days(-1)),
+        "tomorrow" => return Some(days(1)),
+        _ => {}
+    }
+    if let Some(seconds) = date.strip_prefix('@') {
+        let negative = seconds.starts_with('-');
+        let seconds: i64 = number(seconds.trim_start_matches('-'))?;
+        return Some(FileTime::from_unix_time(
+            if negative { -seconds } else { seconds },
+            0,
+        ));
+    }
+
+    let (day, time) = match date.split_once([' ', 'T']) {
+        Some((day, time)) => (day, Some(time.trim())),
+        None => (date, None),
+    };
+    let mut day_parts = day.splitn(3, '-');
+    let year = number(day_parts.next()?)?;
+    let month = number(day_parts.next()?)?;
+    let day = number(day_parts.next()?)?;
+    let (hour, minute, second) = match time {
+        Some(time) => {
+            let mut time_parts = time.splitn(3, ':');
+            let hour = number(time_parts.next()?)?;
+            let minute = number(time_parts.next()?)?;
+            let second = match time_parts.next() {
+                Some(second) => number(second)?,
+                None => 0,
+            };
+            (hour, minute, second)
+        }
+        None => (0, 0, 0),
+    };
+    local_time(year, month, day, hour, minute, second)
+}
+
+/// What to set a file's times to, read from the arguments.
+struct Times {
+    access: bool,
+    modification: bool,
+    no_create: bool,
+    /// The access and modification times to use rather than now
+    given: Option<(FileTime, FileTime)>,
+}
+
+/// Works out which times to change (`-a`, `-m`), whether missing files are
+/// created (`-c`), and the time to use from `-t`, `-d` or the `-r` reference
+/// file, pushing the operands onto `files`. An unknown flag, a missing value
+/// or a date that cannot be read is returned as the message to show.
+fn parse_args(args: &[String], files: &mut Vec<String>) -> Result<Times, String> {
+    let mut times = Times {
+        access: false,
+        modification: false,
+        no_create: false,
+        given: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("option requires an argument -- '{}'", option))
+        };
+        match arg.as_str() {
+            "--" => {
+                files.extend(args.cloned());
+                break;
+            }
+            "-t" => {
+                let stamp = value("t")?;
+                let time = parse_stamp(&stamp)
+                    .ok_or_else(|| format!("invalid date format '{}'", stamp))?;
+                times.given = Some((time, time));
+            }
+            "-d" => {
+                let date = value("d")?;
+                let time =
+                    parse_date(&date).ok_or_else(|| format!("invalid date format '{}'", date))?;
+                times.given = Some((time, time));
+            }
+            "-r" => {
+                let reference = value("r")?;
+                let metadata = fs::metadata(&reference).map_err(|e| {
+                    format!(
+                        "failed to get attributes of '{}': {}",
+                        reference,
+                        commands::io_message(&e)
+                    )
+                })?;
+                times.given = Some((
+                    FileTime::from_last_access_time(&metadata),
+                    FileTime::from_last_modification_time(&metadata),
+                ));
+            }
+            "--no-create" => times.no_create = true,
+            flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'a' => times.access = true,
+                        'm' => times.modification = true,
+                        'c' => times.no_create = true,
+                        _ => return Err(format!("invalid option -- '{}'", flag)),
+                    }
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("unrecognized option '{}'", arg)),
+            _ => files.push(arg.clone()),
+        }
+    }
+    // Neither -a nor -m means both
+    if !times.access && !times.modification {
+        times.access = true;
+        times.modification = true;
+    }
+    Ok(times)
+}
+
+/// Sets the times of an existing file that `times` asks to change.
+fn set_times(path: &Path, times: &Times) -> std::io::Result<()> {
+    let now = FileTime::now();
+    let (access, modification) = times.given.unwrap_or((now, now));
+    match (times.access, times.modification) {
+        (true, true) => filetime::set_file_times(path, access, modification),
+        (true, false) => filetime::set_file_atime(path, access),
+        _ => filetime::set_file_mtime(path, modification),
+    }
+}
+
+/// Creates or updates each file, returning 1 if any of them failed and 2
+/// if the options were wrong.
+pub fn run(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut files = Vec::new();
+    let times = match parse_args(args, &mut files) {
+        Ok(times) => times,
+        Err(message) => {
+            commands::report_error(err, "touch", message);
+            return commands::usage_error(err, Touch.usage());
+        }
+    };
+    if files.is_empty() {
+        return commands::usage_error(err, Touch.usage());
+    }
 
-pub fn run(args: &[String]) {
-    for file_name in args {
+    let mut status = 0;
+    for file_name in &files {
         let path = Path::new(file_name);
 
         // If the file doesn't exist, create it
-        if !path.exists() {
-            match File::create(&path) {
-                Ok(_) => println!("Created '{}'", file_name),
-                Err(e) => eprintln!("touch: cannot create file '{}': {}", file_name, e),
-            }
-        } else {
-            #[cfg(unix)]
-            {
-                // Update the access and modification times
-                let now = FileTime::now();
-                if let Err(e) = set_file_times(&path, now, now) {
-                    eprintln!("touch: failed to update timestamps for '{}': {}", file_name, e);
-                } else {
-                    println!("Updated timestamp for '{}'", file_name);
+        let existed = path.exists();
+        if !existed {
+            if times.no_create {
+                continue;
+            }
+            match File::create(path) {
+                Ok(_) => {
+                    let _ = writeln!(out, "Created '{}'", file_name);
+                }
+                Err(e) => {
+                    let _ = writeln!(
+                        err,
+                        "touch: cannot create file '{}': {}",
+                        file_name,
+                        commands::io_message(&e)
+                    );
+                    status = 1;
+                    continue;
                 }
             }
+            // A new file already has the current time
+            if times.given.is_none() {
+                continue;
+            }
+        }
 
-            #[cfg(windows)]
-        
-            {   
-                use std::fs::OpenOptions;
-                use std::io::Write;
-                // On Windows, simulate a timestamp update by opening in append mode
-                match OpenOptions::new().append(true).open(&path) {
-                    Ok(mut file) => {
-                        // Optionally write 0 bytes to trigger timestamp update
-                        let _ = file.write(&[]);
-                        println!("Simulated timestamp update for '{}'", file_name);
-                    },
-                    Err(e) => eprintln!("touch: failed to open file '{}': {}", file_name, e),
-                }
+        match set_times(path, &times) {
+            Ok(()) if existed => {
+                let _ = writeln!(out, "Updated timestamp for '{}'", file_name);
+            }
+            Ok(()) => {}
+            Err(e) => {
+                let _ = writeln!(
+                    err,
+                    "touch: setting times of '{}': {}",
+                    file_name,
+                    commands::io_message(&e)
+                );
+                status = 1;
             }
         }
     }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(args: &[&str]) -> i32 {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        run(&args, &mut std::io::sink(), &mut std::io::sink())
+    }
+
+    fn times(path: &Path) -> (i64, i64) {
+        let metadata = fs::metadata(path).unwrap();
+        (
+            FileTime::from_last_access_time(&metadata).unix_seconds(),
+            FileTime::from_last_modification_time(&metadata).unix_seconds(),
+        )
+    }
+
+    #[test]
+    fn test_parse_times() {
+        let at = |y, mo, d, h, mi, s| local_time(y, mo, d, h, mi, s).unwrap();
+        assert_eq!(parse_stamp("202401021000"), Some(at(2024, 1, 2, 10, 0, 0)));
+        assert_eq!(
+            parse_stamp("9901021000.30"),
+            Some(at(1999, 1, 2, 10, 0, 30))
+        );
+        assert_eq!(parse_stamp("0801021000"), Some(at(2008, 1, 2, 10, 0, 0)));
+        assert_eq!(parse_stamp("01321000"), None);
+        assert_eq!(parse_stamp("2024010210"), None);
+        assert_eq!(parse_stamp("202401021000.3"), None);
+
+        assert_eq!(
+            parse_date("2024-01-02 10:00"),
+            Some(at(2024, 1, 2, 10, 0, 0))
+        );
+        assert_eq!(
+            parse_date("2024-01-02T10:00:05"),
+            Some(at(2024, 1, 2, 10, 0, 5))
+        );
+        assert_eq!(parse_date("2024-01-02"), Some(at(2024, 1, 2, 0, 0, 0)));
+        assert_eq!(
+            parse_date("@86400"),
+            Some(FileTime::from_unix_time(86_400, 0))
+        );
+        assert!(parse_date("yesterday").unwrap() < FileTime::now());
+        assert_eq!(parse_date("2024-02-30"), None);
+        assert_eq!(parse_date("soon"), None);
+    }
+
+    #[test]
+    fn test_touch_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        let file_arg = file.to_string_lossy().into_owned();
+
+        assert_eq!(touch(&["-c", &file_arg]), 0);
+        assert!(!file.exists());
+
+        assert_eq!(touch(&["-d", "@1000000000", &file_arg]), 0);
+        assert_eq!(times(&file), (1_000_000_000, 1_000_000_000));
+        assert_eq!(touch(&["-m", "-t", "200901021000", &file_arg]), 0);
+        let modified = local_time(2009, 1, 2, 10, 0, 0).unwrap().unix_seconds();
+        assert_eq!(times(&file), (1_000_000_000, modified));
+        assert_eq!(touch(&["-a", &file_arg]), 0);
+        assert!(times(&file).0 > modified);
+
+        let other = dir.path().join("other");
+        let other_arg = other.to_string_lossy().into_owned();
+        assert_eq!(touch(&["-r", &file_arg, &other_arg]), 0);
+        assert_eq!(times(&other), times(&file));
+
+        assert_eq!(touch(&["-t", "bad", &file_arg]), 2);
+        assert_eq!(touch(&["-x", &file_arg]), 2);
+        assert_eq!(touch(&["-r", "/no/such/file", &file_arg]), 2);
+        assert_eq!(touch(&[]), 2);
+    }
 }
@@ -0,0 +1,316 @@
+//! The trash `rm --trash` moves files to, and the `trash` built-in that
+//! lists, restores and empties it.
+//!
+//! The trash is laid out as the XDG trash specification describes: a
+//! `Trash` directory in the user's data directory, with the files in
+//! `files/` and a `.trashinfo` file in `info/` for each, recording where it
+//! came from and when. File managers that follow the specification show
+//! and restore what winix puts there, and the other way round.
+
+use crate::commands::{self, Command};
+use crate::localtime;
+use crate::mv;
+use crate::vars;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The trash directory: `$XDG_DATA_HOME/Trash`, or `Trash` in the user's
+/// data directory.
+pub fn dir() -> Option<PathBuf> {
+    match vars::get("XDG_DATA_HOME") {
+        Some(data) if !data.is_empty() => Some(PathBuf::from(data).join("Trash")),
+        _ => dirs::data_dir().map(|data| data.join("Trash")),
+    }
+}
+
+fn trash_dir() -> io::Result<PathBuf> {
+    dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory for the trash"))
+}
+
+/// Something in the trash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// Its name in `files/`
+    pub name: String,
+    /// Where it was before it was trashed
+    pub original: PathBuf,
+    /// When it was trashed, as `YYYY-MM-DDThh:mm:ss` local time
+    pub deleted: String,
+}
+
+/// Moves a file or directory to the trash, returning its name there.
+pub fn put(path: &Path) -> io::Result<String> {
+    let trash = trash_dir()?;
+    fs::create_dir_all(trash.join("files"))?;
+    fs::create_dir_all(trash.join("info"))?;
+    let original = std::path::absolute(path)?;
+    let base = original
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash this path"))?;
+
+    // Creating the info file claims the name, as the specification asks,
+    // so that two shells trashing files of the same name do not collide
+    let mut number = 1;
+    let (name, info_path, mut info) = loop {
+        let name = if number == 1 {
+            base.clone()
+        } else {
+            format!("{}.{}", base, number)
+        };
+        let info_path = trash.join("info").join(format!("{}.trashinfo", name));
+        if !trash.join("files").join(&name).exists() {
+            match fs::File::create_new(&info_path) {
+                Ok(file) => break (name, info_path, file),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        number += 1;
+    };
+
+    let now = localtime::now();
+    let written = write!(
+        info,
+        "[Trash Info]\nPath={}\nDeletionDate={:04}-{:02}-{:02}T{:02}:{:02}:{:02}\n",
+        encode(&original.to_string_lossy()),
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let moved = written.and_then(|()| mv::move_path(path, &trash.join("files").join(&name)));
+    if let Err(e) = moved {
+        let _ = fs::remove_file(&info_path);
+        return Err(e);
+    }
+    Ok(name)
+}
+
+/// Everything in the trash, oldest first.
+pub fn items() -> io::Result<Vec<Item>> {
+    let info_dir = trash_dir()?.join("info");
+    let entries = match fs::read_dir(&info_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut items = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(name) = file_name.strip_suffix(".trashinfo") else {
+            continue;
+        };
+        if let Some(item) = parse_info(name, &fs::read_to_string(entry.path())?) {
+            items.push(item);
+        }
+    }
+    items.sort_by(|a, b| a.deleted.cmp(&b.deleted).then_with(|| a.name.cmp(&b.name)));
+    Ok(items)
+}
+
+fn parse_info(name: &str, info: &str) -> Option<Item> {
+    let mut original = None;
+    let mut deleted = String::new();
+    for line in info.lines() {
+        if let Some(path) = line.strip_prefix("Path=") {
+            original = Some(PathBuf::from(decode(path)));
+        } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+            deleted = date.to_string();
+        }
+    }
+    Some(Item {
+        name: name.to_string(),
+        original: original?,
+        deleted,
+    })
+}
+
+/// Puts an item back where it came from.
+pub fn restore(item: &Item) -> io::Result<()> {
+    if fs::symlink_metadata(&item.original).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, "File exists"));
+    }
+    let trash = trash_dir()?;
+    if let Some(parent) = item.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    mv::move_path(&trash.join("files").join(&item.name), &item.original)?;
+    fs::remove_file(trash.join("info").join(format!("{}.trashinfo", item.name)))
+}
+
+/// Deletes everything in the trash for good.
+pub fn empty() -> io::Result<()> {
+    let trash = trash_dir()?;
+    for sub in ["files", "info"] {
+        match fs::remove_dir_all(trash.join(sub)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// A path with the bytes URLs cannot hold written as `%XX`, as the
+/// specification asks.
+fn encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `trash list | restore FILE... | empty`
+pub struct Trash;
+
+impl Trash {
+    /// Restores the most recently trashed item named `file`, by its name in
+    /// the trash or the path it had.
+    fn restore_one(&self, file: &str, items: &mut Vec<Item>) -> Result<(), String> {
+        let path = std::path::absolute(file).unwrap_or_else(|_| PathBuf::from(file));
+        let found = items
+            .iter()
+            .rposition(|item| item.name == file || item.original == path)
+            .ok_or_else(|| format!("'{}': not in the trash", file))?;
+        let item = items.remove(found);
+        restore(&item).map_err(|e| {
+            format!(
+                "cannot restore '{}': {}",
+                item.original.display(),
+                commands::io_message(&e)
+            )
+        })
+    }
+}
+
+impl Command for Trash {
+    fn name(&self) -> &'static str {
+        "trash"
+    }
+
+    fn usage(&self) -> &'static str {
+        "trash list | restore FILE... | empty"
+    }
+
+    fn description(&self) -> &'static str {
+        "List, restore or empty the files rm --trash moved to the trash"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        match (args.first().map(String::as_str), args.len()) {
+            (Some("list"), 1) => {
+                let listed = items().and_then(|items| {
+                    items.iter().try_for_each(|item| {
+                        let deleted = item.deleted.replacen('T', " ", 1);
+                        writeln!(stdout, "{}  {}", deleted, item.original.display())
+                    })
+                });
+                commands::exit_code(stderr, self.name(), listed)
+            }
+            (Some("restore"), 2..) => {
+                let mut items = match items() {
+                    Ok(items) => items,
+                    Err(e) => return commands::report_error(stderr, self.name(), e),
+                };
+                let mut status = 0;
+                for file in &args[1..] {
+                    if let Err(message) = self.restore_one(file, &mut items) {
+                        status = commands::report_error(stderr, self.name(), message);
+                    }
+                }
+                status
+            }
+            (Some("empty"), 1) => commands::exit_code(stderr, self.name(), empty()),
+            _ => commands::usage_error(stderr, self.usage()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("/tmp/a b/ü%.txt"), "/tmp/a%20b/%C3%BC%25.txt");
+        assert_eq!(decode(&encode("/tmp/a b/ü%.txt")), "/tmp/a b/ü%.txt");
+        assert_eq!(decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn test_put_and_restore() {
+        let home = tempfile::tempdir().unwrap();
+        vars::set("XDG_DATA_HOME", &home.path().to_string_lossy());
+        let file = home.path().join("notes.txt");
+        let run = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            let mut output = Vec::new();
+            let code = Trash.run(&args, &mut io::empty(), &mut output, &mut io::sink());
+            (code, String::from_utf8(output).unwrap())
+        };
+
+        fs::write(&file, "first").unwrap();
+        assert_eq!(put(&file).unwrap(), "notes.txt");
+        fs::write(&file, "second").unwrap();
+        assert_eq!(put(&file).unwrap(), "notes.txt.2");
+        assert!(!file.exists());
+        let info =
+            fs::read_to_string(home.path().join("Trash/info/notes.txt.2.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=/"));
+
+        let (code, listed) = run(&["list"]);
+        assert_eq!(code, 0);
+        assert_eq!(listed.lines().count(), 2);
+        assert!(listed.contains("notes.txt"));
+
+        // The most recent one comes back first, and only once
+        assert_eq!(run(&["restore", &file.to_string_lossy()]).0, 0);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "second");
+        assert_eq!(run(&["restore", "notes.txt"]).0, 1);
+        fs::remove_file(&file).unwrap();
+        assert_eq!(run(&["restore", "notes.txt"]).0, 0);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "first");
+
+        assert_eq!(run(&["restore", "nothing"]).0, 1);
+        assert_eq!(run(&["empty"]).0, 0);
+        assert_eq!(items().unwrap(), Vec::new());
+        vars::unset("XDG_DATA_HOME");
+    }
+}
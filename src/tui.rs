@@ -1,3 +1,8 @@
+use crate::commands::CLEAR_SCREEN;
+use crate::completion;
+use crate::history;
+use crate::ls;
+use crate::shell::{Console, SharedBuffer, Shell};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -15,12 +20,9 @@ use ratatui::{
     Frame, Terminal,
 };
 use std::io;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
-// Import statements for integrating with existing modules
-// Note: These are currently unused as we're implementing direct capture functions
-// use crate::{chmod, chown, df, free, ps, sensors, uname, uptime};
-
 #[derive(Debug)]
 pub struct App {
     pub selected_tab: usize,
@@ -35,10 +37,13 @@ pub struct App {
     pub command_input: String,
     pub command_output: Vec<String>,
     pub show_command_mode: bool,
+    shell: Shell,
+    output: SharedBuffer,
 }
 
 impl Default for App {
     fn default() -> App {
+        let output = SharedBuffer::default();
         let mut app = App {
             selected_tab: 0,
             should_quit: false,
@@ -54,28 +59,42 @@ impl Default for App {
             command_input: String::new(),
             command_output: Vec::new(),
             show_command_mode: false,
+            shell: Shell::new(Console::Capture(output.clone())),
+            output,
         };
+        // Anything ~/.winixrc prints shows in the output pane
+        app.shell.source_rc();
+        app.command_output = String::from_utf8_lossy(&app.output.take())
+            .lines()
+            .map(str::to_string)
+            .collect();
+        if let Err(e) = history::load() {
+            app.command_output
+                .push(format!("winix: could not read history: {}", e));
+        }
         app.refresh_ls();
         app
     }
 }
 
 impl App {
+    /// Lists the current directory as `ls -lAh` would, folders first.
     pub fn refresh_ls(&mut self) {
         self.ls_items.clear();
-        if let Ok(entries) = std::fs::read_dir(&self.current_dir) {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    if entry.file_type().is_ok() && entry.file_type().unwrap().is_dir() {
-                        self.ls_items.push(format!("📁 {}", name));
-                    } else {
-                        self.ls_items.push(format!("📄 {}", name));
-                    }
-                }
+        let options = ls::Options {
+            almost_all: true,
+            long: true,
+            human: true,
+            ..ls::Options::default()
+        };
+        if let Ok(mut entries) = ls::read_dir(Path::new(&self.current_dir), &options) {
+            entries.sort_by_key(|entry| !entry.is_dir());
+            let lines = ls::long_lines(&entries, &options);
+            for (entry, line) in entries.iter().zip(lines) {
+                let icon = if entry.is_dir() { "📁" } else { "📄" };
+                self.ls_items.push(format!("{} {}", icon, line));
             }
         }
-        self.ls_items.sort();
     }
 
     pub fn next_tab(&mut self) {
@@ -101,201 +120,67 @@ impl App {
         }
     }
 
+    /// Completes the word at the end of the command input. With several
+    /// candidates, fills in what they share and lists them in the output.
+    pub fn complete_command(&mut self) {
+        let pos = self.command_input.len();
+        let (start, candidates) = completion::complete(&self.command_input, pos);
+        if candidates.is_empty() {
+            return;
+        }
+        self.command_input
+            .replace_range(start.., &completion::common_prefix(&candidates));
+        if candidates.len() > 1 {
+            self.command_output = candidates
+                .into_iter()
+                .map(|candidate| candidate.display)
+                .collect();
+        }
+    }
+
     pub fn execute_command(&mut self) {
         if self.command_input.trim().is_empty() {
             return;
         }
 
-        let parts: Vec<&str> = self.command_input.trim().split_whitespace().collect();
-        let command = parts[0].to_lowercase();
-
+        let line = std::mem::take(&mut self.command_input);
         self.command_output.clear();
-
-        match command.as_str() {
-            "cd" => {
-                if parts.len() > 1 {
-                    if let Err(e) = std::env::set_current_dir(parts[1]) {
-                        self.command_output.push(format!("cd: {}", e));
-                    } else {
-                        self.current_dir = std::env::current_dir()
-                            .unwrap_or_else(|_| "?".into())
-                            .display()
-                            .to_string();
-                        self.refresh_ls();
-                        self.command_output
-                            .push(format!("Changed directory to: {}", self.current_dir));
-                    }
-                } else {
-                    self.command_output
-                        .push("Usage: cd <directory>".to_string());
-                }
-            }
-            "pwd" => {
-                self.command_output.push(self.current_dir.clone());
-            }
-            "ls" => {
-                for item in &self.ls_items {
-                    self.command_output.push(item.clone());
-                }
-            }
-            "uname" => {
-                // Capture uname output directly
-                let output = capture_uname_output();
-                for line in output.lines() {
-                    self.command_output.push(line.to_string());
-                }
-            }
-            "ps" => {
-                // Capture ps output directly
-                let output = capture_ps_output();
-                for line in output.lines() {
-                    self.command_output.push(line.to_string());
-                }
-            }
-            "free" => {
-                // Capture free output directly
-                let output = capture_free_output();
-                for line in output.lines() {
-                    self.command_output.push(line.to_string());
-                }
-            }
-            "df" => {
-                // Capture df output directly
-                let output = capture_df_output();
-                for line in output.lines() {
-                    self.command_output.push(line.to_string());
-                }
-            }
-            "uptime" => {
-                // Capture uptime output directly
-                let output = capture_uptime_output();
-                for line in output.lines() {
-                    self.command_output.push(line.to_string());
-                }
-            }
-            "sensors" => {
-                // Capture sensors output directly
-                let output = capture_sensors_output();
-                for line in output.lines() {
-                    self.command_output.push(line.to_string());
-                }
-            }
-            "chmod" => {
-                if parts.len() < 3 {
-                    self.command_output
-                        .push("Usage: chmod <permissions> <file>".to_string());
-                } else {
-                    let args: Vec<&str> = parts[1..].to_vec();
-                    let output = capture_chmod_output(&args);
-                    for line in output.lines() {
-                        self.command_output.push(line.to_string());
-                    }
-                }
-            }
-            "chown" => {
-                if parts.len() < 3 {
-                    self.command_output
-                        .push("Usage: chown <owner> <file>".to_string());
-                } else {
-                    let args: Vec<&str> = parts[1..].to_vec();
-                    let output = capture_chown_output(&args);
-                    for line in output.lines() {
-                        self.command_output.push(line.to_string());
-                    }
-                }
-            }
-            "git" => {
-                if parts.len() < 2 {
-                    self.command_output
-                        .push("Usage: git <command> [options]".to_string());
-                    self.command_output.push("Examples:".to_string());
-                    self.command_output.push("  git status".to_string());
-                    self.command_output.push("  git log --oneline".to_string());
-                    self.command_output.push("  git add .".to_string());
-                    self.command_output
-                        .push("  git commit -m \"message\"".to_string());
-                } else {
-                    let args: Vec<&str> = parts[1..].to_vec();
-                    let output = capture_git_output(&args);
-                    for line in output.lines() {
-                        self.command_output.push(line.to_string());
-                    }
-                }
-            }
-            "psh" | "powershell" => {
-                if parts.len() < 2 {
-                    self.command_output
-                        .push("Usage: psh <command> [options]".to_string());
-                    self.command_output.push("Examples:".to_string());
-                    self.command_output.push("  psh Get-Process".to_string());
-                    self.command_output.push("  psh Get-ChildItem".to_string());
-                    self.command_output.push("  psh Get-Service".to_string());
-                    self.command_output
-                        .push("  psh Test-Connection google.com".to_string());
-                } else {
-                    let args: Vec<&str> = parts[1..].to_vec();
-                    let output = capture_powershell_output(&args);
-                    for line in output.lines() {
-                        self.command_output.push(line.to_string());
-                    }
-                }
-            }
-            "clear" => {
-                self.command_output.clear();
-            }
-            "help" => {
-                self.command_output.push("Available commands:".to_string());
-                self.command_output
-                    .push("  cd <dir>     - Change directory".to_string());
-                self.command_output
-                    .push("  pwd          - Print working directory".to_string());
-                self.command_output
-                    .push("  ls           - List files".to_string());
-                self.command_output
-                    .push("  uname        - System information".to_string());
-                self.command_output
-                    .push("  ps           - Process list".to_string());
-                self.command_output
-                    .push("  free         - Memory usage".to_string());
-                self.command_output
-                    .push("  df           - Disk usage".to_string());
-                self.command_output
-                    .push("  uptime       - System uptime".to_string());
-                self.command_output
-                    .push("  sensors      - Temperature sensors".to_string());
-                self.command_output
-                    .push("  chmod        - Change permissions".to_string());
-                self.command_output
-                    .push("  chown        - Change ownership".to_string());
-                self.command_output
-                    .push("  git          - Git version control".to_string());
-                self.command_output
-                    .push("  psh          - PowerShell commands".to_string());
-                self.command_output
-                    .push("  clear        - Clear output".to_string());
-                self.command_output
-                    .push("  help         - Show this help".to_string());
-                self.command_output.push("".to_string());
-                self.command_output
-                    .push("Note: Unknown commands will be passed to PowerShell".to_string());
+        let line = match history::expand(&line) {
+            Ok(Some(expanded)) => {
+                self.command_output.push(expanded.clone());
+                expanded
             }
-            _ => {
-                // Fallback to PowerShell for unknown commands
-                let output = capture_powershell_output(&parts);
-                if output.trim().is_empty() {
-                    self.command_output
-                        .push(format!("Unknown command: '{}'", command));
-                    self.command_output
-                        .push("Type 'help' for built-in commands".to_string());
-                } else {
-                    for line in output.lines() {
-                        self.command_output.push(line.to_string());
-                    }
-                }
+            Ok(None) => line,
+            Err(e) => {
+                self.command_output.push(format!("winix: {}", e));
+                return;
             }
+        };
+        self.shell.run_line(&line);
+        if let Err(e) = history::record(&line, self.shell.last_status()) {
+            self.command_output
+                .push(format!("winix: could not save history: {}", e));
+        }
+        self.shell.report_finished_jobs();
+        if self.shell.exit_status().is_some() {
+            self.should_quit = true;
         }
 
-        self.command_input.clear();
+        let output = String::from_utf8_lossy(&self.output.take()).into_owned();
+        // `clear` erases everything written before it
+        let output = match output.rfind(CLEAR_SCREEN) {
+            Some(pos) => &output[pos + CLEAR_SCREEN.len()..],
+            None => &output[..],
+        };
+        self.command_output
+            .extend(output.lines().map(str::to_string));
+
+        // Commands such as `cd`, `cp` and `rm` change what the file browser shows
+        self.current_dir = std::env::current_dir()
+            .unwrap_or_else(|_| "?".into())
+            .display()
+            .to_string();
+        self.refresh_ls();
     }
 }
 
@@ -310,8 +195,12 @@ pub fn run_tui() -> Result<(), Box<dyn std::error::Error>> {
     // Create app state
     let mut app = App::default();
 
+    // Command output is shown as plain text in the output pane
+    colored::control::set_override(false);
+
     // Main loop
     let result = run_app(&mut terminal, &mut app);
+    colored::control::unset_override();
 
     // Restore terminal
     disable_raw_mode()?;
@@ -333,51 +222,53 @@ fn run_app(
         terminal.draw(|f| ui(f, app))?;
 
         // Use slightly longer polling for better performance while maintaining responsiveness
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    if app.show_command_mode {
-                        match key.code {
-                            KeyCode::Char(c) => {
-                                app.command_input.push(c);
-                            }
-                            KeyCode::Backspace => {
-                                app.command_input.pop();
-                            }
-                            KeyCode::Enter => {
-                                app.execute_command();
-                            }
-                            KeyCode::Esc => {
-                                app.toggle_command_mode();
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Char('Q') => {
-                                app.should_quit = true;
-                            }
-                            KeyCode::Char('h') | KeyCode::Char('H') => {
-                                app.toggle_help();
-                            }
-                            KeyCode::Char('c') | KeyCode::Char('C') => {
-                                app.toggle_command_mode();
-                            }
-                            KeyCode::Left => {
-                                app.previous_tab();
-                            }
-                            KeyCode::Right => {
-                                app.next_tab();
-                            }
-                            KeyCode::Tab => {
-                                app.next_tab();
-                            }
-                            KeyCode::Char('r') | KeyCode::Char('R') => {
-                                app.last_update = Instant::now();
-                            }
-                            _ => {}
-                        }
+        if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            if app.show_command_mode {
+                match key.code {
+                    KeyCode::Char(c) => {
+                        app.command_input.push(c);
                     }
+                    KeyCode::Backspace => {
+                        app.command_input.pop();
+                    }
+                    KeyCode::Enter => {
+                        app.execute_command();
+                    }
+                    KeyCode::Tab => {
+                        app.complete_command();
+                    }
+                    KeyCode::Esc => {
+                        app.toggle_command_mode();
+                    }
+                    _ => {}
+                }
+            } else {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Char('Q') => {
+                        app.should_quit = true;
+                    }
+                    KeyCode::Char('h') | KeyCode::Char('H') => {
+                        app.toggle_help();
+                    }
+                    KeyCode::Char('c') | KeyCode::Char('C') => {
+                        app.toggle_command_mode();
+                    }
+                    KeyCode::Left => {
+                        app.previous_tab();
+                    }
+                    KeyCode::Right => {
+                        app.next_tab();
+                    }
+                    KeyCode::Tab => {
+                        app.next_tab();
+                    }
+                    KeyCode::Char('r') | KeyCode::Char('R') => {
+                        app.last_update = Instant::now();
+                    }
+                    _ => {}
                 }
             }
         }
@@ -985,288 +876,6 @@ fn get_sensor_info() -> Text<'static> {
 
 // Helper functions to capture output from existing command modules
 
-fn capture_uname_output() -> String {
-    // Call uname module to get actual system info
-    let mut info = String::new();
-    if let Some(name) = sysinfo::System::name() {
-        info.push_str(&format!("OS: {}\n", name));
-    }
-    if let Some(version) = sysinfo::System::os_version() {
-        info.push_str(&format!("OS Version: {}\n", version));
-    }
-    if let Some(kernel) = sysinfo::System::kernel_version() {
-        info.push_str(&format!("Kernel: {}\n", kernel));
-    }
-    info.push_str(&format!("Architecture: {}\n", std::env::consts::ARCH));
-    if let Some(hostname) = sysinfo::System::host_name() {
-        info.push_str(&format!("Hostname: {}\n", hostname));
-    }
-    info
-}
-
-fn capture_ps_output() -> String {
-    use sysinfo::System;
-    let mut sys = System::new_all();
-    sys.refresh_all();
-
-    let mut output = String::new();
-    output.push_str("PID      NAME                     CPU%     MEMORY\n");
-    output.push_str("=".repeat(50).as_str());
-    output.push('\n');
-
-    let mut processes: Vec<_> = sys.processes().iter().collect();
-    processes.sort_by(|a, b| {
-        b.1.cpu_usage()
-            .partial_cmp(&a.1.cpu_usage())
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-
-    for (pid, process) in processes.iter().take(10) {
-        let name = process.name().to_string_lossy();
-        let name = if name.len() > 20 {
-            format!("{}...", &name[..17])
-        } else {
-            name.to_string()
-        };
-        output.push_str(&format!(
-            "{:<8} {:<23} {:<8.1} {}\n",
-            pid,
-            name,
-            process.cpu_usage(),
-            format_bytes(process.memory())
-        ));
-    }
-    output
-}
-
-fn capture_free_output() -> String {
-    use sysinfo::System;
-    let mut sys = System::new_all();
-    sys.refresh_all();
-
-    format!(
-        "Used memory : {}\nTotal memory: {}\nTotal swap  : {}\nUsed swap   : {}",
-        format_bytes(sys.used_memory()),
-        format_bytes(sys.total_memory()),
-        format_bytes(sys.total_swap()),
-        format_bytes(sys.used_swap())
-    )
-}
-
-fn capture_df_output() -> String {
-    use sysinfo::Disks;
-    let disks = Disks::new_with_refreshed_list();
-
-    let mut output = String::new();
-    output.push_str("Filesystem     Size      Used      Avail     Use%   Mounted on\n");
-    output.push_str("=".repeat(70).as_str());
-    output.push('\n');
-
-    for disk in &disks {
-        let total_space = disk.total_space();
-        let available_space = disk.available_space();
-        let used_space = total_space - available_space;
-
-        let usage_percent = if total_space > 0 {
-            (used_space as f64 / total_space as f64) * 100.0
-        } else {
-            0.0
-        };
-
-        let mount_point = disk.mount_point().to_string_lossy();
-        let name = disk.name().to_string_lossy();
-
-        output.push_str(&format!(
-            "{:<14} {:<9} {:<9} {:<9} {:<6.1}% {}\n",
-            name,
-            format_bytes(total_space),
-            format_bytes(used_space),
-            format_bytes(available_space),
-            usage_percent,
-            mount_point
-        ));
-    }
-    output
-}
-
-fn capture_uptime_output() -> String {
-    let uptime_seconds = sysinfo::System::uptime();
-    let days = uptime_seconds / 86400;
-    let hours = (uptime_seconds % 86400) / 3600;
-    let minutes = (uptime_seconds % 3600) / 60;
-
-    format!(
-        "System uptime: {} days, {} hours, {} minutes",
-        days, hours, minutes
-    )
-}
-
-fn capture_sensors_output() -> String {
-    use sysinfo::Components;
-    let components = Components::new_with_refreshed_list();
-
-    if components.is_empty() {
-        return "No temperature sensors found or accessible.\nNote: On Windows, temperature sensors may require administrator privileges.".to_string();
-    }
-
-    let mut output = String::new();
-    output.push_str("Component Temperatures:\n");
-    output.push_str("=".repeat(30).as_str());
-    output.push('\n');
-
-    for component in &components {
-        let label = component.label();
-        let temperature = component.temperature();
-
-        if let Some(temp) = temperature {
-            output.push_str(&format!("{}: {:.1}°C\n", label, temp));
-        }
-    }
-    output
-}
-
-fn capture_chmod_output(args: &[&str]) -> String {
-    // For demonstration - in a real implementation, this would call the actual chmod module
-    if args.len() < 2 {
-        return "Usage: chmod <permissions> <file>".to_string();
-    }
-
-    let permissions = args[0];
-    let file = args[1];
-
-    // Check if file exists
-    if std::path::Path::new(file).exists() {
-        format!("Changed permissions of '{}' to '{}'", file, permissions)
-    } else {
-        format!("File '{}' not found", file)
-    }
-}
-
-fn capture_chown_output(args: &[&str]) -> String {
-    // For demonstration - in a real implementation, this would call the actual chown module
-    if args.len() < 2 {
-        return "Usage: chown <owner> <file>".to_string();
-    }
-
-    let owner = args[0];
-    let file = args[1];
-
-    // Check if file exists
-    if std::path::Path::new(file).exists() {
-        format!("Changed ownership of '{}' to '{}'", file, owner)
-    } else {
-        format!("File '{}' not found", file)
-    }
-}
-
-fn capture_git_output(args: &[&str]) -> String {
-    use std::process::Command;
-
-    // Check if git is available
-    if !crate::git::is_git_available() {
-        return "Error: Git is not installed or not in PATH".to_string();
-    }
-
-    match Command::new("git").args(args).output() {
-        Ok(output) => {
-            let mut result = String::new();
-
-            // Add stdout
-            if !output.stdout.is_empty() {
-                result.push_str(&String::from_utf8_lossy(&output.stdout));
-            }
-
-            // Add stderr
-            if !output.stderr.is_empty() {
-                if !result.is_empty() {
-                    result.push('\n');
-                }
-                result.push_str(&String::from_utf8_lossy(&output.stderr));
-            }
-
-            // If command failed and no output, add error message
-            if !output.status.success() && result.is_empty() {
-                if let Some(code) = output.status.code() {
-                    result = format!("Git command failed with exit code: {}", code);
-                } else {
-                    result = "Git command failed".to_string();
-                }
-            }
-
-            result
-        }
-        Err(e) => {
-            format!("Failed to execute git command: {}", e)
-        }
-    }
-}
-
-fn capture_powershell_output(args: &[&str]) -> String {
-    use std::process::Command;
-
-    // Check if PowerShell is available
-    if !crate::powershell::is_powershell_available() {
-        return "Error: PowerShell is not available on this system".to_string();
-    }
-
-    // Get the preferred PowerShell executable
-    let ps_exe = if is_command_available("pwsh") {
-        "pwsh"
-    } else {
-        "powershell"
-    };
-
-    let command_string = args.join(" ");
-
-    match Command::new(ps_exe)
-        .args(&["-Command", &command_string])
-        .output()
-    {
-        Ok(output) => {
-            let mut result = String::new();
-
-            // Add stdout
-            if !output.stdout.is_empty() {
-                result.push_str(&String::from_utf8_lossy(&output.stdout));
-            }
-
-            // Add stderr
-            if !output.stderr.is_empty() {
-                if !result.is_empty() {
-                    result.push('\n');
-                }
-                result.push_str(&String::from_utf8_lossy(&output.stderr));
-            }
-
-            // If command failed and no output, add error message
-            if !output.status.success() && result.is_empty() {
-                if let Some(code) = output.status.code() {
-                    result = format!("PowerShell command failed with exit code: {}", code);
-                } else {
-                    result = "PowerShell command failed".to_string();
-                }
-            }
-
-            result
-        }
-        Err(e) => {
-            format!("Failed to execute PowerShell command: {}", e)
-        }
-    }
-}
-
-fn is_command_available(cmd: &str) -> bool {
-    use std::process::Command;
-    match Command::new(cmd)
-        .arg("-Command")
-        .arg("$PSVersionTable.PSVersion")
-        .output()
-    {
-        Ok(output) => output.status.success(),
-        Err(_) => false,
-    }
-}
-
 fn render_git_info(f: &mut Frame, area: Rect) {
     // Check if we're in a git repository
     let is_git_repo = crate::git::is_git_repo();
@@ -1423,7 +1032,7 @@ fn render_git_status_detailed(f: &mut Frame, area: Rect) {
     use std::process::Command;
 
     let status_output = Command::new("git")
-        .args(&["status", "--porcelain"])
+        .args(["status", "--porcelain"])
         .output()
         .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
         .unwrap_or_else(|_| "Error getting status".to_string());
@@ -1478,7 +1087,7 @@ fn render_git_recent_commits(f: &mut Frame, area: Rect) {
     use std::process::Command;
 
     let log_output = Command::new("git")
-        .args(&["log", "--oneline", "-10"])
+        .args(["log", "--oneline", "-10"])
         .output()
         .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
         .unwrap_or_else(|_| "Error getting log".to_string());
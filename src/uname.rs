@@ -1,45 +1,83 @@
+use crate::commands::{self, Command};
+use std::io::{self, Read, Write};
 use sysinfo::{Networks, System};
 
-pub fn execute() {
+pub struct Uname;
+
+impl Command for Uname {
+    fn name(&self) -> &'static str {
+        "uname"
+    }
+
+    fn usage(&self) -> &'static str {
+        "uname"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show system information"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
     let mut sys = System::new_all();
 
     sys.refresh_all();
     // Display system information (handle Option types):
-    println!(
+    writeln!(
+        out,
         "System name:             {}",
         System::name().unwrap_or_else(|| "Unknown".to_string())
-    );
+    )?;
 
-    println!("System kernel version:   {}", System::kernel_long_version());
+    writeln!(
+        out,
+        "System kernel version:   {}",
+        System::kernel_long_version()
+    )?;
 
-    println!(
+    writeln!(
+        out,
         "System OS version:       {}",
         System::long_os_version().unwrap_or_else(|| "Unknown".to_string())
-    );
+    )?;
 
-    println!(
+    writeln!(
+        out,
         "System host name:        {}",
         System::host_name().unwrap_or_else(|| "Unknown".to_string())
-    );
+    )?;
 
-    println!("CPUs:         {}", sys.cpus().len());
-    println!("CPU usage:    {}", sys.global_cpu_usage());
-    println!("CPU Architecture: {:?}", System::cpu_arch());
-    println!(
+    writeln!(out, "CPUs:         {}", sys.cpus().len())?;
+    writeln!(out, "CPU usage:    {}", sys.global_cpu_usage())?;
+    writeln!(out, "CPU Architecture: {:?}", System::cpu_arch())?;
+    writeln!(
+        out,
         "Physical cores: {}",
         System::physical_core_count().map_or("Unknown".to_string(), |count| count.to_string())
-    );
+    )?;
 
     // Network interfaces with formatted data:
     let networks = Networks::new_with_refreshed_list();
-    println!("\nNetworks:");
+    writeln!(out, "\nNetworks:")?;
     for (interface_name, data) in &networks {
-        println!(
+        writeln!(
+            out,
             "{interface_name}: {} (down) / {} (up)",
             format_memory(data.total_received()),
             format_memory(data.total_transmitted()),
-        );
+        )?;
     }
+    Ok(())
 }
 
 // Helper function to format bytes into human-readable format
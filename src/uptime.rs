@@ -1,14 +1,44 @@
+use crate::commands::{self, Command};
+use std::io::{self, Read, Write};
 use sysinfo::System;
 
-pub fn execute() {
+pub struct Uptime;
+
+impl Command for Uptime {
+    fn name(&self) -> &'static str {
+        "uptime"
+    }
+
+    fn usage(&self) -> &'static str {
+        "uptime"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show how long the system has been running"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        commands::exit_code(stderr, self.name(), execute(stdout))
+    }
+}
+
+pub fn execute(out: &mut dyn Write) -> io::Result<()> {
     let mut sys = System::new_all();
     let load_avg = System::load_average();
     sys.refresh_all();
 
-    println!("System booted at {} seconds", System::boot_time());
-    println!("System running since {} seconds", System::uptime());
-    println!(
+    writeln!(out, "System booted at {} seconds", System::boot_time())?;
+    writeln!(out, "System running since {} seconds", System::uptime())?;
+    writeln!(
+        out,
         "one minute: {}%, five minutes: {}%, fifteen minutes: {}%",
         load_avg.one, load_avg.five, load_avg.fifteen,
-    );
+    )?;
+    Ok(())
 }
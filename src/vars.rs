@@ -0,0 +1,371 @@
+//! Shell variables and the environment passed to child processes, with the
+//! `export`, `unset`, `env` and `set` built-ins.
+//!
+//! The variables live in one store for the whole process, seeded from the
+//! environment winix was started with, so that every place that spawns a
+//! program can hand it the exported variables.
+
+use crate::commands::{self, Command};
+use crate::options::{self, ShellOption, Shopt};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug, Clone)]
+struct Variable {
+    value: String,
+    exported: bool,
+}
+
+fn store() -> &'static RwLock<BTreeMap<String, Variable>> {
+    static STORE: OnceLock<RwLock<BTreeMap<String, Variable>>> = OnceLock::new();
+    STORE.get_or_init(|| {
+        let variables = std::env::vars()
+            .map(|(name, value)| {
+                let variable = Variable {
+                    value,
+                    exported: true,
+                };
+                (name, variable)
+            })
+            .collect();
+        RwLock::new(variables)
+    })
+}
+
+fn read() -> RwLockReadGuard<'static, BTreeMap<String, Variable>> {
+    store().read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write() -> RwLockWriteGuard<'static, BTreeMap<String, Variable>> {
+    store().write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The value of a variable, if it is set.
+pub fn get(name: &str) -> Option<String> {
+    read().get(name).map(|variable| variable.value.clone())
+}
+
+/// Sets a variable, keeping it exported if it already was.
+pub fn set(name: &str, value: &str) {
+    write()
+        .entry(name.to_string())
+        .and_modify(|variable| variable.value = value.to_string())
+        .or_insert_with(|| Variable {
+            value: value.to_string(),
+            exported: false,
+        });
+}
+
+/// Marks a variable as exported, setting it first if a value is given.
+pub fn export(name: &str, value: Option<&str>) {
+    let mut variables = write();
+    let variable = variables.entry(name.to_string()).or_insert(Variable {
+        value: String::new(),
+        exported: true,
+    });
+    variable.exported = true;
+    if let Some(value) = value {
+        variable.value = value.to_string();
+    }
+}
+
+pub fn unset(name: &str) {
+    write().remove(name);
+}
+
+/// Every variable as it is now, to put back with [`restore`].
+#[derive(Debug)]
+pub struct Saved(BTreeMap<String, Variable>);
+
+/// Saves the variables before running commands, such as those of a `$(...)`,
+/// whose assignments must not outlive them.
+pub fn save() -> Saved {
+    Saved(read().clone())
+}
+
+/// Puts back the variables saved by [`save`], dropping any set since.
+pub fn restore(saved: Saved) {
+    *write() = saved.0;
+}
+
+/// Every variable with its value, sorted by name.
+pub fn all() -> Vec<(String, String)> {
+    read()
+        .iter()
+        .map(|(name, variable)| (name.clone(), variable.value.clone()))
+        .collect()
+}
+
+/// The exported variables, which make up the environment of child processes.
+pub fn exported() -> Vec<(String, String)> {
+    read()
+        .iter()
+        .filter(|(_, variable)| variable.exported)
+        .map(|(name, variable)| (name.clone(), variable.value.clone()))
+        .collect()
+}
+
+/// The variables that are not exported, which child processes do not see.
+pub fn unexported() -> Vec<(String, String)> {
+    read()
+        .iter()
+        .filter(|(_, variable)| !variable.exported)
+        .map(|(name, variable)| (name.clone(), variable.value.clone()))
+        .collect()
+}
+
+/// Gives a child process the exported variables as its environment.
+pub fn apply(command: &mut std::process::Command) {
+    command.env_clear().envs(exported());
+}
+
+/// Whether `name` can be used as a variable name.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits a `NAME=value` word into its name and value.
+pub fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=').filter(|(name, _)| is_valid_name(name))
+}
+
+/// Quotes a value so that it reads back as a single word.
+pub fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:,+=@%\\".contains(c))
+    {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+fn invalid_name(stderr: &mut dyn Write, command: &str, name: &str) -> i32 {
+    commands::report_error(
+        stderr,
+        command,
+        format!("'{}': not a valid identifier", name),
+    )
+}
+
+/// `export [NAME[=VALUE]]...`
+pub struct Export;
+
+impl Command for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn usage(&self) -> &'static str {
+        "export [NAME[=VALUE]]..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Set environment variables for child processes"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        if args.is_empty() {
+            let listed: io::Result<()> = exported()
+                .iter()
+                .try_for_each(|(name, value)| writeln!(stdout, "export {}={}", name, quote(value)));
+            return commands::exit_code(stderr, self.name(), listed);
+        }
+
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if is_valid_name(name) {
+                export(name, value);
+            } else {
+                status = invalid_name(stderr, self.name(), name);
+            }
+        }
+        status
+    }
+}
+
+/// `unset NAME...`
+pub struct Unset;
+
+impl Command for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn usage(&self) -> &'static str {
+        "unset NAME..."
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove variables"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let mut status = 0;
+        for name in args {
+            if is_valid_name(name) {
+                unset(name);
+            } else {
+                status = invalid_name(stderr, self.name(), name);
+            }
+        }
+        status
+    }
+}
+
+/// `env`
+pub struct Env;
+
+impl Command for Env {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn usage(&self) -> &'static str {
+        "env"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the environment"
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        let listed: io::Result<()> = exported()
+            .iter()
+            .try_for_each(|(name, value)| writeln!(stdout, "{}={}", name, value));
+        commands::exit_code(stderr, self.name(), listed)
+    }
+}
+
+/// `set [-e|+e] [-o OPTION|+o OPTION]`
+pub struct Set;
+
+impl Command for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn usage(&self) -> &'static str {
+        "set [-e|+e] [-o OPTION|+o OPTION]"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print shell variables, or turn options on (-) or off (+)"
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> i32 {
+        if args.is_empty() {
+            let listed: io::Result<()> = all()
+                .iter()
+                .try_for_each(|(name, value)| writeln!(stdout, "{}={}", name, quote(value)));
+            return commands::exit_code(stderr, self.name(), listed);
+        }
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let on = arg.starts_with('-');
+            let option = match arg.as_str() {
+                "-e" | "+e" => Some(ShellOption::ErrExit),
+                "-o" | "+o" => match args.next() {
+                    Some(name) => match ShellOption::from_name(name) {
+                        Some(option) => Some(option),
+                        None => {
+                            return commands::report_error(
+                                stderr,
+                                self.name(),
+                                format!("{}: invalid option name", name),
+                            );
+                        }
+                    },
+                    // `set -o` on its own lists the options
+                    None => return Shopt.run(&[], stdin, stdout, stderr),
+                },
+                _ => None,
+            };
+            match option {
+                Some(option) => options::set(option, on),
+                None => return commands::usage_error(stderr, self.usage()),
+            }
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(command: &dyn Command, args: &[&str]) -> (i32, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut output = Vec::new();
+        let code = command.run(&args, &mut io::empty(), &mut output, &mut io::sink());
+        (code, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_set_export_unset() {
+        set("WINIX_TEST_LOCAL", "one");
+        assert_eq!(get("WINIX_TEST_LOCAL").as_deref(), Some("one"));
+        assert!(!run(&Env, &[]).1.contains("WINIX_TEST_LOCAL="));
+        assert!(run(&Set, &[]).1.contains("WINIX_TEST_LOCAL=one\n"));
+
+        assert_eq!(
+            run(&Export, &["WINIX_TEST_LOCAL", "WINIX_TEST_NEW=a b"]).0,
+            0
+        );
+        let env = run(&Env, &[]).1;
+        assert!(env.contains("WINIX_TEST_LOCAL=one\n"));
+        assert!(env.contains("WINIX_TEST_NEW=a b\n"));
+        assert!(
+            run(&Export, &[])
+                .1
+                .contains("export WINIX_TEST_NEW='a b'\n")
+        );
+
+        assert_eq!(run(&Unset, &["WINIX_TEST_LOCAL", "WINIX_TEST_NEW"]).0, 0);
+        assert_eq!(get("WINIX_TEST_LOCAL"), None);
+        assert!(!exported().iter().any(|(name, _)| name == "WINIX_TEST_NEW"));
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        assert_eq!(run(&Export, &["1X=y"]).0, 1);
+        assert_eq!(run(&Unset, &["a-b"]).0, 1);
+        assert_eq!(parse_assignment("A_1=x=y"), Some(("A_1", "x=y")));
+        assert_eq!(parse_assignment("=x"), None);
+        assert_eq!(parse_assignment("--opt=x"), None);
+    }
+}
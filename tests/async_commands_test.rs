@@ -4,10 +4,14 @@ mod tests {
         cat::{cat_async_to_string, benchmark_cat_sync_vs_async},
         grep::{grep_async_to_string, grep_sync},
         head::{head_async_to_string, head_sync},
-        tail::{tail_async_to_string, tail_sync},
-        pipeline::{CatGrepPipeline, CatHeadPipeline, execute_pipeline},
+        tail::tail_async_to_string,
+        pipeline::{parse_stage, run_stages_to_string},
     };
 
+    fn argv(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
     #[tokio::test]
     async fn test_grep_async_basic() {
         let file_path = "test_grep_basic.txt";
@@ -64,12 +68,12 @@ mod tests {
 
         tokio::fs::write(file_path, content).await.unwrap();
 
-        let pipeline = CatGrepPipeline::new(
-            vec![file_path.to_string()],
-            "hello".to_string(),
-        );
+        let stages = vec![
+            parse_stage(&argv(&["cat", file_path])).unwrap(),
+            parse_stage(&argv(&["grep", "hello"])).unwrap(),
+        ];
 
-        let result = execute_pipeline(pipeline).await.unwrap();
+        let result = run_stages_to_string(stages).await.unwrap();
         assert!(result.contains("hello world"));
         assert!(result.contains("hello again"));
         assert!(!result.contains("bye world"));
@@ -84,9 +88,12 @@ mod tests {
 
         tokio::fs::write(file_path, content).await.unwrap();
 
-        let pipeline = CatHeadPipeline::new(vec![file_path.to_string()], 3);
+        let stages = vec![
+            parse_stage(&argv(&["cat", file_path])).unwrap(),
+            parse_stage(&argv(&["head", "-n", "3"])).unwrap(),
+        ];
 
-        let result = execute_pipeline(pipeline).await.unwrap();
+        let result = run_stages_to_string(stages).await.unwrap();
         let lines: Vec<&str> = result.lines().collect();
         assert_eq!(lines.len(), 3);
         assert_eq!(lines[0], "line 1");
@@ -0,0 +1,245 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn winix(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_winix"))
+        .args(args)
+        .output()
+        .expect("failed to run winix")
+}
+
+fn winix_with_input(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_winix"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run winix");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+    drop(stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_command_option() {
+    let output = winix(&["-c", "echo one; echo two | grep two"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "one\ntwo\n");
+
+    let output = winix(&["-c", "cat test_script_missing.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = winix(&["-c", "exit 3; echo unreachable"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn test_pipeline_reads_stdin() {
+    for (line, expected) in [
+        ("grep a", "a\n"),
+        ("cat | grep b", "b\n"),
+        ("head -n 1 | grep a", "a\n"),
+        ("tail -n 1 | cat", "b\n"),
+    ] {
+        let output = winix_with_input(&["-c", line], "a\nb\n");
+        assert_eq!(output.status.code(), Some(0), "{}", line);
+        assert_eq!(stdout(&output), expected, "{}", line);
+    }
+}
+
+#[test]
+fn test_script_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("build.wx");
+    fs::write(
+        &script,
+        "#!/usr/bin/env winix\n\
+         # Comments and continuations\n\
+         echo first \\\n  second # trailing comment\n\
+         cat test_script_missing.txt || echo recovered\n\
+         set -e\n\
+         false_command_that_does_not_exist\n\
+         echo unreachable\n",
+    )
+    .unwrap();
+
+    let output = winix(&[script.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(127));
+    assert_eq!(stdout(&output), "first second\nrecovered\n");
+}
+
+#[test]
+fn test_script_control_flow() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("args.wx");
+    fs::write(
+        &script,
+        "set -e\n\
+         count() {\n\
+           echo \"$# \"\n\
+         }\n\
+         count \"$@\"\n\
+         # A failing condition does not end the script\n\
+         if false; then\n\
+           echo never\n\
+         else\n\
+           echo \"[$1]\"\n\
+         fi\n\
+         for arg in \"$@\"\n\
+         do\n\
+           case $arg in\n\
+             *.log) echo \"log \" ;;\n\
+             *) echo \"other \" ;;\n\
+           esac\n\
+         done\n\
+         exit $#\n",
+    )
+    .unwrap();
+
+    let output = winix(&[script.to_str().unwrap(), "a b", "c.log"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "2 \n[a b]\nother \nlog \n");
+}
+
+#[test]
+fn test_script_ending_in_an_operator() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("dangling.wx");
+    fs::write(&script, "echo a\necho b |\n").unwrap();
+
+    let output = winix(&[script.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "a\n");
+    let errors = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        errors.contains("unexpected end of input after '|'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("\n  echo b |\n"), "{}", errors);
+}
+
+#[test]
+fn test_missing_script() {
+    let output = winix(&["test_script_missing.wx"]);
+    assert_eq!(output.status.code(), Some(127));
+}
+
+#[test]
+fn test_background_jobs() {
+    // Jobs get the shell's variables, functions and arguments, but what
+    // they change stays in the job
+    let output = winix(&[
+        "-c",
+        "cd / & WINIX_X=2 & greet() { echo \"hi $1 $WINIX_Y\"; }\n\
+         WINIX_Y=there; greet $1 & wait; pwd; echo \"[$WINIX_X]\"",
+        "you",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+        stdout(&output),
+        format!("hi you there\n{}\n[]\n", cwd.display())
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[1] cd /\n[2] WINIX_X=2\n[3] greet $1\n"));
+
+    let output = winix(&[
+        "-c",
+        "cat test_script_missing.txt & fg %cat; echo \"fg $?\"\n\
+         exit 3 & wait $!; echo \"wait $?\"; fg",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stdout(&output),
+        "cat test_script_missing.txt\nfg 1\nwait 3\n"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.ends_with("fg: current: no such job\n"));
+
+    let output = winix(&[
+        "-c",
+        "sleep 10 & WINIX_PID=$!; exit 2 & wait %2\n\
+         jobs %1; jobs %1 %9; echo \"jobs $?\"; kill $WINIX_PID",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let running = "[1]+  Running                 sleep 10 &\n";
+    assert_eq!(stdout(&output), format!("{}{}jobs 1\n", running, running));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.ends_with("jobs: %9: no such job\n"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stopped_jobs() {
+    let output = winix(&[
+        "-c",
+        "sleep 10 & kill -STOP $!; wait $!; echo \"wait $?\"; jobs; bg; jobs; kill -- -$!",
+    ]);
+    assert_eq!(
+        stdout(&output),
+        "wait 148\n\
+         [1]+  Stopped                 sleep 10\n\
+         [1]+  Running                 sleep 10 &\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[1]+ sleep 10 &\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_disowned_jobs_outlive_the_shell() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("output");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_winix"))
+        .arg("--cli")
+        .env("HOME", dir.path())
+        .env("HISTFILE", dir.path().join("history"))
+        .stdin(std::process::Stdio::piped())
+        // A pipe would stay open as long as the disowned job runs
+        .stdout(fs::File::create(&output_path).unwrap())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(
+        child.stdin.as_mut().unwrap(),
+        b"sleep 10 &\necho \"kept $!\"\ndisown\nsleep 10 &\necho \"ended $!\"\nexit\n",
+    )
+    .unwrap();
+    child.wait().unwrap();
+
+    let output = fs::read_to_string(&output_path).unwrap();
+    let pid = |label: &str| {
+        let line = output.lines().find(|line| line.starts_with(label)).unwrap();
+        line[label.len()..].to_string()
+    };
+    let alive = |pid: &str| {
+        Command::new("kill")
+            .args(["-0", pid])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    };
+    let (kept, ended) = (pid("kept "), pid("ended "));
+    assert!(alive(&kept));
+    Command::new("kill")
+        .args(["--", &format!("-{}", kept)])
+        .status()
+        .unwrap();
+    // The other job was hung up when the shell exited, and may take a moment
+    // to be collected
+    let mut tries = 0;
+    while alive(&ended) && tries < 50 {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        tries += 1;
+    }
+    assert!(!alive(&ended));
+}
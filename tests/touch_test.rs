@@ -12,7 +12,13 @@ fn test_touch_creates_file() {
     }
 
     // Call touch::run directly
-    touch::run(&vec![filename.to_string()]);
+    let mut output = Vec::new();
+    let status = touch::run(&[filename.to_string()], &mut output, &mut std::io::sink());
+    assert_eq!(status, 0);
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        format!("Created '{}'\n", filename)
+    );
 
     // Check file exists
     assert!(Path::new(filename).exists(), "File was not created");
@@ -20,3 +26,20 @@ fn test_touch_creates_file() {
     // Cleanup after
     fs::remove_file(filename).unwrap();
 }
+
+#[test]
+fn test_touch_reports_failure() {
+    let mut errors = Vec::new();
+    let status = touch::run(
+        &["test_missing_dir/file.txt".to_string()],
+        &mut std::io::sink(),
+        &mut errors,
+    );
+
+    assert_eq!(status, 1);
+    assert!(
+        String::from_utf8(errors)
+            .unwrap()
+            .starts_with("touch: cannot create file")
+    );
+}